@@ -12,4 +12,13 @@ fn main() {
         }
         _ => {}
     };
+    #[cfg(feature = "server")]
+    compile_grpc_service();
+}
+
+/// Generates the messages and service stubs of `proto/annoy.proto` with a vendored `protoc`.
+#[cfg(feature = "server")]
+fn compile_grpc_service() {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+    tonic_prost_build::compile_protos("proto/annoy.proto").unwrap();
 }
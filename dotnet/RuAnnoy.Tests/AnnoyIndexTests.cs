@@ -1,5 +1,6 @@
 ﻿using System;
 using System.Collections.Generic;
+using System.IO;
 using System.Linq;
 using FluentAssertions;
 using NUnit.Framework;
@@ -26,6 +27,59 @@ namespace RuAnnoy.Tests
             index.Should().BeNull();
         }
 
+        [Test]
+        public void TestLoadFromBuffer()
+        {
+            var path = $"index.angular.{TEST_INDEX_DIM}d.ann";
+            foreach (var index in new[] {
+                AnnoyIndex.LoadFromBuffer(File.ReadAllBytes(path), TEST_INDEX_DIM, IndexType.Angular),
+                AnnoyIndex.LoadIntoMemory(path, TEST_INDEX_DIM, IndexType.Angular),
+            })
+            {
+                index.Should().NotBeNull();
+                using (index)
+                {
+                    index!.Size.Should().Be(TEST_NODE_COUNT);
+                    index.GetNearestToItem(0, 5, -1, false).IdList.ToArray().Should().BeEquivalentTo(new long[] { 0, 4, 37, 61, 29 });
+                }
+            }
+        }
+
+        [Test]
+        public void TestGetNearestBatch()
+        {
+            using IAnnoyIndex? index = AnnoyIndex.Load($"index.angular.{TEST_INDEX_DIM}d.ann", TEST_INDEX_DIM, IndexType.Angular);
+            index.Should().NotBeNull();
+
+            var queries = new[] { index!.GetItemVector(0), index.GetItemVector(3) };
+            var results = index.GetNearestBatch(queries, 5, -1, true);
+            results.Count.Should().Be(2);
+            for (var i = 0; i < queries.Length; i++)
+            {
+                var expected = index.GetNearest(queries[i], 5, -1, true);
+                results[i].Count.Should().Be(expected.Count);
+                results[i].IdList.ToArray().Should().Equal(expected.IdList.ToArray());
+                results[i].DistanceList.ToArray().Should().Equal(expected.DistanceList.ToArray());
+            }
+
+            var nearest = index.GetNearestToItem(3, 5, -1, true);
+            for (var i = 0; i < nearest.Count; i++)
+            {
+                var distance = index.GetDistance(3, (ulong)nearest.IdList.Span[i]);
+                RoundTo(distance).Should().Be(RoundTo(nearest.DistanceList.Span[i]));
+            }
+        }
+
+        [Test]
+        public void TestOutOfRange()
+        {
+            using IAnnoyIndex? index = AnnoyIndex.Load($"index.angular.{TEST_INDEX_DIM}d.ann", TEST_INDEX_DIM, IndexType.Angular);
+            index.Should().NotBeNull();
+
+            index!.Invoking(i => i.GetItemVector(TEST_NODE_COUNT)).Should().Throw<ArgumentOutOfRangeException>();
+            index.Invoking(i => i.GetDistance(0, TEST_NODE_COUNT)).Should().Throw<ArgumentOutOfRangeException>();
+        }
+
         [Test]
         public void TestAngular()
         {
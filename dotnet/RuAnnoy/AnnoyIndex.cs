@@ -33,6 +33,32 @@ namespace RuAnnoy
             IndexType type)
         {
             var indexPtr = NativeMethods.LoadAnnoyIndex(path, dimension, type);
+            return FromPtr(indexPtr, dimension, type);
+        }
+
+        public static IAnnoyIndex? LoadIntoMemory(
+            string path,
+            int dimension,
+            IndexType type)
+        {
+            var indexPtr = NativeMethods.LoadAnnoyIndexIntoMem(path, dimension, type);
+            return FromPtr(indexPtr, dimension, type);
+        }
+
+        public static IAnnoyIndex? LoadFromBuffer(
+            byte[] buffer,
+            int dimension,
+            IndexType type)
+        {
+            var indexPtr = NativeMethods.LoadAnnoyIndexFromBuffer(buffer, (ulong)buffer.LongLength, dimension, type);
+            return FromPtr(indexPtr, dimension, type);
+        }
+
+        private static IAnnoyIndex? FromPtr(
+            IntPtr indexPtr,
+            int dimension,
+            IndexType type)
+        {
             if (indexPtr != IntPtr.Zero)
             {
                 return new AnnoyIndex(indexPtr, dimension, type);
@@ -51,10 +77,21 @@ namespace RuAnnoy
             }
 
             var itemVector = new float[Dimension];
-            NativeMethods.GetItemVector(_indexPtr, itemIndex, itemVector);
+            NativeMethods.Check(NativeMethods.TryGetItemVector(_indexPtr, itemIndex, itemVector));
             return itemVector;
         }
 
+        public float GetDistance(ulong i, ulong j)
+        {
+            if (_indexPtr == IntPtr.Zero)
+            {
+                throw new ObjectDisposedException("index");
+            }
+
+            NativeMethods.Check(NativeMethods.TryGetDistance(_indexPtr, i, j, out var distance));
+            return distance;
+        }
+
         public AnnoyIndexSearchResult GetNearest(
             IReadOnlyList<float> queryVector,
             uint nResult,
@@ -109,6 +146,54 @@ namespace RuAnnoy
             }
         }
 
+        public IReadOnlyList<AnnoyIndexSearchResult> GetNearestBatch(
+            IReadOnlyList<IReadOnlyList<float>> queryVectors,
+            uint nResult,
+            int searchK,
+            bool shouldIncludeDistance)
+        {
+            if (_indexPtr == IntPtr.Zero)
+            {
+                throw new ObjectDisposedException("index");
+            }
+
+            var nQueries = queryVectors.Count;
+            var vectors = new float[nQueries * Dimension];
+            for (var i = 0; i < nQueries; i++)
+            {
+                if (queryVectors[i].Count != Dimension)
+                {
+                    throw new ArgumentException($"Wrong input dimension, {Dimension} expected, {queryVectors[i].Count} provided.", nameof(queryVectors));
+                }
+
+                for (var j = 0; j < Dimension; j++)
+                {
+                    vectors[i * Dimension + j] = queryVectors[i][j];
+                }
+            }
+
+            var idList = new long[nQueries * nResult];
+            var distanceList = shouldIncludeDistance ? new float[nQueries * nResult] : null;
+            var countList = new ulong[nQueries];
+            NativeMethods.Check(NativeMethods.TryGetNearestBatch(
+                _indexPtr,
+                vectors,
+                (ulong)nQueries,
+                nResult,
+                searchK,
+                idList,
+                distanceList,
+                countList));
+
+            var results = new AnnoyIndexSearchResult[nQueries];
+            for (var i = 0; i < nQueries; i++)
+            {
+                results[i] = AnnoyIndexSearchResult.FromBatch(idList, distanceList, i * (int)nResult, (int)countList[i]);
+            }
+
+            return results;
+        }
+
         protected override void DisposeResources()
         {
             NativeMethods.FreeAnnoyIndex(_indexPtr);
@@ -17,6 +17,27 @@ namespace RuAnnoy
 
         public ReadOnlyMemory<float> DistanceList { get; private set; }
 
+        internal static AnnoyIndexSearchResult FromBatch(
+            long[] idList,
+            float[]? distanceList,
+            int offset,
+            int count)
+        {
+            var result = new AnnoyIndexSearchResult
+            {
+                Count = count,
+                IsDistanceIncluded = distanceList != null,
+                IdList = new ReadOnlyMemory<long>(idList, offset, count),
+            };
+
+            if (distanceList != null)
+            {
+                result.DistanceList = new ReadOnlyMemory<float>(distanceList, offset, count);
+            }
+
+            return result;
+        }
+
         internal static AnnoyIndexSearchResult LoadFromPtr(IntPtr searchResult, bool isDistanceIncluded)
         {
             var count = (int)NativeMethods.GetResultCount(searchResult);
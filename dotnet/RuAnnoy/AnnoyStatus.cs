@@ -0,0 +1,12 @@
+﻿namespace RuAnnoy
+{
+    internal enum AnnoyStatus
+    {
+        Ok = 0,
+        NullPointer = 1,
+        InvalidArgument = 2,
+        IndexOutOfRange = 3,
+        LoadFailed = 4,
+        Panic = 5,
+    }
+}
@@ -11,6 +11,8 @@ namespace RuAnnoy
 
         IReadOnlyList<float> GetItemVector(ulong itemIndex);
 
+        float GetDistance(ulong i, ulong j);
+
         AnnoyIndexSearchResult GetNearest(
             IReadOnlyList<float> queryVector,
             uint nResult,
@@ -22,5 +24,11 @@ namespace RuAnnoy
             uint nResult,
             int searchK,
             bool shouldIncludeDistance);
+
+        IReadOnlyList<AnnoyIndexSearchResult> GetNearestBatch(
+            IReadOnlyList<IReadOnlyList<float>> queryVectors,
+            uint nResult,
+            int searchK,
+            bool shouldIncludeDistance);
     }
 }
@@ -13,6 +13,19 @@ namespace RuAnnoy
             Int32 dimension,
             IndexType indexType);
 
+        [DllImport(DLLPATH, EntryPoint = "load_annoy_index_into_mem", CharSet = CharSet.Ansi)]
+        internal static extern IntPtr LoadAnnoyIndexIntoMem(
+            string path,
+            Int32 dimension,
+            IndexType indexType);
+
+        [DllImport(DLLPATH, EntryPoint = "load_annoy_index_from_buffer", CharSet = CharSet.Ansi)]
+        internal static extern IntPtr LoadAnnoyIndexFromBuffer(
+            byte[] buffer,
+            ulong bufferLength,
+            Int32 dimension,
+            IndexType indexType);
+
         [DllImport(DLLPATH, EntryPoint = "free_annoy_index", CharSet = CharSet.Ansi)]
         internal static extern void FreeAnnoyIndex(IntPtr index);
 
@@ -22,8 +35,8 @@ namespace RuAnnoy
         [DllImport(DLLPATH, EntryPoint = "get_size", CharSet = CharSet.Ansi)]
         internal static extern ulong GetSize(IntPtr index);
 
-        [DllImport(DLLPATH, EntryPoint = "get_item_vector", CharSet = CharSet.Ansi)]
-        internal static extern void GetItemVector(IntPtr index, ulong itemIndex, [Out] float[] itemVector);
+        [DllImport(DLLPATH, EntryPoint = "try_get_item_vector", CharSet = CharSet.Ansi)]
+        internal static extern AnnoyStatus TryGetItemVector(IntPtr index, ulong itemIndex, [Out] float[] itemVector);
 
         [DllImport(DLLPATH, EntryPoint = "get_nearest", CharSet = CharSet.Ansi)]
         internal static extern IntPtr GetNearest(
@@ -41,6 +54,20 @@ namespace RuAnnoy
             int searchK,
             bool shouldIncludeDistance);
 
+        [DllImport(DLLPATH, EntryPoint = "try_get_nearest_batch", CharSet = CharSet.Ansi)]
+        internal static extern AnnoyStatus TryGetNearestBatch(
+            IntPtr index,
+            float[] vectors,
+            ulong nQueries,
+            uint nResults,
+            int searchK,
+            [Out] long[] idList,
+            [Out] float[]? distanceList,
+            [Out] ulong[] countList);
+
+        [DllImport(DLLPATH, EntryPoint = "try_get_distance", CharSet = CharSet.Ansi)]
+        internal static extern AnnoyStatus TryGetDistance(IntPtr index, ulong i, ulong j, out float distance);
+
         [DllImport(DLLPATH, EntryPoint = "free_search_result", CharSet = CharSet.Ansi)]
         internal static extern void FreeSearchResult(IntPtr searchResult);
 
@@ -52,5 +79,31 @@ namespace RuAnnoy
 
         [DllImport(DLLPATH, EntryPoint = "get_distance_list", CharSet = CharSet.Ansi)]
         internal static extern IntPtr GetDistanceList(IntPtr searchResult);
+
+        [DllImport(DLLPATH, EntryPoint = "annoy_last_error", CharSet = CharSet.Ansi)]
+        internal static extern IntPtr AnnoyLastError();
+
+        /// <summary>
+        /// Throws with the message of <c>annoy_last_error</c> unless <paramref name="status"/> is <see cref="AnnoyStatus.Ok"/>.
+        /// </summary>
+        internal static void Check(AnnoyStatus status)
+        {
+            if (status == AnnoyStatus.Ok)
+            {
+                return;
+            }
+
+            var message = Marshal.PtrToStringAnsi(AnnoyLastError()) ?? status.ToString();
+            switch (status)
+            {
+                case AnnoyStatus.IndexOutOfRange:
+                    throw new ArgumentOutOfRangeException(null, message);
+                case AnnoyStatus.NullPointer:
+                case AnnoyStatus.InvalidArgument:
+                    throw new ArgumentException(message);
+                default:
+                    throw new InvalidOperationException(message);
+            }
+        }
     }
 }
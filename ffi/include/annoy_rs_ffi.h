@@ -0,0 +1,183 @@
+#ifndef ANNOY_RS_FFI_H
+#define ANNOY_RS_FFI_H
+
+/* Generated by cbindgen from ffi/src, do not edit manually. */
+
+#include <stdbool.h>
+#include <stdint.h>
+
+typedef struct AnnoyIndex AnnoyIndex;
+typedef struct AnnoyIndexSearchResult AnnoyIndexSearchResult;
+
+// Status code returned by the `try_*` functions, details of a failure are
+// available via [`annoy_last_error`].
+typedef enum AnnoyStatus {
+  ANNOY_STATUS_OK = 0,
+  ANNOY_STATUS_NULL_POINTER = 1,
+  ANNOY_STATUS_INVALID_ARGUMENT = 2,
+  ANNOY_STATUS_INDEX_OUT_OF_RANGE = 3,
+  ANNOY_STATUS_LOAD_FAILED = 4,
+  ANNOY_STATUS_PANIC = 5,
+} AnnoyStatus;
+
+// Returns null on failure, see `annoy_last_error` for the reason.
+const AnnoyIndex *load_annoy_index(const char *path, int32_t dimension, uint8_t index_type);
+
+// Same as `load_annoy_index`, but reads the whole file into memory instead of memory mapping it.
+const AnnoyIndex *load_annoy_index_into_mem(const char *path,
+                                            int32_t dimension,
+                                            uint8_t index_type);
+
+// Loads an index from `buffer_len` bytes at `buffer`, which are copied so the caller keeps ownership.
+const AnnoyIndex *load_annoy_index_from_buffer(const uint8_t *buffer,
+                                               uint64_t buffer_len,
+                                               int32_t dimension,
+                                               uint8_t index_type);
+
+void free_annoy_index(const AnnoyIndex *index);
+
+int32_t get_dimension(const AnnoyIndex *index_ptr);
+
+uint64_t get_size(const AnnoyIndex *index_ptr);
+
+void get_item_vector(const AnnoyIndex *index_ptr, uint64_t item_index, float *item_vector);
+
+const AnnoyIndexSearchResult *get_nearest(const AnnoyIndex *index_ptr,
+                                          const float *query_vector_ptr,
+                                          uint32_t n_results,
+                                          int32_t search_k,
+                                          bool should_include_distance);
+
+const AnnoyIndexSearchResult *get_nearest_to_item(const AnnoyIndex *index_ptr,
+                                                  uint64_t item_index,
+                                                  uint32_t n_results,
+                                                  int32_t search_k,
+                                                  bool should_include_distance);
+
+// Writes up to `n_results` ids to `id_list_ptr` and, unless it is null, distances to
+// `distance_list_ptr`, returning the number of results written. No result has to be freed.
+uint64_t get_nearest_into(const AnnoyIndex *index_ptr,
+                          const float *query_vector_ptr,
+                          uint32_t n_results,
+                          int32_t search_k,
+                          uint64_t *id_list_ptr,
+                          float *distance_list_ptr);
+
+// Same as `get_nearest_into`, searching for the neighbours of an item.
+uint64_t get_nearest_to_item_into(const AnnoyIndex *index_ptr,
+                                  uint64_t item_index,
+                                  uint32_t n_results,
+                                  int32_t search_k,
+                                  uint64_t *id_list_ptr,
+                                  float *distance_list_ptr);
+
+// Searches `n_queries` query vectors stored back to back in `query_vectors_ptr`.
+// Results of query `i` are written from offset `i * n_results` of `id_list_ptr` and
+// `distance_list_ptr` (which may be null if distances are not needed), and their number
+// to `count_list_ptr[i]`, slots after the last result of a query are left untouched.
+// Failures only set `annoy_last_error`, `try_get_nearest_batch` also returns the status.
+void get_nearest_batch(const AnnoyIndex *index_ptr,
+                       const float *query_vectors_ptr,
+                       uint64_t n_queries,
+                       uint32_t n_results,
+                       int32_t search_k,
+                       uint64_t *id_list_ptr,
+                       float *distance_list_ptr,
+                       uint64_t *count_list_ptr);
+
+float get_distance(const AnnoyIndex *index_ptr, uint64_t i, uint64_t j);
+
+void free_search_result(const AnnoyIndexSearchResult *search_result_ptr);
+
+uint64_t get_result_count(const AnnoyIndexSearchResult *search_result_ptr);
+
+const uint64_t *get_id_list(const AnnoyIndexSearchResult *search_result_ptr);
+
+const float *get_distance_list(const AnnoyIndexSearchResult *search_result_ptr);
+
+enum AnnoyStatus try_load_annoy_index(const char *path,
+                                      int32_t dimension,
+                                      uint8_t index_type,
+                                      const AnnoyIndex **out_index);
+
+enum AnnoyStatus try_load_annoy_index_into_mem(const char *path,
+                                               int32_t dimension,
+                                               uint8_t index_type,
+                                               const AnnoyIndex **out_index);
+
+enum AnnoyStatus try_load_annoy_index_from_buffer(const uint8_t *buffer,
+                                                  uint64_t buffer_len,
+                                                  int32_t dimension,
+                                                  uint8_t index_type,
+                                                  const AnnoyIndex **out_index);
+
+enum AnnoyStatus try_free_annoy_index(const AnnoyIndex *index);
+
+enum AnnoyStatus try_get_dimension(const AnnoyIndex *index_ptr, int32_t *out_dimension);
+
+enum AnnoyStatus try_get_size(const AnnoyIndex *index_ptr, uint64_t *out_size);
+
+enum AnnoyStatus try_get_item_vector(const AnnoyIndex *index_ptr,
+                                     uint64_t item_index,
+                                     float *item_vector);
+
+enum AnnoyStatus try_get_nearest(const AnnoyIndex *index_ptr,
+                                 const float *query_vector_ptr,
+                                 uint32_t n_results,
+                                 int32_t search_k,
+                                 bool should_include_distance,
+                                 const AnnoyIndexSearchResult **out_result);
+
+enum AnnoyStatus try_get_nearest_to_item(const AnnoyIndex *index_ptr,
+                                         uint64_t item_index,
+                                         uint32_t n_results,
+                                         int32_t search_k,
+                                         bool should_include_distance,
+                                         const AnnoyIndexSearchResult **out_result);
+
+enum AnnoyStatus try_get_nearest_into(const AnnoyIndex *index_ptr,
+                                      const float *query_vector_ptr,
+                                      uint32_t n_results,
+                                      int32_t search_k,
+                                      uint64_t *id_list_ptr,
+                                      float *distance_list_ptr,
+                                      uint64_t *out_count);
+
+enum AnnoyStatus try_get_nearest_to_item_into(const AnnoyIndex *index_ptr,
+                                              uint64_t item_index,
+                                              uint32_t n_results,
+                                              int32_t search_k,
+                                              uint64_t *id_list_ptr,
+                                              float *distance_list_ptr,
+                                              uint64_t *out_count);
+
+enum AnnoyStatus try_get_nearest_batch(const AnnoyIndex *index_ptr,
+                                       const float *query_vectors_ptr,
+                                       uint64_t n_queries,
+                                       uint32_t n_results,
+                                       int32_t search_k,
+                                       uint64_t *id_list_ptr,
+                                       float *distance_list_ptr,
+                                       uint64_t *count_list_ptr);
+
+enum AnnoyStatus try_get_distance(const AnnoyIndex *index_ptr,
+                                  uint64_t i,
+                                  uint64_t j,
+                                  float *out_distance);
+
+enum AnnoyStatus try_free_search_result(const AnnoyIndexSearchResult *search_result_ptr);
+
+enum AnnoyStatus try_get_result_count(const AnnoyIndexSearchResult *search_result_ptr,
+                                      uint64_t *out_count);
+
+enum AnnoyStatus try_get_id_list(const AnnoyIndexSearchResult *search_result_ptr,
+                                 const uint64_t **out_id_list);
+
+enum AnnoyStatus try_get_distance_list(const AnnoyIndexSearchResult *search_result_ptr,
+                                       const float **out_distance_list);
+
+// Message of the most recent failure on the calling thread, null if there is none.
+// The string is owned by the library and stays valid until the next call on the same thread.
+const char *annoy_last_error(void);
+
+#endif  /* ANNOY_RS_FFI_H */
@@ -0,0 +1,62 @@
+use libc::c_char;
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::CString;
+use std::ptr;
+
+/// Status code returned by the `try_*` functions, details of a failure are
+/// available via [`annoy_last_error`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnoyStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidArgument = 2,
+    IndexOutOfRange = 3,
+    LoadFailed = 4,
+    Panic = 5,
+}
+
+#[derive(Debug)]
+pub(crate) struct FfiError {
+    pub(crate) status: AnnoyStatus,
+    pub(crate) message: String,
+}
+
+impl FfiError {
+    pub(crate) fn new(status: AnnoyStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn null_pointer(name: &str) -> Self {
+        Self::new(AnnoyStatus::NullPointer, format!("{name} is null"))
+    }
+
+    pub(crate) fn load_failed(err: Box<dyn Error>) -> Self {
+        Self::new(AnnoyStatus::LoadFailed, err.to_string())
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+pub(crate) fn clear_last_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+ffi_fn! {
+    /// Message of the most recent failure on the calling thread, null if there is none.
+    /// The string is owned by the library and stays valid until the next call on the same thread.
+    fn annoy_last_error() -> *const c_char {
+        LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+    }
+}
@@ -2,23 +2,51 @@
 
 #[macro_use]
 mod macros;
+mod error;
 
 pub use annoy_rs;
 use annoy_rs::*;
+pub use error::{annoy_last_error, AnnoyStatus};
 
+use error::*;
 use libc::c_char;
-use std::error::Error;
 use std::ffi::CStr;
-use std::mem;
 use std::ptr;
 use std::slice;
 
 ffi_fn! {
+    /// Returns null on failure, see `annoy_last_error` for the reason.
     fn load_annoy_index(path: *const c_char, dimension: i32, index_type: u8) -> *const AnnoyIndex {
-        let result = load_annoy_index_inner(path, dimension, index_type);
-        match result {
-            Ok(ptr) => ptr,
-            Err(_e) => ptr::null(),
+        null_on_error(load_annoy_index_inner(path, dimension, index_type, false))
+    }
+}
+
+ffi_fn! {
+    /// Same as `load_annoy_index`, but reads the whole file into memory instead of memory mapping it.
+    fn load_annoy_index_into_mem(path: *const c_char, dimension: i32, index_type: u8) -> *const AnnoyIndex {
+        null_on_error(load_annoy_index_inner(path, dimension, index_type, true))
+    }
+}
+
+ffi_fn! {
+    /// Loads an index from `buffer_len` bytes at `buffer`, which are copied so the caller keeps ownership.
+    fn load_annoy_index_from_buffer(
+        buffer: *const u8,
+        buffer_len: u64,
+        dimension: i32,
+        index_type: u8,
+    ) -> *const AnnoyIndex {
+        null_on_error(load_annoy_index_from_buffer_inner(buffer, buffer_len, dimension, index_type))
+    }
+}
+
+fn null_on_error(result: Result<*const AnnoyIndex, FfiError>) -> *const AnnoyIndex {
+    clear_last_error();
+    match result {
+        Ok(ptr) => ptr,
+        Err(err) => {
+            set_last_error(&err.message);
+            ptr::null()
         }
     }
 }
@@ -27,14 +55,78 @@ fn load_annoy_index_inner(
     path: *const c_char,
     dimension: i32,
     index_type: u8,
-) -> Result<*const AnnoyIndex, Box<dyn Error>> {
+    into_mem: bool,
+) -> Result<*const AnnoyIndex, FfiError> {
+    if path.is_null() {
+        return Err(FfiError::null_pointer("path"));
+    }
     let c_str_path = unsafe { CStr::from_ptr(path) };
-    let ru_path = c_str_path.to_str()?;
-    let ru_index_type: IndexType = unsafe { mem::transmute(index_type) };
-    let index = AnnoyIndex::load(dimension as usize, ru_path, ru_index_type)?;
+    let ru_path = c_str_path
+        .to_str()
+        .map_err(|err| FfiError::new(AnnoyStatus::InvalidArgument, err.to_string()))?;
+    let (dimension, index_type) = parse_load_args(dimension, index_type)?;
+    let index = if into_mem {
+        AnnoyIndex::load_into_mem(dimension, ru_path, index_type)
+    } else {
+        AnnoyIndex::load(dimension, ru_path, index_type)
+    }
+    .map_err(FfiError::load_failed)?;
+    Ok(Box::into_raw(Box::new(index)))
+}
+
+fn load_annoy_index_from_buffer_inner(
+    buffer: *const u8,
+    buffer_len: u64,
+    dimension: i32,
+    index_type: u8,
+) -> Result<*const AnnoyIndex, FfiError> {
+    if buffer.is_null() {
+        return Err(FfiError::null_pointer("buffer"));
+    }
+    let (dimension, index_type) = parse_load_args(dimension, index_type)?;
+    let buffer = unsafe { slice::from_raw_parts(buffer, buffer_len as usize) }.to_vec();
+    let index = AnnoyIndex::load_from_buffer(buffer, dimension, index_type)
+        .map_err(FfiError::load_failed)?;
     Ok(Box::into_raw(Box::new(index)))
 }
 
+fn parse_load_args(dimension: i32, index_type: u8) -> Result<(usize, IndexType), FfiError> {
+    if dimension <= 0 {
+        return Err(FfiError::new(
+            AnnoyStatus::InvalidArgument,
+            format!("Invalid dimension {dimension}"),
+        ));
+    }
+    let index_type = IndexType::try_from(index_type)
+        .map_err(|err| FfiError::new(AnnoyStatus::InvalidArgument, err.to_string()))?;
+    Ok((dimension as usize, index_type))
+}
+
+fn as_ref<'a, T>(ptr: *const T, name: &str) -> Result<&'a T, FfiError> {
+    unsafe { ptr.as_ref() }.ok_or_else(|| FfiError::null_pointer(name))
+}
+
+fn write_out<T>(out: *mut T, value: T, name: &str) -> Result<(), FfiError> {
+    if out.is_null() {
+        return Err(FfiError::null_pointer(name));
+    }
+    unsafe { out.write(value) };
+    Ok(())
+}
+
+fn check_item(index: &AnnoyIndex, item_index: u64) -> Result<(), FfiError> {
+    if item_index as usize >= index.size {
+        return Err(FfiError::new(
+            AnnoyStatus::IndexOutOfRange,
+            format!(
+                "Item index {item_index} out of range, size is {}",
+                index.size
+            ),
+        ));
+    }
+    Ok(())
+}
+
 ffi_fn! {
     fn free_annoy_index(index: *const AnnoyIndex) {
         unsafe { drop(Box::from_raw(index as *mut AnnoyIndex)); }
@@ -94,6 +186,141 @@ ffi_fn! {
     }
 }
 
+ffi_fn! {
+    /// Writes up to `n_results` ids to `id_list_ptr` and, unless it is null, distances to
+    /// `distance_list_ptr`, returning the number of results written. No result has to be freed.
+    fn get_nearest_into(
+        index_ptr: *const AnnoyIndex,
+        query_vector_ptr: *const f32,
+        n_results: u32,
+        search_k: i32,
+        id_list_ptr: *mut u64,
+        distance_list_ptr: *mut f32,
+    ) -> u64 {
+        let index = unsafe { &*index_ptr };
+        let query_vector = unsafe { slice::from_raw_parts(query_vector_ptr, index.dimension) };
+        let result = index.get_nearest(query_vector, n_results as usize, search_k, !distance_list_ptr.is_null());
+        write_result(&result, id_list_ptr, distance_list_ptr)
+    }
+}
+
+ffi_fn! {
+    /// Same as `get_nearest_into`, searching for the neighbours of an item.
+    fn get_nearest_to_item_into(
+        index_ptr: *const AnnoyIndex,
+        item_index: u64,
+        n_results: u32,
+        search_k: i32,
+        id_list_ptr: *mut u64,
+        distance_list_ptr: *mut f32,
+    ) -> u64 {
+        let index = unsafe { &*index_ptr };
+        let result = index.get_nearest_to_item(item_index, n_results as usize, search_k, !distance_list_ptr.is_null());
+        write_result(&result, id_list_ptr, distance_list_ptr)
+    }
+}
+
+fn write_result(
+    result: &AnnoyIndexSearchResult,
+    id_list_ptr: *mut u64,
+    distance_list_ptr: *mut f32,
+) -> u64 {
+    unsafe {
+        result
+            .id_list
+            .as_ptr()
+            .copy_to_nonoverlapping(id_list_ptr, result.count);
+        if !distance_list_ptr.is_null() {
+            result
+                .distance_list
+                .as_ptr()
+                .copy_to_nonoverlapping(distance_list_ptr, result.count);
+        }
+    }
+    result.count as u64
+}
+
+ffi_fn! {
+    /// Searches `n_queries` query vectors stored back to back in `query_vectors_ptr`.
+    /// Results of query `i` are written from offset `i * n_results` of `id_list_ptr` and
+    /// `distance_list_ptr` (which may be null if distances are not needed), and their number
+    /// to `count_list_ptr[i]`, slots after the last result of a query are left untouched.
+    /// Failures only set `annoy_last_error`, `try_get_nearest_batch` also returns the status.
+    fn get_nearest_batch(
+        index_ptr: *const AnnoyIndex,
+        query_vectors_ptr: *const f32,
+        n_queries: u64,
+        n_results: u32,
+        search_k: i32,
+        id_list_ptr: *mut u64,
+        distance_list_ptr: *mut f32,
+        count_list_ptr: *mut u64,
+    ) {
+        clear_last_error();
+        if let Err(err) = get_nearest_batch_inner(
+            index_ptr,
+            query_vectors_ptr,
+            n_queries,
+            n_results,
+            search_k,
+            id_list_ptr,
+            distance_list_ptr,
+            count_list_ptr,
+        ) {
+            set_last_error(&err.message);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn get_nearest_batch_inner(
+    index_ptr: *const AnnoyIndex,
+    query_vectors_ptr: *const f32,
+    n_queries: u64,
+    n_results: u32,
+    search_k: i32,
+    id_list_ptr: *mut u64,
+    distance_list_ptr: *mut f32,
+    count_list_ptr: *mut u64,
+) -> Result<(), FfiError> {
+    let index = as_ref(index_ptr, "index")?;
+    as_ref(query_vectors_ptr, "query_vectors")?;
+    if id_list_ptr.is_null() {
+        return Err(FfiError::null_pointer("id_list"));
+    }
+    if count_list_ptr.is_null() {
+        return Err(FfiError::null_pointer("count_list"));
+    }
+    let n_queries = n_queries as usize;
+    let n_results = n_results as usize;
+    let query_vectors =
+        unsafe { slice::from_raw_parts(query_vectors_ptr, n_queries * index.dimension) };
+    let id_list = unsafe { slice::from_raw_parts_mut(id_list_ptr, n_queries * n_results) };
+    let count_list = unsafe { slice::from_raw_parts_mut(count_list_ptr, n_queries) };
+    let mut distance_list = if distance_list_ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { slice::from_raw_parts_mut(distance_list_ptr, n_queries * n_results) })
+    };
+    for (i, query_vector) in query_vectors.chunks_exact(index.dimension).enumerate() {
+        let result = index.get_nearest(query_vector, n_results, search_k, distance_list.is_some());
+        let offset = i * n_results;
+        id_list[offset..offset + result.count].copy_from_slice(&result.id_list);
+        if let Some(distance_list) = distance_list.as_mut() {
+            distance_list[offset..offset + result.count].copy_from_slice(&result.distance_list);
+        }
+        count_list[i] = result.count as u64;
+    }
+    Ok(())
+}
+
+ffi_fn! {
+    fn get_distance(index_ptr: *const AnnoyIndex, i: u64, j: u64) -> f32 {
+        let index = unsafe { &*index_ptr };
+        index.get_distance(i, j)
+    }
+}
+
 ffi_fn! {
     fn free_search_result(search_result_ptr: *const AnnoyIndexSearchResult){
         unsafe { drop(Box::from_raw(search_result_ptr as *mut AnnoyIndexSearchResult)); }
@@ -120,3 +347,236 @@ ffi_fn! {
         search_result.distance_list.as_ptr()
     }
 }
+
+ffi_try_fn! {
+    fn try_load_annoy_index(
+        path: *const c_char,
+        dimension: i32,
+        index_type: u8,
+        out_index: *mut *const AnnoyIndex,
+    ) {
+        if out_index.is_null() {
+            return Err(FfiError::null_pointer("out_index"));
+        }
+        let index = load_annoy_index_inner(path, dimension, index_type, false)?;
+        write_out(out_index, index, "out_index")
+    }
+}
+
+ffi_try_fn! {
+    fn try_load_annoy_index_into_mem(
+        path: *const c_char,
+        dimension: i32,
+        index_type: u8,
+        out_index: *mut *const AnnoyIndex,
+    ) {
+        if out_index.is_null() {
+            return Err(FfiError::null_pointer("out_index"));
+        }
+        let index = load_annoy_index_inner(path, dimension, index_type, true)?;
+        write_out(out_index, index, "out_index")
+    }
+}
+
+ffi_try_fn! {
+    fn try_load_annoy_index_from_buffer(
+        buffer: *const u8,
+        buffer_len: u64,
+        dimension: i32,
+        index_type: u8,
+        out_index: *mut *const AnnoyIndex,
+    ) {
+        if out_index.is_null() {
+            return Err(FfiError::null_pointer("out_index"));
+        }
+        let index = load_annoy_index_from_buffer_inner(buffer, buffer_len, dimension, index_type)?;
+        write_out(out_index, index, "out_index")
+    }
+}
+
+ffi_try_fn! {
+    fn try_free_annoy_index(index: *const AnnoyIndex) {
+        as_ref(index, "index")?;
+        unsafe { drop(Box::from_raw(index as *mut AnnoyIndex)); }
+        Ok(())
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_dimension(index_ptr: *const AnnoyIndex, out_dimension: *mut i32) {
+        let index = as_ref(index_ptr, "index")?;
+        write_out(out_dimension, index.dimension as i32, "out_dimension")
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_size(index_ptr: *const AnnoyIndex, out_size: *mut u64) {
+        let index = as_ref(index_ptr, "index")?;
+        write_out(out_size, index.size as u64, "out_size")
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_item_vector(index_ptr: *const AnnoyIndex, item_index: u64, item_vector: *mut f32) {
+        let index = as_ref(index_ptr, "index")?;
+        if item_vector.is_null() {
+            return Err(FfiError::null_pointer("item_vector"));
+        }
+        check_item(index, item_index)?;
+        let item_vec = index.get_item_vector(item_index);
+        unsafe { item_vec.as_ptr().copy_to(item_vector, index.dimension) };
+        Ok(())
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_nearest(
+        index_ptr: *const AnnoyIndex,
+        query_vector_ptr: *const f32,
+        n_results: u32,
+        search_k: i32,
+        should_include_distance: bool,
+        out_result: *mut *const AnnoyIndexSearchResult,
+    ) {
+        let index = as_ref(index_ptr, "index")?;
+        as_ref(query_vector_ptr, "query_vector")?;
+        if out_result.is_null() {
+            return Err(FfiError::null_pointer("out_result"));
+        }
+        let query_vector = unsafe { slice::from_raw_parts(query_vector_ptr, index.dimension) };
+        let result = index.get_nearest(query_vector, n_results as usize, search_k, should_include_distance);
+        write_out(out_result, Box::into_raw(Box::new(result)) as *const _, "out_result")
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_nearest_to_item(
+        index_ptr: *const AnnoyIndex,
+        item_index: u64,
+        n_results: u32,
+        search_k: i32,
+        should_include_distance: bool,
+        out_result: *mut *const AnnoyIndexSearchResult,
+    ) {
+        let index = as_ref(index_ptr, "index")?;
+        check_item(index, item_index)?;
+        if out_result.is_null() {
+            return Err(FfiError::null_pointer("out_result"));
+        }
+        let result =
+            index.get_nearest_to_item(item_index, n_results as usize, search_k, should_include_distance);
+        write_out(out_result, Box::into_raw(Box::new(result)) as *const _, "out_result")
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_nearest_into(
+        index_ptr: *const AnnoyIndex,
+        query_vector_ptr: *const f32,
+        n_results: u32,
+        search_k: i32,
+        id_list_ptr: *mut u64,
+        distance_list_ptr: *mut f32,
+        out_count: *mut u64,
+    ) {
+        let index = as_ref(index_ptr, "index")?;
+        as_ref(query_vector_ptr, "query_vector")?;
+        if id_list_ptr.is_null() {
+            return Err(FfiError::null_pointer("id_list"));
+        }
+        if out_count.is_null() {
+            return Err(FfiError::null_pointer("out_count"));
+        }
+        let query_vector = unsafe { slice::from_raw_parts(query_vector_ptr, index.dimension) };
+        let result = index.get_nearest(query_vector, n_results as usize, search_k, !distance_list_ptr.is_null());
+        write_out(out_count, write_result(&result, id_list_ptr, distance_list_ptr), "out_count")
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_nearest_to_item_into(
+        index_ptr: *const AnnoyIndex,
+        item_index: u64,
+        n_results: u32,
+        search_k: i32,
+        id_list_ptr: *mut u64,
+        distance_list_ptr: *mut f32,
+        out_count: *mut u64,
+    ) {
+        let index = as_ref(index_ptr, "index")?;
+        check_item(index, item_index)?;
+        if id_list_ptr.is_null() {
+            return Err(FfiError::null_pointer("id_list"));
+        }
+        if out_count.is_null() {
+            return Err(FfiError::null_pointer("out_count"));
+        }
+        let result = index.get_nearest_to_item(item_index, n_results as usize, search_k, !distance_list_ptr.is_null());
+        write_out(out_count, write_result(&result, id_list_ptr, distance_list_ptr), "out_count")
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_nearest_batch(
+        index_ptr: *const AnnoyIndex,
+        query_vectors_ptr: *const f32,
+        n_queries: u64,
+        n_results: u32,
+        search_k: i32,
+        id_list_ptr: *mut u64,
+        distance_list_ptr: *mut f32,
+        count_list_ptr: *mut u64,
+    ) {
+        get_nearest_batch_inner(
+            index_ptr,
+            query_vectors_ptr,
+            n_queries,
+            n_results,
+            search_k,
+            id_list_ptr,
+            distance_list_ptr,
+            count_list_ptr,
+        )
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_distance(index_ptr: *const AnnoyIndex, i: u64, j: u64, out_distance: *mut f32) {
+        let index = as_ref(index_ptr, "index")?;
+        check_item(index, i)?;
+        check_item(index, j)?;
+        write_out(out_distance, index.get_distance(i, j), "out_distance")
+    }
+}
+
+ffi_try_fn! {
+    fn try_free_search_result(search_result_ptr: *const AnnoyIndexSearchResult) {
+        as_ref(search_result_ptr, "search_result")?;
+        unsafe { drop(Box::from_raw(search_result_ptr as *mut AnnoyIndexSearchResult)); }
+        Ok(())
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_result_count(search_result_ptr: *const AnnoyIndexSearchResult, out_count: *mut u64) {
+        let search_result = as_ref(search_result_ptr, "search_result")?;
+        write_out(out_count, search_result.count as u64, "out_count")
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_id_list(search_result_ptr: *const AnnoyIndexSearchResult, out_id_list: *mut *const u64) {
+        let search_result = as_ref(search_result_ptr, "search_result")?;
+        write_out(out_id_list, search_result.id_list.as_ptr(), "out_id_list")
+    }
+}
+
+ffi_try_fn! {
+    fn try_get_distance_list(
+        search_result_ptr: *const AnnoyIndexSearchResult,
+        out_distance_list: *mut *const f32,
+    ) {
+        let search_result = as_ref(search_result_ptr, "search_result")?;
+        write_out(out_distance_list, search_result.distance_list.as_ptr(), "out_distance_list")
+    }
+}
@@ -1,23 +1,18 @@
 macro_rules! ffi_fn {
-    (fn $name:ident($($arg:ident: $arg_ty:ty),*,) -> $ret:ty $body:block) => {
-        ffi_fn!(fn $name($($arg: $arg_ty),*) -> $ret $body);
+    ($(#[$meta:meta])* fn $name:ident($($arg:ident: $arg_ty:ty),*,) -> $ret:ty $body:block) => {
+        ffi_fn!($(#[$meta])* fn $name($($arg: $arg_ty),*) -> $ret $body);
     };
-    (fn $name:ident($($arg:ident: $arg_ty:ty),*) -> $ret:ty $body:block) => {
+    ($(#[$meta:meta])* fn $name:ident($($arg:ident: $arg_ty:ty),*) -> $ret:ty $body:block) => {
+        $(#[$meta])*
         #[no_mangle]
-        pub unsafe extern fn $name($($arg: $arg_ty),*) -> $ret {
+        pub unsafe extern "C" fn $name($($arg: $arg_ty),*) -> $ret {
             use ::std::io::{self, Write};
             use ::std::panic::{self, AssertUnwindSafe};
             use ::libc::abort;
             match panic::catch_unwind(AssertUnwindSafe(move || $body)) {
                 Ok(v) => v,
                 Err(err) => {
-                    let msg = if let Some(&s) = err.downcast_ref::<&str>() {
-                        s.to_owned()
-                    } else if let Some(s) = err.downcast_ref::<String>() {
-                        s.to_owned()
-                    } else {
-                        "UNABLE TO SHOW RESULT OF PANIC.".to_owned()
-                    };
+                    let msg = $crate::macros::panic_message(&err);
                     let _ = writeln!(
                         &mut io::stderr(),
                         "panic unwind caught, aborting: {:?}",
@@ -27,10 +22,48 @@ macro_rules! ffi_fn {
             }
         }
     };
-    (fn $name:ident($($arg:ident: $arg_ty:ty),*,) $body:block) => {
-        ffi_fn!(fn $name($($arg: $arg_ty),*) -> () $body);
+    ($(#[$meta:meta])* fn $name:ident($($arg:ident: $arg_ty:ty),*,) $body:block) => {
+        ffi_fn!($(#[$meta])* fn $name($($arg: $arg_ty),*) -> () $body);
     };
-    (fn $name:ident($($arg:ident: $arg_ty:ty),*) $body:block) => {
-        ffi_fn!(fn $name($($arg: $arg_ty),*) -> () $body);
+    ($(#[$meta:meta])* fn $name:ident($($arg:ident: $arg_ty:ty),*) $body:block) => {
+        ffi_fn!($(#[$meta])* fn $name($($arg: $arg_ty),*) -> () $body);
     };
 }
+
+/// Like `ffi_fn!`, but the body returns `Result<(), FfiError>` which is reported as an
+/// `AnnoyStatus` with the message kept for `annoy_last_error`, panics are reported instead of aborting.
+macro_rules! ffi_try_fn {
+    ($(#[$meta:meta])* fn $name:ident($($arg:ident: $arg_ty:ty),*,) $body:block) => {
+        ffi_try_fn!($(#[$meta])* fn $name($($arg: $arg_ty),*) $body);
+    };
+    ($(#[$meta:meta])* fn $name:ident($($arg:ident: $arg_ty:ty),*) $body:block) => {
+        $(#[$meta])*
+        #[no_mangle]
+        pub unsafe extern "C" fn $name($($arg: $arg_ty),*) -> $crate::error::AnnoyStatus {
+            use ::std::panic::{self, AssertUnwindSafe};
+            use $crate::error::{clear_last_error, set_last_error, AnnoyStatus, FfiError};
+            clear_last_error();
+            let result: Result<(), FfiError> = match panic::catch_unwind(AssertUnwindSafe(move || $body)) {
+                Ok(r) => r,
+                Err(err) => Err(FfiError::new(AnnoyStatus::Panic, $crate::macros::panic_message(&err))),
+            };
+            match result {
+                Ok(()) => AnnoyStatus::Ok,
+                Err(err) => {
+                    set_last_error(&err.message);
+                    err.status
+                }
+            }
+        }
+    };
+}
+
+pub(crate) fn panic_message(err: &Box<dyn ::std::any::Any + Send>) -> String {
+    if let Some(&s) = err.downcast_ref::<&str>() {
+        s.to_owned()
+    } else if let Some(s) = err.downcast_ref::<String>() {
+        s.to_owned()
+    } else {
+        "UNABLE TO SHOW RESULT OF PANIC.".to_owned()
+    }
+}
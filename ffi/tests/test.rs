@@ -3,7 +3,7 @@ mod tests {
     use annoy_rs_ffi::{annoy_rs::*, *};
     use libc::c_char;
     use std::alloc::{alloc, Layout};
-    use std::ffi::CString;
+    use std::ffi::{CStr, CString};
     use std::ptr;
     use std::slice;
 
@@ -94,12 +94,12 @@ mod tests {
             let v3_raw = alloc(Layout::array::<f32>(dim as usize).unwrap()) as *mut f32;
             get_item_vector(index, 3, v3_raw);
             // let v3_raw = get_item_vector(index, 3);
-            let v3 = slice::from_raw_parts(v3_raw as *mut f32, dim as usize).to_vec();
+            let v3 = slice::from_raw_parts(v3_raw, dim as usize).to_vec();
             assert_eq!(v3, expected_item3_vec);
 
             let v0_raw = alloc(Layout::array::<f32>(dim as usize).unwrap()) as *mut f32;
             get_item_vector(index, 0, v0_raw);
-            let _v0 = slice::from_raw_parts(v0_raw as *mut f32, dim as usize).to_vec();
+            let _v0 = slice::from_raw_parts(v0_raw, dim as usize).to_vec();
             // let v0_raw = get_item_vector(index, 0);
             assert_eq!(TEST_NODE_COUNT, get_size(index) as usize);
             {
@@ -172,6 +172,268 @@ mod tests {
             )
         };
         assert_eq!(index_ptr, ptr::null());
+        assert!(!last_error().is_empty());
+    }
+
+    #[test]
+    fn error_reporting_cffi() {
+        let filepath =
+            CString::new(format!("../tests/index.angular.{TEST_INDEX_DIM}d.ann")).unwrap();
+        unsafe {
+            let index = load_annoy_index(filepath.as_ptr(), TEST_INDEX_DIM as i32, 42);
+            assert_eq!(index, ptr::null());
+            assert_eq!(last_error(), "Invalid index type 42");
+
+            let mut index = ptr::null();
+            assert_eq!(
+                try_load_annoy_index(ptr::null(), TEST_INDEX_DIM as i32, 0, &mut index),
+                AnnoyStatus::NullPointer
+            );
+            assert_eq!(
+                try_load_annoy_index(filepath.as_ptr(), TEST_INDEX_DIM as i32, 42, &mut index),
+                AnnoyStatus::InvalidArgument
+            );
+            let invalid_path = CString::new("invalid_index.ann").unwrap();
+            assert_eq!(
+                try_load_annoy_index(invalid_path.as_ptr(), TEST_INDEX_DIM as i32, 0, &mut index),
+                AnnoyStatus::LoadFailed
+            );
+            assert!(!last_error().is_empty());
+            assert_eq!(
+                try_load_annoy_index(
+                    filepath.as_ptr(),
+                    TEST_INDEX_DIM as i32,
+                    IndexType::Angular as u8,
+                    &mut index
+                ),
+                AnnoyStatus::Ok
+            );
+            assert_eq!(annoy_last_error(), ptr::null());
+
+            let mut size = 0;
+            assert_eq!(try_get_size(index, &mut size), AnnoyStatus::Ok);
+            assert_eq!(size as usize, TEST_NODE_COUNT);
+            assert_eq!(
+                try_get_size(ptr::null(), &mut size),
+                AnnoyStatus::NullPointer
+            );
+
+            let mut v0 = vec![0.0_f32; TEST_INDEX_DIM];
+            assert_eq!(
+                try_get_item_vector(index, 0, v0.as_mut_ptr()),
+                AnnoyStatus::Ok
+            );
+            assert_eq!(
+                try_get_item_vector(index, TEST_NODE_COUNT as u64, v0.as_mut_ptr()),
+                AnnoyStatus::IndexOutOfRange
+            );
+
+            let mut result = ptr::null();
+            assert_eq!(
+                try_get_nearest(index, v0.as_ptr(), 5, -1, true, &mut result),
+                AnnoyStatus::Ok
+            );
+            let mut count = 0;
+            assert_eq!(try_get_result_count(result, &mut count), AnnoyStatus::Ok);
+            let mut id_list = ptr::null();
+            assert_eq!(try_get_id_list(result, &mut id_list), AnnoyStatus::Ok);
+            assert_eq!(
+                slice::from_raw_parts(id_list, count as usize),
+                &[0, 4, 37, 61, 29]
+            );
+            assert_eq!(try_free_search_result(result), AnnoyStatus::Ok);
+            assert_eq!(
+                try_get_nearest_to_item(index, TEST_NODE_COUNT as u64, 5, -1, true, &mut result),
+                AnnoyStatus::IndexOutOfRange
+            );
+            assert_eq!(try_free_annoy_index(index), AnnoyStatus::Ok);
+        }
+    }
+
+    #[test]
+    fn load_from_buffer_and_batch_cffi() {
+        let filepath = format!("../tests/index.angular.{TEST_INDEX_DIM}d.ann");
+        let buffer = std::fs::read(&filepath).unwrap();
+        let filepath = CString::new(filepath).unwrap();
+        unsafe {
+            let from_buffer = load_annoy_index_from_buffer(
+                buffer.as_ptr(),
+                buffer.len() as u64,
+                TEST_INDEX_DIM as i32,
+                IndexType::Angular as u8,
+            );
+            assert_ne!(from_buffer, ptr::null());
+            let into_mem = load_annoy_index_into_mem(
+                filepath.as_ptr(),
+                TEST_INDEX_DIM as i32,
+                IndexType::Angular as u8,
+            );
+            assert_ne!(into_mem, ptr::null());
+            let mut index = ptr::null();
+            assert_eq!(
+                try_load_annoy_index_from_buffer(
+                    ptr::null(),
+                    0,
+                    TEST_INDEX_DIM as i32,
+                    IndexType::Angular as u8,
+                    &mut index
+                ),
+                AnnoyStatus::NullPointer
+            );
+
+            for index in [from_buffer, into_mem] {
+                assert_eq!(get_size(index) as usize, TEST_NODE_COUNT);
+                let mut queries = vec![0.0_f32; 2 * TEST_INDEX_DIM];
+                get_item_vector(index, 0, queries.as_mut_ptr());
+                get_item_vector(index, 3, queries[TEST_INDEX_DIM..].as_mut_ptr());
+
+                let n_results = 5;
+                let mut id_list = vec![0_u64; 2 * n_results];
+                let mut distance_list = vec![0.0_f32; 2 * n_results];
+                let mut count_list = vec![0_u64; 2];
+                get_nearest_batch(
+                    index,
+                    queries.as_ptr(),
+                    2,
+                    n_results as u32,
+                    -1,
+                    id_list.as_mut_ptr(),
+                    distance_list.as_mut_ptr(),
+                    count_list.as_mut_ptr(),
+                );
+                assert_eq!(count_list, &[5, 5]);
+                assert_eq!(&id_list[..n_results], &[0, 4, 37, 61, 29]);
+                assert_eq!(id_list[n_results], 3);
+
+                let nearest = index.as_ref().unwrap().get_nearest_to_item(3, 5, -1, true);
+                assert_eq!(&id_list[n_results..], nearest.id_list);
+                assert_eq!(&distance_list[n_results..], nearest.distance_list);
+                for (&id, &distance) in nearest.id_list.iter().zip(nearest.distance_list.iter()) {
+                    assert!((get_distance(index, 3, id) - distance).abs() < 1e-5);
+                }
+
+                let mut id_list = vec![0_u64; 2 * n_results];
+                assert_eq!(
+                    try_get_nearest_batch(
+                        index,
+                        queries.as_ptr(),
+                        2,
+                        n_results as u32,
+                        -1,
+                        id_list.as_mut_ptr(),
+                        ptr::null_mut(),
+                        count_list.as_mut_ptr(),
+                    ),
+                    AnnoyStatus::Ok
+                );
+                assert_eq!(&id_list[..n_results], &[0, 4, 37, 61, 29]);
+
+                let mut distance = 0.0;
+                assert_eq!(
+                    try_get_distance(index, 0, TEST_NODE_COUNT as u64, &mut distance),
+                    AnnoyStatus::IndexOutOfRange
+                );
+                free_annoy_index(index);
+            }
+        }
+    }
+
+    #[test]
+    fn caller_allocated_buffers_cffi() {
+        let filepath =
+            CString::new(format!("../tests/index.angular.{TEST_INDEX_DIM}d.ann")).unwrap();
+        unsafe {
+            let index = load_annoy_index(
+                filepath.as_ptr(),
+                TEST_INDEX_DIM as i32,
+                IndexType::Angular as u8,
+            );
+            let mut v0 = vec![0.0_f32; TEST_INDEX_DIM];
+            get_item_vector(index, 0, v0.as_mut_ptr());
+            let expected = index.as_ref().unwrap().get_nearest(&v0, 5, -1, true);
+
+            let mut id_list = vec![u64::MAX; 8];
+            let mut distance_list = vec![f32::NAN; 8];
+            let count = get_nearest_into(
+                index,
+                v0.as_ptr(),
+                5,
+                -1,
+                id_list.as_mut_ptr(),
+                distance_list.as_mut_ptr(),
+            );
+            assert_eq!(count, 5);
+            assert_eq!(&id_list[..5], expected.id_list);
+            assert_eq!(&distance_list[..5], expected.distance_list);
+            assert_eq!(id_list[5], u64::MAX);
+
+            let mut id_list = vec![0_u64; 5];
+            let count =
+                get_nearest_to_item_into(index, 0, 5, -1, id_list.as_mut_ptr(), ptr::null_mut());
+            assert_eq!(count, 5);
+            assert_eq!(id_list, expected.id_list);
+
+            let mut count = 0;
+            assert_eq!(
+                try_get_nearest_into(
+                    index,
+                    v0.as_ptr(),
+                    5,
+                    -1,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    &mut count
+                ),
+                AnnoyStatus::NullPointer
+            );
+            assert_eq!(
+                try_get_nearest_to_item_into(
+                    index,
+                    0,
+                    5,
+                    -1,
+                    id_list.as_mut_ptr(),
+                    distance_list.as_mut_ptr(),
+                    &mut count
+                ),
+                AnnoyStatus::Ok
+            );
+            assert_eq!(count, 5);
+            assert_eq!(&distance_list[..5], expected.distance_list);
+            assert_eq!(
+                try_get_nearest_to_item_into(
+                    index,
+                    TEST_NODE_COUNT as u64,
+                    5,
+                    -1,
+                    id_list.as_mut_ptr(),
+                    ptr::null_mut(),
+                    &mut count
+                ),
+                AnnoyStatus::IndexOutOfRange
+            );
+            assert_eq!(
+                try_get_nearest_into(
+                    index,
+                    ptr::null(),
+                    5,
+                    -1,
+                    id_list.as_mut_ptr(),
+                    ptr::null_mut(),
+                    &mut count
+                ),
+                AnnoyStatus::NullPointer
+            );
+            free_annoy_index(index);
+        }
+    }
+
+    fn last_error() -> String {
+        let message = unsafe { annoy_last_error() };
+        assert_ne!(message, ptr::null());
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
     }
 
     pub trait RoundToVec<T> {
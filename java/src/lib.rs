@@ -2,11 +2,12 @@
 mod macros;
 
 use annoy_rs::*;
-use jni::objects::{JClass, JFloatArray, JLongArray, JString};
+use jni::objects::{GlobalRef, JByteArray, JByteBuffer, JClass, JFloatArray, JLongArray, JString};
 use jni::sys::{jboolean, jbyte, jclass, jint, jlong};
 use jni::JNIEnv;
 use std::error::Error;
 use std::mem;
+use std::slice;
 
 /*
  * Class:     com_github_hanabi1224_RuAnnoy_NativeMethods
@@ -26,10 +27,7 @@ ffi_fn! {
         let result = Java_com_github_hanabi1224_RuAnnoy_NativeMethods_loadIndex_inner(
             env, class, path, dimension, index_type,
         );
-        match result {
-            Ok(pointer) => pointer,
-            _ => 0,
-        }
+        result.unwrap_or_default()
     }
 }
 
@@ -42,12 +40,168 @@ fn Java_com_github_hanabi1224_RuAnnoy_NativeMethods_loadIndex_inner(
     index_type: jbyte,
 ) -> Result<jlong, Box<dyn Error>> {
     let ru_path: String = env.get_string(&path)?.into();
-    let ru_index_type: IndexType = unsafe { mem::transmute(index_type) };
+    let ru_index_type = IndexType::try_from(index_type as u8)?;
     let index = AnnoyIndex::load(dimension as usize, ru_path.as_str(), ru_index_type)?;
     let ptr = Box::into_raw(Box::new(index));
     Ok(ptr as jlong)
 }
 
+/*
+ * Class:     com_github_hanabi1224_RuAnnoy_NativeMethods
+ * Method:    loadIndexFromPath
+ * Signature: (Ljava/lang/String;IBZ)J
+ */
+// JNIEXPORT jlong JNICALL Java_com_github_hanabi1224_RuAnnoy_NativeMethods_loadIndexFromPath
+//   (JNIEnv *, jclass, jstring, jint, jbyte, jboolean);
+ffi_fn! {
+    fn Java_com_github_hanabi1224_RuAnnoy_NativeMethods_loadIndexFromPath(
+        env: JNIEnv,
+        _class: JClass,
+        path: JString,
+        dimension: jint,
+        index_type: jbyte,
+        into_mem: jboolean,
+    ) -> jlong {
+        let mut env = env;
+        let result = env
+            .get_string(&path)
+            .map(String::from)
+            .map_err(|err| LoadError::InvalidArgument(err.to_string()))
+            .and_then(|path| {
+                let (dimension, index_type) = parse_load_args(dimension, index_type)?;
+                if into_mem != 0 {
+                    AnnoyIndex::load_into_mem(dimension, &path, index_type)
+                } else {
+                    AnnoyIndex::load(dimension, &path, index_type)
+                }
+                .map_err(LoadError::from)
+            });
+        into_pointer_or_throw(&mut env, result)
+    }
+}
+
+/*
+ * Class:     com_github_hanabi1224_RuAnnoy_NativeMethods
+ * Method:    loadIndexFromBytes
+ * Signature: ([BIB)J
+ */
+// JNIEXPORT jlong JNICALL Java_com_github_hanabi1224_RuAnnoy_NativeMethods_loadIndexFromBytes
+//   (JNIEnv *, jclass, jbyteArray, jint, jbyte);
+ffi_fn! {
+    fn Java_com_github_hanabi1224_RuAnnoy_NativeMethods_loadIndexFromBytes(
+        env: JNIEnv,
+        _class: JClass,
+        data: JByteArray,
+        dimension: jint,
+        index_type: jbyte,
+    ) -> jlong {
+        let mut env = env;
+        let result = env
+            .convert_byte_array(&data)
+            .map_err(|err| LoadError::InvalidArgument(err.to_string()))
+            .and_then(|buffer| {
+                let (dimension, index_type) = parse_load_args(dimension, index_type)?;
+                AnnoyIndex::load_from_buffer(buffer, dimension, index_type).map_err(LoadError::from)
+            });
+        into_pointer_or_throw(&mut env, result)
+    }
+}
+
+/*
+ * Class:     com_github_hanabi1224_RuAnnoy_NativeMethods
+ * Method:    loadIndexFromDirectBuffer
+ * Signature: (Ljava/nio/ByteBuffer;IB)J
+ */
+// JNIEXPORT jlong JNICALL Java_com_github_hanabi1224_RuAnnoy_NativeMethods_loadIndexFromDirectBuffer
+//   (JNIEnv *, jclass, jobject, jint, jbyte);
+ffi_fn! {
+    fn Java_com_github_hanabi1224_RuAnnoy_NativeMethods_loadIndexFromDirectBuffer(
+        env: JNIEnv,
+        _class: JClass,
+        data: JByteBuffer,
+        dimension: jint,
+        index_type: jbyte,
+    ) -> jlong {
+        let mut env = env;
+        let result = parse_load_args(dimension, index_type).and_then(|(dimension, index_type)| {
+            let len = env
+                .get_direct_buffer_capacity(&data)
+                .map_err(|_| LoadError::InvalidArgument("data is not a direct buffer".to_owned()))?;
+            let ptr = direct_ptr::<u8>(&mut env, &data, len, "data")
+                .map_err(|err| LoadError::InvalidArgument(err.to_string()))?
+                .cast_const();
+            let buffer = env
+                .new_global_ref(&data)
+                .map_err(|err| LoadError::InvalidArgument(err.to_string()))?;
+            let owner = DirectBuffer {
+                _buffer: buffer,
+                ptr,
+                len,
+            };
+            AnnoyIndex::load_from_owner(owner, dimension, index_type).map_err(LoadError::from)
+        });
+        into_pointer_or_throw(&mut env, result)
+    }
+}
+
+/// Bytes of a direct `ByteBuffer`, kept reachable by a global reference until the index is dropped.
+struct DirectBuffer {
+    _buffer: GlobalRef,
+    ptr: *const u8,
+    len: usize,
+}
+
+// The address of a direct buffer never changes, and the index only reads from it.
+unsafe impl Send for DirectBuffer {}
+unsafe impl Sync for DirectBuffer {}
+
+impl AsRef<[u8]> for DirectBuffer {
+    fn as_ref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+enum LoadError {
+    Io(String),
+    InvalidArgument(String),
+}
+
+impl From<Box<dyn Error>> for LoadError {
+    fn from(err: Box<dyn Error>) -> Self {
+        if err.is::<std::io::Error>() {
+            LoadError::Io(err.to_string())
+        } else {
+            LoadError::InvalidArgument(err.to_string())
+        }
+    }
+}
+
+fn parse_load_args(dimension: jint, index_type: jbyte) -> Result<(usize, IndexType), LoadError> {
+    if dimension <= 0 {
+        return Err(LoadError::InvalidArgument(format!(
+            "Invalid dimension {dimension}"
+        )));
+    }
+    let index_type = IndexType::try_from(index_type as u8)
+        .map_err(|err| LoadError::InvalidArgument(err.to_string()))?;
+    Ok((dimension as usize, index_type))
+}
+
+/// Boxes a loaded index, or throws `IOException` / `IllegalArgumentException` and returns 0.
+fn into_pointer_or_throw(env: &mut JNIEnv, result: Result<AnnoyIndex, LoadError>) -> jlong {
+    match result {
+        Ok(index) => Box::into_raw(Box::new(index)) as jlong,
+        Err(LoadError::Io(msg)) => {
+            let _ = env.throw_new("java/io/IOException", msg);
+            0
+        }
+        Err(LoadError::InvalidArgument(msg)) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", msg);
+            0
+        }
+    }
+}
+
 /*
  * Class:     com_github_hanabi1224_RuAnnoy_NativeMethods
  * Method:    freeIndex
@@ -99,8 +253,12 @@ ffi_fn! {
         pointer: jlong,
         item_index: jlong,
     ) -> JFloatArray {
+        let mut env = env;
         let index = unsafe { &*(pointer as *const AnnoyIndex) };
-        let vector = index.get_item_vector(item_index as u64);
+        let Some(item_index) = check_item(&mut env, index, item_index) else {
+            return JFloatArray::default();
+        };
+        let vector = index.get_item_vector(item_index);
         let result = env.new_float_array(index.dimension as i32).unwrap();
         env.set_float_array_region(&result, 0, vector.as_slice()).unwrap();
         result
@@ -126,9 +284,13 @@ ffi_fn! {
         id_list: JLongArray,
         distance_list: JFloatArray,
     ) -> jint {
+        let mut env = env;
         let index = unsafe { &*(pointer as *const AnnoyIndex) };
+        let Some(item_index) = check_item(&mut env, index, item_index) else {
+            return 0;
+        };
         let r = index.get_nearest_to_item(
-            item_index as u64,
+            item_index,
             n_results as usize,
             search_k,
             should_include_distance != 0,
@@ -184,3 +346,148 @@ ffi_fn! {
         }
     }
 }
+
+/*
+ * Class:     com_github_hanabi1224_RuAnnoy_NativeMethods
+ * Method:    getNearestDirect
+ * Signature: (JLjava/nio/ByteBuffer;IILjava/nio/ByteBuffer;Ljava/nio/ByteBuffer;)I
+ */
+// JNIEXPORT jint JNICALL Java_com_github_hanabi1224_RuAnnoy_NativeMethods_getNearestDirect
+//   (JNIEnv *, jclass, jlong, jobject, jint, jint, jobject, jobject);
+ffi_fn! {
+    fn Java_com_github_hanabi1224_RuAnnoy_NativeMethods_getNearestDirect(
+        env: JNIEnv,
+        _class: jclass,
+        pointer: jlong,
+        query_vector: JByteBuffer,
+        n_results: jint,
+        search_k: jint,
+        id_list: JByteBuffer,
+        distance_list: JByteBuffer,
+    ) -> jint {
+        let mut env = env;
+        let index = unsafe { &*(pointer as *const AnnoyIndex) };
+        let result = direct_ptr::<f32>(&mut env, &query_vector, index.dimension, "queryVector")
+            .and_then(|ptr| {
+                // Only read, and no longer once results are written, even if it is also an output
+                let query_vector = unsafe { slice::from_raw_parts(ptr, index.dimension) };
+                let r = index.get_nearest(
+                    query_vector,
+                    n_results as usize,
+                    search_k,
+                    !distance_list.is_null(),
+                );
+                write_direct(&mut env, &r, &id_list, &distance_list)
+            });
+        throw_on_error(&mut env, result)
+    }
+}
+
+/*
+ * Class:     com_github_hanabi1224_RuAnnoy_NativeMethods
+ * Method:    getNearestToItemDirect
+ * Signature: (JJIILjava/nio/ByteBuffer;Ljava/nio/ByteBuffer;)I
+ */
+// JNIEXPORT jint JNICALL Java_com_github_hanabi1224_RuAnnoy_NativeMethods_getNearestToItemDirect
+//   (JNIEnv *, jclass, jlong, jlong, jint, jint, jobject, jobject);
+ffi_fn! {
+    fn Java_com_github_hanabi1224_RuAnnoy_NativeMethods_getNearestToItemDirect(
+        env: JNIEnv,
+        _class: jclass,
+        pointer: jlong,
+        item_index: jlong,
+        n_results: jint,
+        search_k: jint,
+        id_list: JByteBuffer,
+        distance_list: JByteBuffer,
+    ) -> jint {
+        let mut env = env;
+        let index = unsafe { &*(pointer as *const AnnoyIndex) };
+        let Some(item_index) = check_item(&mut env, index, item_index) else {
+            return 0;
+        };
+        let r = index.get_nearest_to_item(
+            item_index,
+            n_results as usize,
+            search_k,
+            !distance_list.is_null(),
+        );
+        let result = write_direct(&mut env, &r, &id_list, &distance_list);
+        throw_on_error(&mut env, result)
+    }
+}
+
+/// Writes ids and, unless `distance_list` is null, distances of `r` into direct buffers.
+fn write_direct(
+    env: &mut JNIEnv,
+    r: &AnnoyIndexSearchResult,
+    id_list: &JByteBuffer,
+    distance_list: &JByteBuffer,
+) -> Result<jint, Box<dyn Error>> {
+    let ids = direct_ptr::<u64>(env, id_list, r.count, "idList")?;
+    if !distance_list.is_null() {
+        let distances = direct_ptr::<f32>(env, distance_list, r.count, "distanceList")?;
+        let id_bytes = ids as usize..ids.wrapping_add(r.count) as usize;
+        let distance_bytes = distances as usize..distances.wrapping_add(r.count) as usize;
+        if id_bytes.start < distance_bytes.end && distance_bytes.start < id_bytes.end {
+            return Err("idList and distanceList overlap".into());
+        }
+        unsafe { slice::from_raw_parts_mut(distances, r.count) }.copy_from_slice(&r.distance_list);
+    }
+    unsafe { slice::from_raw_parts_mut(ids, r.count) }.copy_from_slice(&r.id_list);
+    Ok(r.count as jint)
+}
+
+/// Address of a direct buffer holding at least `len` elements, which must be in native byte order.
+/// Buffers are used from their base address, callers pass a `slice()` to start at the position.
+fn direct_ptr<T>(
+    env: &mut JNIEnv,
+    buffer: &JByteBuffer,
+    len: usize,
+    name: &str,
+) -> Result<*mut T, Box<dyn Error>> {
+    if buffer.is_null() {
+        return Err(format!("{name} is null").into());
+    }
+    let ptr = env
+        .get_direct_buffer_address(buffer)
+        .map_err(|_| format!("{name} is not a direct buffer"))?;
+    let capacity = env.get_direct_buffer_capacity(buffer)?;
+    if capacity < len * mem::size_of::<T>() {
+        return Err(format!(
+            "{name} holds {capacity} bytes, {} required",
+            len * mem::size_of::<T>()
+        )
+        .into());
+    }
+    let ptr = ptr as *mut T;
+    if !ptr.is_aligned() {
+        return Err(format!("{name} is not {}-byte aligned", mem::align_of::<T>()).into());
+    }
+    Ok(ptr)
+}
+
+/// Throws `IndexOutOfBoundsException` and returns `None` unless `item_index` is an item of `index`.
+fn check_item(env: &mut JNIEnv, index: &AnnoyIndex, item_index: jlong) -> Option<u64> {
+    if item_index < 0 || item_index as usize >= index.size {
+        let _ = env.throw_new(
+            "java/lang/IndexOutOfBoundsException",
+            format!(
+                "Item index {item_index} out of range, size is {}",
+                index.size
+            ),
+        );
+        return None;
+    }
+    Some(item_index as u64)
+}
+
+fn throw_on_error(env: &mut JNIEnv, result: Result<jint, Box<dyn Error>>) -> jint {
+    match result {
+        Ok(count) => count,
+        Err(err) => {
+            let _ = env.throw_new("java/lang/IllegalArgumentException", err.to_string());
+            0
+        }
+    }
+}
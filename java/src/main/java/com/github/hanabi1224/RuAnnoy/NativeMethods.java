@@ -1,10 +1,20 @@
 package com.github.hanabi1224.RuAnnoy;
 
+import java.io.IOException;
+import java.nio.ByteBuffer;
+
 // jni: javac -h . src\main\java\com\github\hanabi1224\RuAnnoy\NativeMethods.java
 class NativeMethods
 {
     native static long loadIndex(String path, int dimension, byte type);
 
+    native static long loadIndexFromPath(String path, int dimension, byte type, boolean intoMemory) throws IOException;
+
+    native static long loadIndexFromBytes(byte[] data, int dimension, byte type);
+
+    // data must be a 4-byte aligned direct buffer, which is used without copying.
+    native static long loadIndexFromDirectBuffer(ByteBuffer data, int dimension, byte type);
+
     native static void freeIndex(long pointer);
 
     native static long getIndexSize(long pointer);
@@ -30,4 +40,23 @@ class NativeMethods
             long[] idList,
             float[] distanceList
         );
+
+    // Buffers must be direct and in native byte order, distanceList may be null.
+    native static int getNearestDirect(
+            long indexPointer,
+            ByteBuffer queryVector,
+            int nResult,
+            int searchK,
+            ByteBuffer idList,
+            ByteBuffer distanceList
+        );
+
+    native static int getNearestToItemDirect(
+            long indexPointer,
+            long itemIndex,
+            int nResult,
+            int searchK,
+            ByteBuffer idList,
+            ByteBuffer distanceList
+        );
 }
@@ -4,6 +4,9 @@
 package com.github.hanabi1224.RuAnnoy
 
 import java.io.Closeable
+import java.io.IOException
+import java.io.InputStream
+import java.nio.ByteBuffer
 
 public enum class IndexType(val value: Byte) {
     Angular(0),
@@ -37,13 +40,48 @@ public interface IAnnoyIndex : Closeable {
             searchK: Int,
             shouldIncludeDistance: Boolean
     ): AnnoyIndexSearchResult
+    /** Writes results into caller-owned arrays, skipping distances if [distanceList] is null. */
+    fun getNearestInto(
+            queryVector: FloatArray,
+            nResult: Int,
+            searchK: Int,
+            idList: LongArray,
+            distanceList: FloatArray?
+    ): Int
+    fun getNearestToItemInto(
+            itemIndex: Long,
+            nResult: Int,
+            searchK: Int,
+            idList: LongArray,
+            distanceList: FloatArray?
+    ): Int
+    /**
+     * Same as above with direct buffers in native byte order, which are read and written from their
+     * position without copies. [idList] and [distanceList] must not overlap.
+     */
+    fun getNearestInto(
+            queryVector: ByteBuffer,
+            nResult: Int,
+            searchK: Int,
+            idList: ByteBuffer,
+            distanceList: ByteBuffer?
+    ): Int
+    fun getNearestToItemInto(
+            itemIndex: Long,
+            nResult: Int,
+            searchK: Int,
+            idList: ByteBuffer,
+            distanceList: ByteBuffer?
+    ): Int
 }
 
 public class AnnoyIndex(
         val pointer: Long,
         override val dimension: Int,
         override val type: IndexType,
-        override val size: Long
+        override val size: Long,
+        // Keeps a direct buffer the index was loaded from reachable until the index is closed.
+        private val buffer: ByteBuffer? = null
 ) : IAnnoyIndex {
     public override fun getItemVector(itemIndex: Long): FloatArray {
         return NativeMethods.getItemVector(this.pointer, itemIndex)
@@ -101,11 +139,82 @@ public class AnnoyIndex(
         return AnnoyIndexSearchResult(count, shouldIncludeDistance, idList, distanceList)
     }
 
+    public override fun getNearestInto(
+            queryVector: FloatArray,
+            nResult: Int,
+            searchK: Int,
+            idList: LongArray,
+            distanceList: FloatArray?
+    ): Int {
+        checkCapacity(nResult, idList.size, distanceList?.size)
+        return NativeMethods.getNearest(
+                this.pointer,
+                queryVector,
+                nResult,
+                searchK,
+                distanceList != null,
+                idList,
+                distanceList ?: emptyDistanceList)
+    }
+
+    public override fun getNearestToItemInto(
+            itemIndex: Long,
+            nResult: Int,
+            searchK: Int,
+            idList: LongArray,
+            distanceList: FloatArray?
+    ): Int {
+        checkCapacity(nResult, idList.size, distanceList?.size)
+        return NativeMethods.getNearestToItem(
+                this.pointer,
+                itemIndex,
+                nResult,
+                searchK,
+                distanceList != null,
+                idList,
+                distanceList ?: emptyDistanceList)
+    }
+
+    public override fun getNearestInto(
+            queryVector: ByteBuffer,
+            nResult: Int,
+            searchK: Int,
+            idList: ByteBuffer,
+            distanceList: ByteBuffer?
+    ): Int {
+        return NativeMethods.getNearestDirect(
+                this.pointer,
+                queryVector.slice(),
+                nResult,
+                searchK,
+                idList.slice(),
+                distanceList?.slice())
+    }
+
+    public override fun getNearestToItemInto(
+            itemIndex: Long,
+            nResult: Int,
+            searchK: Int,
+            idList: ByteBuffer,
+            distanceList: ByteBuffer?
+    ): Int {
+        return NativeMethods.getNearestToItemDirect(
+                this.pointer, itemIndex, nResult, searchK, idList.slice(), distanceList?.slice())
+    }
+
+    private fun checkCapacity(nResult: Int, idListSize: Int, distanceListSize: Int?) {
+        require(idListSize >= nResult) { "idList holds $idListSize ids, $nResult required" }
+        require(distanceListSize == null || distanceListSize >= nResult) {
+            "distanceList holds $distanceListSize distances, $nResult required"
+        }
+    }
+
     public override fun close() {
         NativeMethods.freeIndex(pointer)
     }
 
     companion object {
+        private val emptyDistanceList = FloatArray(0)
         val nativeLibraryLoaded = NativeLibraryLoader.loadLibrary("/", "annoy_rs_jni")
         @JvmStatic
         public fun isNativeLibraryLoaded(): Boolean {
@@ -124,5 +233,81 @@ public class AnnoyIndex(
             val size = NativeMethods.getIndexSize(pointer)
             return AnnoyIndex(pointer, dimension, type, size)
         }
+
+        /**
+         * Memory maps the index file, or reads it into memory if [intoMemory] is true.
+         *
+         * @throws IOException if the file can't be read
+         * @throws IllegalArgumentException if the arguments or the index are invalid
+         */
+        @JvmStatic
+        @JvmOverloads
+        @Throws(IOException::class)
+        public fun load(
+                path: String,
+                dimension: Int,
+                type: IndexType,
+                intoMemory: Boolean = false,
+        ): IAnnoyIndex {
+            val pointer = NativeMethods.loadIndexFromPath(path, dimension, type.value, intoMemory)
+            return fromPointer(pointer, dimension, type, null)
+        }
+
+        /** Loads an index from a copy of [data]. */
+        @JvmStatic
+        public fun load(
+                data: ByteArray,
+                dimension: Int,
+                type: IndexType,
+        ): IAnnoyIndex {
+            val pointer = NativeMethods.loadIndexFromBytes(data, dimension, type.value)
+            return fromPointer(pointer, dimension, type, null)
+        }
+
+        /**
+         * Loads an index from the remaining bytes of [data], 4-byte aligned direct buffers are used
+         * without copying and must not be modified while the index is open.
+         */
+        @JvmStatic
+        public fun load(
+                data: ByteBuffer,
+                dimension: Int,
+                type: IndexType,
+        ): IAnnoyIndex {
+            if (!data.isDirect) {
+                val bytes = ByteArray(data.remaining())
+                data.duplicate().get(bytes)
+                return load(bytes, dimension, type)
+            }
+            var slice = data.slice()
+            if (slice.alignmentOffset(0, 4) != 0) {
+                // Nodes are read in place as 4-byte values, copy misaligned bytes once
+                slice = ByteBuffer.allocateDirect(data.remaining()).put(data.duplicate())
+                slice.flip()
+            }
+            val pointer = NativeMethods.loadIndexFromDirectBuffer(slice, dimension, type.value)
+            return fromPointer(pointer, dimension, type, slice)
+        }
+
+        /** Reads the whole [stream], e.g. an index bundled as a jar resource, into memory. */
+        @JvmStatic
+        @Throws(IOException::class)
+        public fun load(
+                stream: InputStream,
+                dimension: Int,
+                type: IndexType,
+        ): IAnnoyIndex {
+            return load(stream.readBytes(), dimension, type)
+        }
+
+        private fun fromPointer(
+                pointer: Long,
+                dimension: Int,
+                type: IndexType,
+                buffer: ByteBuffer?
+        ): IAnnoyIndex {
+            val size = NativeMethods.getIndexSize(pointer)
+            return AnnoyIndex(pointer, dimension, type, size, buffer)
+        }
     }
 }
@@ -3,6 +3,10 @@
  */
 package com.github.hanabi1224.RuAnnoy
 
+import java.io.File
+import java.io.IOException
+import java.nio.ByteBuffer
+import java.nio.ByteOrder
 import java.util.Arrays
 import kotlin.test.*
 
@@ -88,6 +92,104 @@ class AnnoyIndexTest {
                 }
         }
 
+        @Test
+        fun testCallerAllocatedBuffers() {
+                if (!System.getenv("JITPACK").isNullOrBlank()) {
+                        return
+                }
+
+                val indexPath = "$pwd/src/test/resources/index.angular.5d.ann"
+                val index = AnnoyIndex.tryLoad(indexPath, 5, IndexType.Angular)
+                assertTrue(index != null, "indexPath: $indexPath")
+                index.use {
+                        val v0 = index.getItemVector(0)
+                        val expected = index.getNearest(v0, 5, -1, true)
+
+                        val idList = LongArray(8)
+                        val distanceList = FloatArray(8)
+                        assertEquals(5, index.getNearestInto(v0, 5, -1, idList, distanceList))
+                        assertContentEquals(expected.idList, idList.copyOf(5))
+                        assertContentEquals(expected.distanceList, distanceList.copyOf(5))
+                        assertEquals(5, index.getNearestToItemInto(0, 5, -1, idList, null))
+                        assertContentEquals(expected.idList, idList.copyOf(5))
+                        assertFailsWith<IllegalArgumentException> {
+                                index.getNearestInto(v0, 9, -1, idList, null)
+                        }
+
+                        val query = ByteBuffer.allocateDirect(5 * 4).order(ByteOrder.nativeOrder())
+                        query.asFloatBuffer().put(v0)
+                        val idBuffer = ByteBuffer.allocateDirect(5 * 8).order(ByteOrder.nativeOrder())
+                        val distanceBuffer =
+                                        ByteBuffer.allocateDirect(5 * 4).order(ByteOrder.nativeOrder())
+                        assertEquals(5, index.getNearestInto(query, 5, -1, idBuffer, distanceBuffer))
+                        val ids = LongArray(5)
+                        idBuffer.asLongBuffer().get(ids)
+                        assertContentEquals(expected.idList, ids)
+                        val distances = FloatArray(5)
+                        distanceBuffer.asFloatBuffer().get(distances)
+                        assertContentEquals(expected.distanceList, distances)
+
+                        assertEquals(5, index.getNearestToItemInto(0, 5, -1, idBuffer, null))
+                        assertFailsWith<IllegalArgumentException> {
+                                index.getNearestToItemInto(0, 6, -1, idBuffer, null)
+                        }
+                        val offsetIds = ByteBuffer.allocateDirect(6 * 8).order(ByteOrder.nativeOrder())
+                        offsetIds.putLong(0, -1L).position(8)
+                        assertEquals(5, index.getNearestToItemInto(0, 5, -1, offsetIds, null))
+                        assertEquals(-1L, offsetIds.getLong(0))
+                        offsetIds.asLongBuffer().get(ids)
+                        assertContentEquals(expected.idList, ids)
+                        assertFailsWith<IllegalArgumentException> {
+                                index.getNearestToItemInto(0, 6, -1, offsetIds, null)
+                        }
+                        assertFailsWith<IllegalArgumentException> {
+                                index.getNearestToItemInto(0, 5, -1, idBuffer, idBuffer)
+                        }
+                        assertFailsWith<IndexOutOfBoundsException> {
+                                index.getNearestToItemInto(100, 5, -1, idBuffer, null)
+                        }
+                        assertFailsWith<IndexOutOfBoundsException> { index.getNearestToItem(-1, 5, -1, false) }
+                        assertFailsWith<IndexOutOfBoundsException> { index.getItemVector(100) }
+                }
+        }
+
+        @Test
+        fun testLoadVariants() {
+                if (!System.getenv("JITPACK").isNullOrBlank()) {
+                        return
+                }
+
+                val indexPath = "$pwd/src/test/resources/index.angular.5d.ann"
+                val bytes = File(indexPath).readBytes()
+                val direct = ByteBuffer.allocateDirect(bytes.size)
+                direct.put(bytes).flip()
+                val misaligned = ByteBuffer.allocateDirect(bytes.size + 1)
+                misaligned.put(0.toByte()).put(bytes).flip()
+                misaligned.position(1)
+                val indexes =
+                                listOf(
+                                                AnnoyIndex.load(indexPath, 5, IndexType.Angular),
+                                                AnnoyIndex.load(indexPath, 5, IndexType.Angular, true),
+                                                AnnoyIndex.load(bytes, 5, IndexType.Angular),
+                                                AnnoyIndex.load(ByteBuffer.wrap(bytes), 5, IndexType.Angular),
+                                                AnnoyIndex.load(direct, 5, IndexType.Angular),
+                                                AnnoyIndex.load(misaligned, 5, IndexType.Angular),
+                                                AnnoyIndex.load(bytes.inputStream(), 5, IndexType.Angular),
+                                )
+                for (index in indexes) {
+                        index.use {
+                                assertEquals(100, index.size)
+                                val nearest = index.getNearestToItem(0, 5, -1, false)
+                                assertContentEquals(longArrayOf(0, 4, 37, 61, 29), nearest.idList)
+                        }
+                }
+
+                assertFailsWith<IOException> { AnnoyIndex.load("invalid.ann", 5, IndexType.Angular) }
+                assertFailsWith<IllegalArgumentException> {
+                        AnnoyIndex.load(indexPath, 0, IndexType.Angular)
+                }
+        }
+
         companion object {
                 val pwd = System.getProperty("user.dir")
         }
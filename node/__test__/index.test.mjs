@@ -0,0 +1,70 @@
+import assert from 'node:assert/strict'
+import { createRequire } from 'node:module'
+import { dirname, join } from 'node:path'
+import { test } from 'node:test'
+import { fileURLToPath } from 'node:url'
+
+const require = createRequire(import.meta.url)
+const { AnnoyIndex, IndexType } = require('../index.js')
+
+const INDEX_PATH = join(dirname(fileURLToPath(import.meta.url)), '..', '..', 'tests', 'index.angular.5d.ann')
+const EXPECTED_IDS = [0, 4, 37, 61, 29]
+const EXPECTED_DISTANCES = [0.0, 0.41608822, 0.5517523, 0.73420954, 0.7592961]
+
+function assertDistances(actual, expected) {
+  assert.equal(actual.length, expected.length)
+  actual.forEach((d, i) => assert.ok(Math.abs(d - expected[i]) < 1e-5))
+}
+
+test('properties', () => {
+  const index = AnnoyIndex.load(INDEX_PATH, 5, IndexType.Angular)
+  assert.equal(index.dimension, 5)
+  assert.equal(index.size, 100)
+})
+
+test('getItemVector', () => {
+  const index = AnnoyIndex.load(INDEX_PATH, 5, IndexType.Angular)
+  const v3 = index.getItemVector(3)
+  assert.ok(v3 instanceof Float32Array)
+  assertDistances(v3, [-0.38846132, 0.87912065, 0.058009166, 0.86642665, 0.40251824])
+  assert.throws(() => index.getItemVector(100))
+})
+
+test('getNearest', () => {
+  const index = AnnoyIndex.load(INDEX_PATH, 5, IndexType.Angular)
+  const v0 = index.getItemVector(0)
+
+  let result = index.getNearest(v0, 5)
+  assert.deepEqual([...result.ids], EXPECTED_IDS)
+  assert.equal(result.distances, undefined)
+
+  result = index.getNearest(v0, 5, -1, true)
+  assert.deepEqual([...result.ids], EXPECTED_IDS)
+  assertDistances(result.distances, EXPECTED_DISTANCES)
+
+  result = index.getNearestToItem(0, 5, -1, true)
+  assert.deepEqual([...result.ids], EXPECTED_IDS)
+  assertDistances(result.distances, EXPECTED_DISTANCES)
+
+  assert.throws(() => index.getNearest(new Float32Array(4), 5))
+})
+
+test('getNearestAsync', async () => {
+  const index = AnnoyIndex.load(INDEX_PATH, 5, IndexType.Angular)
+  const v0 = index.getItemVector(0)
+
+  let result = await index.getNearestAsync(v0, 5, -1, true)
+  assert.deepEqual([...result.ids], EXPECTED_IDS)
+  assertDistances(result.distances, EXPECTED_DISTANCES)
+
+  result = await index.getNearestToItemAsync(0, 5)
+  assert.deepEqual([...result.ids], EXPECTED_IDS)
+
+  const results = await Promise.all([1, 2, 3].map(() => index.getNearestAsync(v0, 5)))
+  results.forEach((r) => assert.deepEqual([...r.ids], EXPECTED_IDS))
+})
+
+test('invalid index', () => {
+  assert.throws(() => AnnoyIndex.load('invalid_index.ann', 5, IndexType.Angular))
+  assert.throws(() => AnnoyIndex.load(INDEX_PATH, 5, IndexType.Hamming))
+})
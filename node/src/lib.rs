@@ -0,0 +1,233 @@
+#[macro_use]
+extern crate napi_derive;
+
+use annoy_rs::*;
+use napi::bindgen_prelude::*;
+use std::sync::Arc;
+
+#[napi(js_name = "IndexType")]
+pub enum JsIndexType {
+    Angular = 0,
+    Euclidean = 1,
+    Manhattan = 2,
+    Hamming = 3,
+    Dot = 4,
+}
+
+impl From<JsIndexType> for IndexType {
+    fn from(index_type: JsIndexType) -> Self {
+        match index_type {
+            JsIndexType::Angular => IndexType::Angular,
+            JsIndexType::Euclidean => IndexType::Euclidean,
+            JsIndexType::Manhattan => IndexType::Manhattan,
+            JsIndexType::Hamming => IndexType::Hamming,
+            JsIndexType::Dot => IndexType::Dot,
+        }
+    }
+}
+
+#[napi(object)]
+pub struct SearchResult {
+    pub ids: Uint32Array,
+    pub distances: Option<Float32Array>,
+}
+
+impl From<AnnoyIndexSearchResult> for SearchResult {
+    fn from(result: AnnoyIndexSearchResult) -> Self {
+        let ids: Vec<u32> = result.id_list.iter().map(|&id| id as u32).collect();
+        SearchResult {
+            ids: ids.into(),
+            distances: if result.is_distance_included {
+                Some(result.distance_list.into())
+            } else {
+                None
+            },
+        }
+    }
+}
+
+#[napi(js_name = "AnnoyIndex")]
+pub struct JsAnnoyIndex {
+    index: Arc<AnnoyIndex>,
+}
+
+#[napi]
+impl JsAnnoyIndex {
+    /// Memory maps the index file, so that it's not copied into the js heap.
+    #[napi(factory)]
+    pub fn load(path: String, dimension: u32, index_type: JsIndexType) -> Result<Self> {
+        let index = AnnoyIndex::load(dimension as usize, &path, index_type.into())
+            .map_err(|err| Error::from_reason(err.to_string()))?;
+        Ok(Self {
+            index: Arc::new(index),
+        })
+    }
+
+    #[napi(getter)]
+    pub fn dimension(&self) -> u32 {
+        self.index.dimension as u32
+    }
+
+    #[napi(getter)]
+    pub fn size(&self) -> u32 {
+        self.index.size as u32
+    }
+
+    #[napi]
+    pub fn get_item_vector(&self, item_index: u32) -> Result<Float32Array> {
+        self.check_item(item_index)?;
+        Ok(self.index.get_item_vector(item_index as u64).into())
+    }
+
+    #[napi]
+    pub fn get_nearest(
+        &self,
+        query_vector: Float32Array,
+        n_results: u32,
+        search_k: Option<i32>,
+        should_include_distance: Option<bool>,
+    ) -> Result<SearchResult> {
+        let mut task = self.search_task(
+            Query::Vector(query_vector.to_vec()),
+            n_results,
+            search_k,
+            should_include_distance,
+        )?;
+        Ok(task.compute()?.into())
+    }
+
+    #[napi]
+    pub fn get_nearest_to_item(
+        &self,
+        item_index: u32,
+        n_results: u32,
+        search_k: Option<i32>,
+        should_include_distance: Option<bool>,
+    ) -> Result<SearchResult> {
+        let mut task = self.search_task(
+            Query::Item(item_index),
+            n_results,
+            search_k,
+            should_include_distance,
+        )?;
+        Ok(task.compute()?.into())
+    }
+
+    /// Same as `getNearest`, but searches on the libuv threadpool without blocking the event loop.
+    #[napi(ts_return_type = "Promise<SearchResult>")]
+    pub fn get_nearest_async(
+        &self,
+        query_vector: Float32Array,
+        n_results: u32,
+        search_k: Option<i32>,
+        should_include_distance: Option<bool>,
+        signal: Option<AbortSignal>,
+    ) -> Result<AsyncTask<SearchTask>> {
+        let task = self.search_task(
+            Query::Vector(query_vector.to_vec()),
+            n_results,
+            search_k,
+            should_include_distance,
+        )?;
+        Ok(AsyncTask::with_optional_signal(task, signal))
+    }
+
+    #[napi(ts_return_type = "Promise<SearchResult>")]
+    pub fn get_nearest_to_item_async(
+        &self,
+        item_index: u32,
+        n_results: u32,
+        search_k: Option<i32>,
+        should_include_distance: Option<bool>,
+        signal: Option<AbortSignal>,
+    ) -> Result<AsyncTask<SearchTask>> {
+        let task = self.search_task(
+            Query::Item(item_index),
+            n_results,
+            search_k,
+            should_include_distance,
+        )?;
+        Ok(AsyncTask::with_optional_signal(task, signal))
+    }
+}
+
+impl JsAnnoyIndex {
+    fn check_item(&self, item_index: u32) -> Result<()> {
+        if item_index as usize >= self.index.size {
+            return Err(Error::new(
+                Status::InvalidArg,
+                "item_index out of range".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn search_task(
+        &self,
+        query: Query,
+        n_results: u32,
+        search_k: Option<i32>,
+        should_include_distance: Option<bool>,
+    ) -> Result<SearchTask> {
+        match &query {
+            Query::Vector(v) if v.len() != self.index.dimension => {
+                return Err(Error::new(
+                    Status::InvalidArg,
+                    format!(
+                        "Wrong input dimension, {} expected, {} provided.",
+                        self.index.dimension,
+                        v.len()
+                    ),
+                ));
+            }
+            Query::Item(item_index) => self.check_item(*item_index)?,
+            _ => {}
+        }
+        Ok(SearchTask {
+            index: self.index.clone(),
+            query,
+            n_results: n_results as usize,
+            search_k: search_k.unwrap_or(-1),
+            should_include_distance: should_include_distance.unwrap_or(false),
+        })
+    }
+}
+
+enum Query {
+    Vector(Vec<f32>),
+    Item(u32),
+}
+
+pub struct SearchTask {
+    index: Arc<AnnoyIndex>,
+    query: Query,
+    n_results: usize,
+    search_k: i32,
+    should_include_distance: bool,
+}
+
+impl Task for SearchTask {
+    type Output = AnnoyIndexSearchResult;
+    type JsValue = SearchResult;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(match &self.query {
+            Query::Vector(v) => self.index.get_nearest(
+                v,
+                self.n_results,
+                self.search_k,
+                self.should_include_distance,
+            ),
+            Query::Item(item_index) => self.index.get_nearest_to_item(
+                *item_index as u64,
+                self.n_results,
+                self.search_k,
+                self.should_include_distance,
+            ),
+        })
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output.into())
+    }
+}
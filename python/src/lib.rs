@@ -0,0 +1,202 @@
+use annoy_rs::*;
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::exceptions::{PyIOError, PyIndexError, PyValueError};
+use pyo3::prelude::*;
+
+const INDEX_TYPES: [IndexType; 4] = [
+    IndexType::Angular,
+    IndexType::Euclidean,
+    IndexType::Manhattan,
+    IndexType::Dot,
+];
+
+fn parse_index_type(metric: &str) -> PyResult<IndexType> {
+    INDEX_TYPES
+        .iter()
+        .find(|t| t.to_string() == metric)
+        .cloned()
+        .ok_or_else(|| PyValueError::new_err(format!("Unsupported metric: {metric}")))
+}
+
+/// Ids and distances of batch queries, one row per query.
+type BatchResult<'py> = (Bound<'py, PyArray2<i64>>, Bound<'py, PyArray2<f32>>);
+
+#[pyclass(name = "AnnoyIndex", module = "ruannoy", frozen)]
+struct PyAnnoyIndex {
+    index: AnnoyIndex,
+}
+
+#[pymethods]
+impl PyAnnoyIndex {
+    /// Loads an index file, it's memory mapped unless `mmap` is False.
+    #[staticmethod]
+    #[pyo3(signature = (path, dimension, metric, mmap = true))]
+    fn load(
+        py: Python<'_>,
+        path: &str,
+        dimension: usize,
+        metric: &str,
+        mmap: bool,
+    ) -> PyResult<Self> {
+        let index_type = parse_index_type(metric)?;
+        let index = py
+            .detach(|| {
+                if mmap {
+                    AnnoyIndex::load(dimension, path, index_type)
+                } else {
+                    AnnoyIndex::load_into_mem(dimension, path, index_type)
+                }
+                .map_err(|err| err.to_string())
+            })
+            .map_err(PyIOError::new_err)?;
+        Ok(Self { index })
+    }
+
+    #[staticmethod]
+    fn from_bytes(data: Vec<u8>, dimension: usize, metric: &str) -> PyResult<Self> {
+        let index_type = parse_index_type(metric)?;
+        let index = AnnoyIndex::load_from_buffer(data, dimension, index_type)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(Self { index })
+    }
+
+    #[getter]
+    fn dimension(&self) -> usize {
+        self.index.dimension
+    }
+
+    #[getter]
+    fn size(&self) -> usize {
+        self.index.size
+    }
+
+    #[getter]
+    fn metric(&self) -> String {
+        self.index.index_type.to_string()
+    }
+
+    fn __len__(&self) -> usize {
+        self.index.size
+    }
+
+    fn get_item_vector<'py>(
+        &self,
+        py: Python<'py>,
+        item: u64,
+    ) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        self.check_item(item)?;
+        Ok(self.index.get_item_vector(item).into_pyarray(py))
+    }
+
+    /// Returns ids of the nearest items, or a tuple of ids and distances if `include_distances` is True.
+    #[pyo3(signature = (query, n, search_k = -1, include_distances = false))]
+    fn get_nearest<'py>(
+        &self,
+        py: Python<'py>,
+        query: PyReadonlyArray1<'py, f32>,
+        n: usize,
+        search_k: i32,
+        include_distances: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let query = query.as_array().to_vec();
+        self.check_dimension(query.len())?;
+        let result = py.detach(|| {
+            self.index
+                .get_nearest(&query, n, search_k, include_distances)
+        });
+        convert_result(py, result)
+    }
+
+    #[pyo3(signature = (item, n, search_k = -1, include_distances = false))]
+    fn get_nearest_to_item<'py>(
+        &self,
+        py: Python<'py>,
+        item: u64,
+        n: usize,
+        search_k: i32,
+        include_distances: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.check_item(item)?;
+        let result = py.detach(|| {
+            self.index
+                .get_nearest_to_item(item, n, search_k, include_distances)
+        });
+        convert_result(py, result)
+    }
+
+    /// Searches every row of `queries`, returning 2-D arrays of ids and distances,
+    /// rows with less than `n` results are padded with -1 and nan.
+    #[pyo3(signature = (queries, n, search_k = -1))]
+    fn get_nearest_batch<'py>(
+        &self,
+        py: Python<'py>,
+        queries: PyReadonlyArray2<'py, f32>,
+        n: usize,
+        search_k: i32,
+    ) -> PyResult<BatchResult<'py>> {
+        let queries = queries.as_array();
+        let n_queries = queries.nrows();
+        self.check_dimension(queries.ncols())?;
+        let queries: Vec<f32> = queries.iter().copied().collect();
+        let (id_list, distance_list) = py.detach(|| {
+            let mut id_list = vec![-1_i64; n_queries * n];
+            let mut distance_list = vec![f32::NAN; n_queries * n];
+            for (i, query) in queries.chunks_exact(self.index.dimension).enumerate() {
+                let result = self.index.get_nearest(query, n, search_k, true);
+                for (j, (&id, &distance)) in result
+                    .id_list
+                    .iter()
+                    .zip(result.distance_list.iter())
+                    .enumerate()
+                {
+                    id_list[i * n + j] = id as i64;
+                    distance_list[i * n + j] = distance;
+                }
+            }
+            (id_list, distance_list)
+        });
+        let shape = (n_queries, n);
+        let id_list = Array2::from_shape_vec(shape, id_list)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        let distance_list = Array2::from_shape_vec(shape, distance_list)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok((id_list.into_pyarray(py), distance_list.into_pyarray(py)))
+    }
+}
+
+impl PyAnnoyIndex {
+    fn check_item(&self, item: u64) -> PyResult<()> {
+        if item as usize >= self.index.size {
+            return Err(PyIndexError::new_err("item index out of range"));
+        }
+        Ok(())
+    }
+
+    fn check_dimension(&self, dimension: usize) -> PyResult<()> {
+        if dimension != self.index.dimension {
+            return Err(PyValueError::new_err(format!(
+                "Wrong input dimension, {} expected, {} provided.",
+                self.index.dimension, dimension
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn convert_result(py: Python<'_>, result: AnnoyIndexSearchResult) -> PyResult<Bound<'_, PyAny>> {
+    let id_list: Vec<i64> = result.id_list.iter().map(|&id| id as i64).collect();
+    let id_list = id_list.into_pyarray(py);
+    if result.is_distance_included {
+        let distance_list = result.distance_list.into_pyarray(py);
+        Ok((id_list, distance_list).into_pyobject(py)?.into_any())
+    } else {
+        Ok(id_list.into_any())
+    }
+}
+
+#[pymodule]
+fn ruannoy(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyAnnoyIndex>()?;
+    Ok(())
+}
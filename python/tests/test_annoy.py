@@ -0,0 +1,74 @@
+import os
+
+import numpy as np
+import pytest
+import ruannoy
+
+INDEX_PATH = os.path.join(os.path.dirname(__file__), "..", "..", "tests", "index.angular.5d.ann")
+EXPECTED_IDS = [0, 4, 37, 61, 29]
+EXPECTED_DISTANCES = [0.0, 0.41608822, 0.5517523, 0.73420954, 0.7592961]
+
+
+@pytest.fixture(params=[True, False], ids=["mmap", "in-memory"])
+def index(request):
+    return ruannoy.AnnoyIndex.load(INDEX_PATH, 5, "angular", mmap=request.param)
+
+
+def test_properties(index):
+    assert index.dimension == 5
+    assert index.size == 100
+    assert len(index) == 100
+    assert index.metric == "angular"
+
+
+def test_get_item_vector(index):
+    v3 = index.get_item_vector(3)
+    assert v3.dtype == np.float32
+    np.testing.assert_allclose(
+        v3, [-0.38846132, 0.87912065, 0.058009166, 0.86642665, 0.40251824], rtol=1e-6
+    )
+    with pytest.raises(IndexError):
+        index.get_item_vector(100)
+
+
+def test_get_nearest(index):
+    v0 = index.get_item_vector(0)
+    ids = index.get_nearest(v0, 5)
+    assert ids.tolist() == EXPECTED_IDS
+
+    ids, distances = index.get_nearest(v0, 5, include_distances=True)
+    assert ids.tolist() == EXPECTED_IDS
+    np.testing.assert_allclose(distances, EXPECTED_DISTANCES, atol=1e-5)
+
+    ids, distances = index.get_nearest_to_item(0, 5, include_distances=True)
+    assert ids.tolist() == EXPECTED_IDS
+    np.testing.assert_allclose(distances, EXPECTED_DISTANCES, atol=1e-5)
+
+    with pytest.raises(ValueError):
+        index.get_nearest(np.zeros(4, dtype=np.float32), 5)
+
+
+def test_get_nearest_batch(index):
+    queries = np.stack([index.get_item_vector(0), index.get_item_vector(0)])
+    ids, distances = index.get_nearest_batch(queries, 5)
+    assert ids.shape == (2, 5)
+    assert distances.shape == (2, 5)
+    assert ids[0].tolist() == EXPECTED_IDS
+    assert ids[1].tolist() == EXPECTED_IDS
+
+    ids, distances = index.get_nearest_batch(queries, 200, search_k=10000)
+    assert (ids[:, 100:] == -1).all()
+    assert np.isnan(distances[:, 100:]).all()
+
+
+def test_from_bytes():
+    with open(INDEX_PATH, "rb") as f:
+        index = ruannoy.AnnoyIndex.from_bytes(f.read(), 5, "angular")
+    assert index.size == 100
+
+
+def test_invalid_index():
+    with pytest.raises(OSError):
+        ruannoy.AnnoyIndex.load("invalid_index.ann", 5, "angular")
+    with pytest.raises(ValueError):
+        ruannoy.AnnoyIndex.load(INDEX_PATH, 5, "hamming")
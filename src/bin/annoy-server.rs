@@ -0,0 +1,14 @@
+//! Usage: `annoy-server <config.toml>`, send SIGHUP to reload the indexes in the config.
+
+#[cfg(not(target_arch = "wasm32"))]
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+    let config_path = std::env::args()
+        .nth(1)
+        .ok_or("Usage: annoy-server <config.toml>")?;
+    annoy_rs::server::run(config_path).await
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,299 @@
+//! Command line tools for index files.
+//!
+//! ```text
+//! annoy compact <index.ann> <dimension> <index_type> <out.ann> <id_map.tsv> [--tombstones <path>] [--rebuild-trees]
+//! annoy import <vectors> <index_type> <out.ann> <n_trees> [--format <format>] [--id-column <n>] [--column <name>] [--header] [--seed <seed>] [--threads <n>] [--metadata <key=value>]...
+//! annoy recall <index.ann> <index_type> <queries> <ground_truth.ivecs> <k> [--format <format>] [--search-k <n>]
+//! annoy export <index.ann> <dimension> <index_type> <vectors> [--format <format>] [--id-column <n>] [--header] [--trees <trees.json>]
+//! annoy describe <index.ann> [<dimension> <index_type>]
+//! annoy annotate <index.ann> <dimension> <index_type> [--set <key=value>]... [--remove <key>]...
+//! annoy verify <index.ann>
+//! ```
+//!
+//! Vector files are fvecs, bvecs, npy or csv, told apart by their extension unless `--format` is given.
+//! With the `arrow` feature, vectors are also imported from the `--column` of parquet files, ids
+//! from their `--id-column` name, and exported as Arrow IPC files with the `arrow` format.
+//!
+//! `import --metadata` and `annotate` write a metadata footer, which `describe` prints. Plain annoy
+//! files are described from their dimension and index type. `verify` checks the checksums of the
+//! footer and lists corrupt ranges, failing for files without one.
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("compact") => compact(&args[1..]),
+        Some("import") => import(&args[1..]),
+        Some("recall") => recall(&args[1..]),
+        Some("export") => export(&args[1..]),
+        Some("describe") => describe(&args[1..]),
+        Some("annotate") => annotate(&args[1..]),
+        Some("verify") => verify(&args[1..]),
+        _ => Err(USAGE.into()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "Usage:
+    annoy compact <index.ann> <dimension> <index_type> <out.ann> <id_map.tsv> [--tombstones <path>] [--rebuild-trees]
+    annoy import <vectors> <index_type> <out.ann> <n_trees> [--format <format>] [--id-column <n>] [--column <name>] [--header] [--seed <seed>] [--threads <n>] [--metadata <key=value>]...
+    annoy recall <index.ann> <index_type> <queries> <ground_truth.ivecs> <k> [--format <format>] [--search-k <n>]
+    annoy export <index.ann> <dimension> <index_type> <vectors> [--format <format>] [--id-column <n>] [--header] [--trees <trees.json>]
+    annoy describe <index.ann> [<dimension> <index_type>]
+    annoy annotate <index.ann> <dimension> <index_type> [--set <key=value>]... [--remove <key>]...
+    annoy verify <index.ann>";
+
+/// Rebuilds a dense index from the live items and writes the old to new id mapping.
+#[cfg(not(target_arch = "wasm32"))]
+fn compact(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use annoy_rs::*;
+
+    let [index_path, dimension, index_type, out_path, id_map_path, options @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let mut index = AnnoyIndex::load(dimension.parse()?, index_path, index_type.parse()?)?;
+    let mut preserve_splits = true;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--tombstones" => index.load_tombstones(options.next().ok_or(USAGE)?)?,
+            "--rebuild-trees" => preserve_splits = false,
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let compaction = index.compact(preserve_splits)?;
+    compaction.save(out_path, id_map_path)?;
+    println!(
+        "{} of {} items kept, {} trees",
+        compaction.size(),
+        index.size,
+        index.n_trees()
+    );
+    Ok(())
+}
+
+/// Builds an index from a file of vectors.
+#[cfg(not(target_arch = "wasm32"))]
+fn import(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use annoy_rs::*;
+
+    let [vectors_path, index_type, out_path, n_trees, options @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let mut format = vectors_path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase());
+    let (mut id_column, mut column, mut has_header) = (None, None, false);
+    let (mut seed, mut n_threads) = (None, 1);
+    let mut metadata = Vec::new();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let mut value = || options.next().ok_or(USAGE);
+        match option.as_str() {
+            "--format" => format = Some(value()?.clone()),
+            "--id-column" => id_column = Some(value()?),
+            "--column" => column = Some(value()?),
+            "--header" => has_header = true,
+            "--seed" => seed = Some(value()?.parse()?),
+            "--threads" => n_threads = value()?.parse()?,
+            "--metadata" => metadata.push(parse_entry(value()?)?),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let vectors = match format.as_deref() {
+        #[cfg(feature = "arrow")]
+        Some("parquet") => Vectors::read_parquet(
+            vectors_path,
+            column.ok_or("Pass the --column of the vectors")?,
+            id_column.map(String::as_str),
+        )?,
+        Some(format) => {
+            let import_options = ImportOptions {
+                id_column: id_column.map(|column| column.parse()).transpose()?,
+                has_header,
+            };
+            if column.is_some() {
+                return Err("--column only applies to parquet files".into());
+            }
+            Vectors::read(vectors_path, format.parse()?, &import_options)?
+        }
+        None => return Err("Unknown vector format, pass --format".into()),
+    };
+    let mut builder = AnnoyIndexBuilder::new(vectors.dimension, index_type.parse()?)?;
+    if let Some(seed) = seed {
+        builder.set_seed(seed);
+    }
+    builder.set_n_threads(n_threads);
+    for (key, value) in metadata {
+        builder.insert_metadata(key, value);
+    }
+    vectors.add_to(&mut builder)?;
+    builder.save(n_trees.parse()?, out_path)?;
+    println!(
+        "{} items of dimension {} indexed",
+        vectors.len(),
+        vectors.dimension
+    );
+    Ok(())
+}
+
+/// Measures the recall of searches against ground truth nearest neighbors.
+#[cfg(not(target_arch = "wasm32"))]
+fn recall(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use annoy_rs::*;
+
+    let [index_path, index_type, queries_path, ground_truth_path, k, options @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let mut format = VectorFormat::from_path(queries_path);
+    let mut search_k = -1;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let mut value = || options.next().ok_or(USAGE);
+        match option.as_str() {
+            "--format" => format = Some(value()?.parse()?),
+            "--search-k" => search_k = value()?.parse()?,
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let format = format.ok_or("Unknown vector format, pass --format")?;
+    let queries = Vectors::read(queries_path, format, &ImportOptions::default())?;
+    let ground_truth = GroundTruth::read_ivecs(ground_truth_path)?;
+    let index = AnnoyIndex::load(queries.dimension, index_path, index_type.parse()?)?;
+    let k = k.parse()?;
+    let recall = ground_truth.evaluate(&index, &queries, k, search_k)?;
+    println!("recall@{k} {recall:.4} over {} queries", queries.len());
+    Ok(())
+}
+
+/// Writes the item vectors of an index, and optionally its trees as JSON.
+#[cfg(not(target_arch = "wasm32"))]
+fn export(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use annoy_rs::*;
+
+    let [index_path, dimension, index_type, vectors_path, options @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let mut format = vectors_path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase());
+    let mut export_options = ImportOptions::default();
+    let mut trees_path = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let mut value = || options.next().ok_or(USAGE);
+        match option.as_str() {
+            "--format" => format = Some(value()?.clone()),
+            "--id-column" => export_options.id_column = Some(value()?.parse()?),
+            "--header" => export_options.has_header = true,
+            "--trees" => trees_path = Some(value()?),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let index = AnnoyIndex::load(dimension.parse()?, index_path, index_type.parse()?)?;
+    let vectors = index.item_vectors();
+    match format.as_deref() {
+        #[cfg(feature = "arrow")]
+        Some("arrow") => std::fs::write(vectors_path, vectors.to_arrow_ipc()?)?,
+        Some(format) => vectors.save(vectors_path, format.parse()?, &export_options)?,
+        None => return Err("Unknown vector format, pass --format".into()),
+    }
+    if let Some(trees_path) = trees_path {
+        index.save_tree_json(trees_path)?;
+    }
+    println!(
+        "{} of {} items exported, {} trees",
+        vectors.len(),
+        index.size,
+        index.n_trees()
+    );
+    Ok(())
+}
+
+/// Prints the metadata footer of an index, or describes a plain annoy file.
+#[cfg(not(target_arch = "wasm32"))]
+fn describe(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use annoy_rs::*;
+
+    let metadata = match args {
+        [index_path] => IndexMetadata::read(index_path)?.ok_or(
+            "Plain annoy file without metadata, pass its dimension and index type to describe it",
+        )?,
+        [index_path, dimension, index_type] => {
+            AnnoyIndex::load(dimension.parse()?, index_path, index_type.parse()?)?.describe()
+        }
+        _ => return Err(USAGE.into()),
+    };
+    println!("{metadata}");
+    Ok(())
+}
+
+/// Adds or replaces the metadata footer of an index.
+#[cfg(not(target_arch = "wasm32"))]
+fn annotate(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use annoy_rs::*;
+
+    let [index_path, dimension, index_type, options @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let index = AnnoyIndex::load(dimension.parse()?, index_path, index_type.parse()?)?;
+    let mut metadata = index.describe();
+    // The footer is rewritten in place, the index mustn't stay mapped
+    drop(index);
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().ok_or(USAGE)?;
+        match option.as_str() {
+            "--set" => {
+                let (key, value) = parse_entry(value)?;
+                metadata.user.insert(key, value);
+            }
+            "--remove" => {
+                metadata.user.remove(value);
+            }
+            _ => return Err(USAGE.into()),
+        }
+    }
+    metadata.write(index_path)?;
+    println!("{metadata}");
+    Ok(())
+}
+
+/// Checks the checksums of an index without loading it, which corrupt files may fail.
+#[cfg(not(target_arch = "wasm32"))]
+fn verify(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use annoy_rs::*;
+
+    let [index_path] = args else {
+        return Err(USAGE.into());
+    };
+    let file = std::fs::File::open(index_path)?;
+    let bytes = unsafe { memmap2::Mmap::map(&file)? };
+    let corrupt = IndexMetadata::verify_file(&bytes)?;
+    if corrupt.is_empty() {
+        println!("{index_path} is intact");
+        return Ok(());
+    }
+    let node_size = IndexMetadata::find(&bytes)?.and_then(|metadata| metadata.node_size());
+    for range in corrupt.iter() {
+        match node_size {
+            Some(node_size) => println!(
+                "corrupt bytes {range:?}, nodes {:?}",
+                range.start / node_size..range.end.div_ceil(node_size)
+            ),
+            None => println!("corrupt bytes {range:?}"),
+        }
+    }
+    Err(format!("{} corrupt ranges in {index_path}", corrupt.len()).into())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_entry(entry: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let (key, value) = entry
+        .split_once('=')
+        .ok_or_else(|| format!("Expected key=value, got {entry}"))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
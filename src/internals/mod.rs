@@ -1,2 +1,3 @@
 pub(crate) mod priority_queue;
+pub(crate) mod random;
 pub(crate) mod storage_ext;
@@ -0,0 +1,79 @@
+/// Port of annoy's `Kiss64Random` (kissrandom.h), so that builds are reproducible from a seed.
+#[derive(Debug, Clone)]
+pub(crate) struct Kiss64Random {
+    x: u64,
+    y: u64,
+    z: u64,
+    c: u64,
+}
+
+impl Kiss64Random {
+    pub const DEFAULT_SEED: u64 = 1234567890987654321;
+
+    pub fn new(seed: u64) -> Kiss64Random {
+        Kiss64Random {
+            x: seed,
+            y: 362436362436362436,
+            z: 1066149217761810,
+            c: 123456123456123456,
+        }
+    }
+
+    pub fn kiss(&mut self) -> u64 {
+        // Linear congruence generator
+        self.z = self.z.wrapping_mul(6906969069).wrapping_add(1234567);
+        // Xor shift
+        self.y ^= self.y << 13;
+        self.y ^= self.y >> 17;
+        self.y ^= self.y << 43;
+        // Multiply-with-carry
+        let t = (self.x << 58).wrapping_add(self.c);
+        self.c = self.x >> 6;
+        self.x = self.x.wrapping_add(t);
+        self.c = self.c.wrapping_add((self.x < t) as u64);
+        self.x.wrapping_add(self.y).wrapping_add(self.z)
+    }
+
+    pub fn flip(&mut self) -> bool {
+        self.kiss() & 1 == 1
+    }
+
+    pub fn index(&mut self, n: usize) -> usize {
+        (self.kiss() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // First outputs of annoy's Kiss64Random, printed by test_gen/kissrandom.cc
+    #[test]
+    fn test_kiss_known_answers() {
+        let mut rng = Kiss64Random::new(Kiss64Random::DEFAULT_SEED);
+        let outputs: Vec<u64> = (0..5).map(|_| rng.kiss()).collect();
+        assert_eq!(
+            outputs,
+            [
+                8932985056925012148,
+                5710300428094272059,
+                18342510866933518593,
+                14303636270573868250,
+                542381058189297533,
+            ]
+        );
+
+        let mut rng = Kiss64Random::new(42);
+        let outputs: Vec<u64> = (0..5).map(|_| rng.kiss()).collect();
+        assert_eq!(
+            outputs,
+            [
+                5680804532875375661,
+                421217147685971266,
+                15020224898898924438,
+                16951546275468841018,
+                12649982757840217320,
+            ]
+        );
+    }
+}
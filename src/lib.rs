@@ -13,6 +13,9 @@ pub use types::*;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm_exports;
 
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod server;
+
 #[cfg(test)]
 #[cfg(nightly)]
 mod tests {
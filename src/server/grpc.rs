@@ -0,0 +1,97 @@
+//! gRPC service `annoy.Annoy`, generated from `proto/annoy.proto`.
+
+use super::*;
+use tonic::{Request, Response, Status};
+
+tonic::include_proto!("annoy");
+
+pub use annoy_client::AnnoyClient;
+pub use annoy_server::{Annoy, AnnoyServer};
+
+impl From<AnnoyIndexSearchResult> for SearchResult {
+    fn from(result: AnnoyIndexSearchResult) -> Self {
+        SearchResult {
+            ids: result.id_list,
+            distances: result.distance_list,
+        }
+    }
+}
+
+impl From<QueryError> for Status {
+    fn from(err: QueryError) -> Self {
+        match err {
+            QueryError::IndexNotFound(message) => Status::not_found(message),
+            QueryError::InvalidArgument(message) => Status::invalid_argument(message),
+            QueryError::Internal(message) => Status::internal(message),
+        }
+    }
+}
+
+pub struct AnnoyService {
+    registry: IndexRegistry,
+}
+
+pub fn service(registry: IndexRegistry) -> AnnoyServer<AnnoyService> {
+    AnnoyServer::new(AnnoyService { registry })
+}
+
+#[tonic::async_trait]
+impl Annoy for AnnoyService {
+    async fn get_nearest(
+        &self,
+        request: Request<NearestRequest>,
+    ) -> Result<Response<SearchResult>, Status> {
+        let request = request.into_inner();
+        let index = self.registry.index(&request.index)?;
+        let params = self.registry.search_params(
+            request.n_results as usize,
+            request.search_k,
+            request.include_distance,
+        )?;
+        let result = super::get_nearest(index, request.vector, params).await?;
+        Ok(Response::new(result.into()))
+    }
+
+    async fn get_nearest_to_item(
+        &self,
+        request: Request<NearestToItemRequest>,
+    ) -> Result<Response<SearchResult>, Status> {
+        let request = request.into_inner();
+        let index = self.registry.index(&request.index)?;
+        let params = self.registry.search_params(
+            request.n_results as usize,
+            request.search_k,
+            request.include_distance,
+        )?;
+        let result = super::get_nearest_to_item(index, request.item, params).await?;
+        Ok(Response::new(result.into()))
+    }
+
+    async fn get_item_vector(
+        &self,
+        request: Request<ItemVectorRequest>,
+    ) -> Result<Response<ItemVector>, Status> {
+        let request = request.into_inner();
+        let index = self.registry.index(&request.index)?;
+        let vector = super::get_item_vector(index, request.item).await?;
+        Ok(Response::new(ItemVector { vector }))
+    }
+
+    async fn get_nearest_batch(
+        &self,
+        request: Request<NearestBatchRequest>,
+    ) -> Result<Response<BatchSearchResult>, Status> {
+        let request = request.into_inner();
+        let index = self.registry.index(&request.index)?;
+        let params = self.registry.search_params(
+            request.n_results as usize,
+            request.search_k,
+            request.include_distance,
+        )?;
+        let vectors = request.vectors.into_iter().map(|v| v.vector).collect();
+        let results = super::get_nearest_batch(index, vectors, params).await?;
+        Ok(Response::new(BatchSearchResult {
+            results: results.into_iter().map(Into::into).collect(),
+        }))
+    }
+}
@@ -0,0 +1,179 @@
+//! JSON-over-HTTP endpoints.
+//!
+//! - `GET /indexes`
+//! - `GET /indexes/{name}/items/{item}`
+//! - `POST /indexes/{name}/nearest` with `{"vector": [..], "n_results": 10}`
+//! - `POST /indexes/{name}/nearest_to_item` with `{"item": 0, "n_results": 10}`
+//! - `POST /indexes/{name}/nearest_batch` with `{"vectors": [[..], [..]], "n_results": 10}`
+//!
+//! Search requests optionally take `search_k` and `include_distance`, `n_results` and `search_k`
+//! above the limits of the [`ServerConfig`] are rejected with 400.
+//!
+//! `GET /metrics` serves [`metrics::recorder`] in the Prometheus text format.
+
+use super::*;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Serialize;
+
+pub fn router(registry: IndexRegistry) -> Router {
+    Router::new()
+        .route("/metrics", get(|| async { metrics::recorder().render() }))
+        .route("/indexes", get(list_indexes))
+        .route("/indexes/{name}/items/{item}", get(item_vector))
+        .route("/indexes/{name}/nearest", post(nearest))
+        .route("/indexes/{name}/nearest_to_item", post(nearest_to_item))
+        .route("/indexes/{name}/nearest_batch", post(nearest_batch))
+        .with_state(registry)
+}
+
+#[derive(Deserialize)]
+struct NearestRequest {
+    vector: Vec<f32>,
+    #[serde(flatten)]
+    params: SearchParamsJson,
+}
+
+#[derive(Deserialize)]
+struct NearestToItemRequest {
+    item: u64,
+    #[serde(flatten)]
+    params: SearchParamsJson,
+}
+
+#[derive(Deserialize)]
+struct NearestBatchRequest {
+    vectors: Vec<Vec<f32>>,
+    #[serde(flatten)]
+    params: SearchParamsJson,
+}
+
+#[derive(Deserialize)]
+struct SearchParamsJson {
+    n_results: usize,
+    #[serde(default)]
+    search_k: Option<i32>,
+    #[serde(default)]
+    include_distance: bool,
+}
+
+impl SearchParamsJson {
+    fn check(&self, registry: &IndexRegistry) -> Result<SearchParams, QueryError> {
+        registry.search_params(self.n_results, self.search_k, self.include_distance)
+    }
+}
+
+#[derive(Serialize)]
+struct IndexInfo {
+    name: String,
+    dimension: usize,
+    size: usize,
+    index_type: String,
+}
+
+#[derive(Serialize)]
+struct SearchResultJson {
+    ids: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    distances: Option<Vec<f32>>,
+}
+
+impl From<AnnoyIndexSearchResult> for SearchResultJson {
+    fn from(result: AnnoyIndexSearchResult) -> Self {
+        SearchResultJson {
+            ids: result.id_list,
+            distances: if result.is_distance_included {
+                Some(result.distance_list)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BatchSearchResultJson {
+    results: Vec<SearchResultJson>,
+}
+
+#[derive(Serialize)]
+struct ItemVectorJson {
+    vector: Vec<f32>,
+}
+
+impl IntoResponse for QueryError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            QueryError::IndexNotFound(error) => (StatusCode::NOT_FOUND, error),
+            QueryError::InvalidArgument(error) => (StatusCode::BAD_REQUEST, error),
+            QueryError::Internal(error) => (StatusCode::INTERNAL_SERVER_ERROR, error),
+        };
+        (status, Json(serde_json::json!({ "error": error }))).into_response()
+    }
+}
+
+async fn list_indexes(State(registry): State<IndexRegistry>) -> Json<Vec<IndexInfo>> {
+    let indexes = registry
+        .names()
+        .into_iter()
+        .filter_map(|name| {
+            let index = registry.get(&name)?;
+            Some(IndexInfo {
+                name,
+                dimension: index.dimension,
+                size: index.size,
+                index_type: index.index_type.to_string(),
+            })
+        })
+        .collect();
+    Json(indexes)
+}
+
+async fn item_vector(
+    State(registry): State<IndexRegistry>,
+    Path((name, item)): Path<(String, u64)>,
+) -> Result<Json<ItemVectorJson>, QueryError> {
+    let index = registry.index(&name)?;
+    let vector = get_item_vector(index, item).await?;
+    Ok(Json(ItemVectorJson { vector }))
+}
+
+async fn nearest(
+    State(registry): State<IndexRegistry>,
+    Path(name): Path<String>,
+    Json(request): Json<NearestRequest>,
+) -> Result<Json<SearchResultJson>, QueryError> {
+    let index = registry.index(&name)?;
+    let params = request.params.check(&registry)?;
+    let result = get_nearest(index, request.vector, params).await?;
+    Ok(Json(result.into()))
+}
+
+async fn nearest_to_item(
+    State(registry): State<IndexRegistry>,
+    Path(name): Path<String>,
+    Json(request): Json<NearestToItemRequest>,
+) -> Result<Json<SearchResultJson>, QueryError> {
+    let index = registry.index(&name)?;
+    let params = request.params.check(&registry)?;
+    let result = get_nearest_to_item(index, request.item, params).await?;
+    Ok(Json(result.into()))
+}
+
+async fn nearest_batch(
+    State(registry): State<IndexRegistry>,
+    Path(name): Path<String>,
+    Json(request): Json<NearestBatchRequest>,
+) -> Result<Json<BatchSearchResultJson>, QueryError> {
+    let index = registry.index(&name)?;
+    let params = request.params.check(&registry)?;
+    let results = get_nearest_batch(index, request.vectors, params).await?;
+    Ok(Json(BatchSearchResultJson {
+        results: results.into_iter().map(Into::into).collect(),
+    }))
+}
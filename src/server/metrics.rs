@@ -0,0 +1,198 @@
+//! Aggregates [`MetricsRecorder`] events into Prometheus counters and histograms, served at `GET /metrics`.
+
+use crate::*;
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+
+const SECONDS_BUCKETS: &[f64] = &[
+    0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0,
+];
+const COUNT_BUCKETS: &[f64] = &[10.0, 100.0, 1000.0, 10000.0, 100000.0, 1000000.0];
+
+struct Histogram {
+    bounds: &'static [f64],
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_bits: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_bits: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        if let Some(i) = self.bounds.iter().position(|&bound| value <= bound) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum_bits
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = f64::from_bits(self.sum_bits.load(Ordering::Relaxed));
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum {sum}\n{name}_count {count}");
+    }
+}
+
+fn render_counter(out: &mut String, name: &str, help: &str, counter: &AtomicU64) {
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} counter\n{name} {}",
+        counter.load(Ordering::Relaxed)
+    );
+}
+
+pub struct PrometheusRecorder {
+    loads: AtomicU64,
+    mmap_loads: AtomicU64,
+    load_major_page_faults: AtomicU64,
+    load_seconds: Histogram,
+    searches: AtomicU64,
+    truncated_searches: AtomicU64,
+    search_seconds: Histogram,
+    search_k: Histogram,
+    candidates: Histogram,
+    distance_computations: Histogram,
+}
+
+impl Default for PrometheusRecorder {
+    fn default() -> Self {
+        PrometheusRecorder {
+            loads: AtomicU64::new(0),
+            mmap_loads: AtomicU64::new(0),
+            load_major_page_faults: AtomicU64::new(0),
+            load_seconds: Histogram::new(SECONDS_BUCKETS),
+            searches: AtomicU64::new(0),
+            truncated_searches: AtomicU64::new(0),
+            search_seconds: Histogram::new(SECONDS_BUCKETS),
+            search_k: Histogram::new(COUNT_BUCKETS),
+            candidates: Histogram::new(COUNT_BUCKETS),
+            distance_computations: Histogram::new(COUNT_BUCKETS),
+        }
+    }
+}
+
+impl MetricsRecorder for &'static PrometheusRecorder {
+    fn record_load(&self, metrics: &LoadMetrics) {
+        self.loads.fetch_add(1, Ordering::Relaxed);
+        if metrics.storage_kind == StorageKind::Mmap {
+            self.mmap_loads.fetch_add(1, Ordering::Relaxed);
+        }
+        self.load_major_page_faults.fetch_add(
+            metrics.major_page_faults.unwrap_or_default(),
+            Ordering::Relaxed,
+        );
+        self.load_seconds.observe(metrics.duration.as_secs_f64());
+    }
+
+    fn record_search(&self, metrics: &SearchMetrics) {
+        self.searches.fetch_add(1, Ordering::Relaxed);
+        if metrics.is_truncated {
+            self.truncated_searches.fetch_add(1, Ordering::Relaxed);
+        }
+        self.search_seconds.observe(metrics.duration.as_secs_f64());
+        self.search_k.observe(metrics.search_k as f64);
+        self.candidates.observe(metrics.candidate_count as f64);
+        self.distance_computations
+            .observe(metrics.distance_computations as f64);
+    }
+}
+
+impl PrometheusRecorder {
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        render_counter(
+            &mut out,
+            "annoy_index_loads_total",
+            "Indexes loaded.",
+            &self.loads,
+        );
+        render_counter(
+            &mut out,
+            "annoy_index_mmap_loads_total",
+            "Indexes loaded by memory mapping, whose pages are faulted in while searching.",
+            &self.mmap_loads,
+        );
+        render_counter(
+            &mut out,
+            "annoy_index_load_major_page_faults_total",
+            "Major page faults while loading indexes.",
+            &self.load_major_page_faults,
+        );
+        self.load_seconds.render(
+            &mut out,
+            "annoy_index_load_duration_seconds",
+            "Time spent loading an index.",
+        );
+        render_counter(
+            &mut out,
+            "annoy_searches_total",
+            "Queries served.",
+            &self.searches,
+        );
+        render_counter(
+            &mut out,
+            "annoy_truncated_searches_total",
+            "Queries stopped early by a search budget.",
+            &self.truncated_searches,
+        );
+        self.search_seconds.render(
+            &mut out,
+            "annoy_search_duration_seconds",
+            "Time spent serving a query.",
+        );
+        self.search_k
+            .render(&mut out, "annoy_search_k", "Effective search_k of a query.");
+        self.candidates.render(
+            &mut out,
+            "annoy_search_candidates",
+            "Candidate items collected by a query.",
+        );
+        self.distance_computations.render(
+            &mut out,
+            "annoy_search_distance_computations",
+            "Distances evaluated by a query.",
+        );
+        out
+    }
+}
+
+/// The recorder backing `GET /metrics`, installed as the process wide [`MetricsRecorder`]
+/// by [`install`].
+pub fn recorder() -> &'static PrometheusRecorder {
+    static RECORDER: OnceLock<PrometheusRecorder> = OnceLock::new();
+    RECORDER.get_or_init(PrometheusRecorder::default)
+}
+
+/// Installs [`recorder`], no-op if it's already installed, errors if another recorder is.
+pub fn install() -> Result<(), Box<dyn std::error::Error>> {
+    static INSTALLED: OnceLock<Result<(), String>> = OnceLock::new();
+    INSTALLED
+        .get_or_init(|| set_metrics_recorder(recorder()).map_err(|err| err.to_string()))
+        .clone()
+        .map_err(Into::into)
+}
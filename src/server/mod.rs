@@ -0,0 +1,349 @@
+//! Serves indexes listed in a config file over JSON-over-HTTP and gRPC, see the `annoy-server` binary.
+//!
+//! ```toml
+//! http_addr = "127.0.0.1:8080"
+//! grpc_addr = "127.0.0.1:50051"
+//! # Larger values are rejected, defaults are shown
+//! max_n_results = 1000
+//! max_search_k = 1000000
+//!
+//! [[indexes]]
+//! name = "words"
+//! path = "words.ann"
+//! dimension = 100
+//! index_type = "angular"
+//! in_memory = false
+//! ```
+
+pub mod grpc;
+pub mod http;
+pub mod metrics;
+
+use crate::*;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    error::Error,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerConfig {
+    /// Address of the JSON-over-HTTP endpoints, disabled when absent.
+    pub http_addr: Option<SocketAddr>,
+    /// Address of the gRPC service, disabled when absent.
+    pub grpc_addr: Option<SocketAddr>,
+    /// Largest `n_results` of a query.
+    #[serde(default = "default_max_n_results")]
+    pub max_n_results: usize,
+    /// Largest explicit `search_k` of a query.
+    #[serde(default = "default_max_search_k")]
+    pub max_search_k: i32,
+    #[serde(default)]
+    pub indexes: Vec<IndexConfig>,
+}
+
+fn default_max_n_results() -> usize {
+    1000
+}
+
+fn default_max_search_k() -> i32 {
+    1_000_000
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexConfig {
+    pub name: String,
+    /// Relative paths are resolved against the directory of the config file.
+    pub path: PathBuf,
+    pub dimension: usize,
+    /// One of `angular`, `euclidean`, `manhattan`, `hamming` and `dot`.
+    pub index_type: String,
+    /// Reads the whole file into memory instead of memory mapping it.
+    #[serde(default)]
+    pub in_memory: bool,
+}
+
+impl ServerConfig {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ServerConfig, Box<dyn Error>> {
+        let path = path.as_ref();
+        let mut config: ServerConfig = toml::from_str(&std::fs::read_to_string(path)?)?;
+        if let Some(dir) = path.parent() {
+            for index in config.indexes.iter_mut() {
+                index.path = dir.join(&index.path);
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl IndexConfig {
+    fn load(&self) -> Result<AnnoyIndex, Box<dyn Error>> {
+        let path = self
+            .path
+            .to_str()
+            .ok_or_else(|| format!("Invalid path {:?}", self.path))?;
+        let index_type = self.index_type.parse()?;
+        if self.in_memory {
+            AnnoyIndex::load_into_mem(self.dimension, path, index_type)
+        } else {
+            AnnoyIndex::load(self.dimension, path, index_type)
+        }
+    }
+}
+
+type IndexMap = HashMap<String, Arc<AnnoyIndex>>;
+
+#[derive(Default)]
+struct Served {
+    indexes: IndexMap,
+    max_n_results: usize,
+    max_search_k: i32,
+}
+
+/// Named indexes and query limits shared by the HTTP and gRPC endpoints, swapped atomically on reload.
+#[derive(Clone, Default)]
+pub struct IndexRegistry {
+    served: Arc<RwLock<Arc<Served>>>,
+}
+
+impl IndexRegistry {
+    pub fn load(config: &ServerConfig) -> Result<IndexRegistry, Box<dyn Error>> {
+        let registry = IndexRegistry::default();
+        registry.reload(config)?;
+        Ok(registry)
+    }
+
+    /// Loads every index in `config` and replaces the served set, the previous set keeps
+    /// being served if any index fails to load.
+    pub fn reload(&self, config: &ServerConfig) -> Result<(), Box<dyn Error>> {
+        let mut indexes = IndexMap::with_capacity(config.indexes.len());
+        for index in &config.indexes {
+            let loaded = index
+                .load()
+                .map_err(|err| format!("Failed to load index {}: {err}", index.name))?;
+            if indexes
+                .insert(index.name.clone(), Arc::new(loaded))
+                .is_some()
+            {
+                return Err(format!("Duplicate index name {}", index.name).into());
+            }
+        }
+        *self.served.write().unwrap() = Arc::new(Served {
+            indexes,
+            max_n_results: config.max_n_results,
+            max_search_k: config.max_search_k,
+        });
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<AnnoyIndex>> {
+        self.served.read().unwrap().indexes.get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let served = self.served.read().unwrap().clone();
+        let mut names: Vec<String> = served.indexes.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn index(&self, name: &str) -> Result<Arc<AnnoyIndex>, QueryError> {
+        self.get(name)
+            .ok_or_else(|| QueryError::IndexNotFound(format!("Index {name} not found")))
+    }
+
+    fn search_params(
+        &self,
+        n_results: usize,
+        search_k: Option<i32>,
+        should_include_distance: bool,
+    ) -> Result<SearchParams, QueryError> {
+        let served = self.served.read().unwrap().clone();
+        if n_results > served.max_n_results {
+            return Err(QueryError::InvalidArgument(format!(
+                "n_results {n_results} exceeds the limit {}",
+                served.max_n_results
+            )));
+        }
+        let search_k = search_k.unwrap_or(-1);
+        if search_k > served.max_search_k {
+            return Err(QueryError::InvalidArgument(format!(
+                "search_k {search_k} exceeds the limit {}",
+                served.max_search_k
+            )));
+        }
+        Ok(SearchParams {
+            n_results,
+            search_k,
+            should_include_distance,
+        })
+    }
+}
+
+#[derive(Debug)]
+enum QueryError {
+    IndexNotFound(String),
+    InvalidArgument(String),
+    Internal(String),
+}
+
+struct SearchParams {
+    n_results: usize,
+    search_k: i32,
+    should_include_distance: bool,
+}
+
+fn check_vector(index: &AnnoyIndex, vector: &[f32]) -> Result<(), QueryError> {
+    if vector.len() != index.dimension {
+        return Err(QueryError::InvalidArgument(format!(
+            "Wrong input dimension, {} expected, {} provided.",
+            index.dimension,
+            vector.len()
+        )));
+    }
+    Ok(())
+}
+
+fn check_item(index: &AnnoyIndex, item: u64) -> Result<(), QueryError> {
+    if item as usize >= index.size {
+        return Err(QueryError::InvalidArgument(format!(
+            "Item {item} out of range, index size is {}",
+            index.size
+        )));
+    }
+    Ok(())
+}
+
+/// Runs a query on the blocking pool, page faults of memory mapped indexes would stall the runtime.
+async fn run_blocking<T: Send + 'static>(
+    query: impl FnOnce() -> Result<T, QueryError> + Send + 'static,
+) -> Result<T, QueryError> {
+    tokio::task::spawn_blocking(query)
+        .await
+        .map_err(|err| QueryError::Internal(err.to_string()))?
+}
+
+async fn get_nearest(
+    index: Arc<AnnoyIndex>,
+    vector: Vec<f32>,
+    params: SearchParams,
+) -> Result<AnnoyIndexSearchResult, QueryError> {
+    check_vector(&index, &vector)?;
+    run_blocking(move || {
+        Ok(index.get_nearest(
+            &vector,
+            params.n_results,
+            params.search_k,
+            params.should_include_distance,
+        ))
+    })
+    .await
+}
+
+async fn get_nearest_to_item(
+    index: Arc<AnnoyIndex>,
+    item: u64,
+    params: SearchParams,
+) -> Result<AnnoyIndexSearchResult, QueryError> {
+    check_item(&index, item)?;
+    run_blocking(move || {
+        Ok(index.get_nearest_to_item(
+            item,
+            params.n_results,
+            params.search_k,
+            params.should_include_distance,
+        ))
+    })
+    .await
+}
+
+async fn get_nearest_batch(
+    index: Arc<AnnoyIndex>,
+    vectors: Vec<Vec<f32>>,
+    params: SearchParams,
+) -> Result<Vec<AnnoyIndexSearchResult>, QueryError> {
+    for vector in &vectors {
+        check_vector(&index, vector)?;
+    }
+    run_blocking(move || {
+        Ok(vectors
+            .iter()
+            .map(|vector| {
+                index.get_nearest(
+                    vector,
+                    params.n_results,
+                    params.search_k,
+                    params.should_include_distance,
+                )
+            })
+            .collect())
+    })
+    .await
+}
+
+async fn get_item_vector(index: Arc<AnnoyIndex>, item: u64) -> Result<Vec<f32>, QueryError> {
+    check_item(&index, item)?;
+    run_blocking(move || Ok(index.get_item_vector(item))).await
+}
+
+/// Serves until the process is killed, reloading `config_path` on SIGHUP.
+pub async fn run(config_path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+    let config_path = config_path.as_ref().to_path_buf();
+    let config = ServerConfig::from_file(&config_path)?;
+    metrics::install()?;
+    let registry = IndexRegistry::load(&config)?;
+    tracing::info!(indexes = ?registry.names(), "loaded indexes");
+
+    let mut servers = tokio::task::JoinSet::new();
+    if let Some(addr) = config.http_addr {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        tracing::info!(addr = %listener.local_addr()?, "serving HTTP");
+        let router = http::router(registry.clone());
+        servers.spawn(async move {
+            axum::serve(listener, router)
+                .await
+                .map_err(|e| e.to_string())
+        });
+    }
+    if let Some(addr) = config.grpc_addr {
+        tracing::info!(%addr, "serving gRPC");
+        let service = grpc::service(registry.clone());
+        servers.spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve(addr)
+                .await
+                .map_err(|e| e.to_string())
+        });
+    }
+    if servers.is_empty() {
+        return Err("Neither http_addr nor grpc_addr is configured".into());
+    }
+    #[cfg(unix)]
+    servers.spawn(reload_on_sighup(config_path, registry));
+
+    match servers.join_next().await {
+        Some(result) => result?.map_err(Into::into),
+        None => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+async fn reload_on_sighup(config_path: PathBuf, registry: IndexRegistry) -> Result<(), String> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup()).map_err(|e| e.to_string())?;
+    while hangup.recv().await.is_some() {
+        // Addresses are bound once, only the served indexes are reloaded.
+        match ServerConfig::from_file(&config_path).and_then(|config| registry.reload(&config)) {
+            Ok(()) => tracing::info!(indexes = ?registry.names(), "reloaded indexes"),
+            Err(err) => {
+                tracing::error!(%err, "reload failed, keep serving previous indexes")
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,13 @@
 use super::utils::*;
-use super::{AnnoyIndex, IndexType};
+use super::{
+    AnnoyIndex, Distance, IndexMetadata, IndexType, Metric, NodeVector, Tombstones, VectorEncoding,
+};
 use crate::internals::storage_ext::*;
 use crate::types::node::*;
-use crate::Storage;
-use std::error::Error;
+use crate::{ExternalBytes, Storage};
+use std::{error::Error, sync::Arc};
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+use {super::metrics::*, std::time::Instant};
 
 impl AnnoyIndex {
     #[cfg(not(target_arch = "wasm32"))]
@@ -16,7 +20,13 @@ impl AnnoyIndex {
         let file_metadata = std::fs::metadata(index_file_path)?;
         let file_size = file_metadata.len() as i64;
         let storage = Storage::Mmap(Box::new(unsafe { memmap2::MmapOptions::new().map(&file)? }));
-        Self::load_inner(dimension, file_size, index_type, storage)
+        Self::load_inner(
+            dimension,
+            file_size,
+            index_type,
+            VectorEncoding::F32,
+            storage,
+        )
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -28,7 +38,7 @@ impl AnnoyIndex {
         let buffer = std::fs::read(index_file_path)?;
         let size = buffer.len() as i64;
         let storage = Storage::Buffer(buffer);
-        Self::load_inner(dimension, size, index_type, storage)
+        Self::load_inner(dimension, size, index_type, VectorEncoding::F32, storage)
     }
 
     pub fn load_from_buffer(
@@ -38,27 +48,154 @@ impl AnnoyIndex {
     ) -> Result<AnnoyIndex, Box<dyn Error>> {
         let size = buffer.len() as i64;
         let storage = Storage::Buffer(buffer);
-        Self::load_inner(dimension, size, index_type, storage)
+        Self::load_inner(dimension, size, index_type, VectorEncoding::F32, storage)
+    }
+
+    /// Loads an index from bytes kept alive by `owner` without copying them, e.g. a buffer
+    /// allocated by another language runtime. The bytes must be 4-byte aligned, and `owner`
+    /// must return the same bytes for as long as the index is alive.
+    pub fn load_from_owner(
+        owner: impl AsRef<[u8]> + Send + Sync + 'static,
+        dimension: usize,
+        index_type: IndexType,
+    ) -> Result<AnnoyIndex, Box<dyn Error>> {
+        // Owners storing their bytes inline move with the box, so they are checked once boxed
+        let external = ExternalBytes(Box::new(owner));
+        let bytes = external.bytes();
+        if !bytes.as_ptr().cast::<f32>().is_aligned() {
+            return Err("Index bytes must be 4-byte aligned".into());
+        }
+        let size = bytes.len() as i64;
+        let storage = Storage::External(external);
+        Self::load_inner(dimension, size, index_type, VectorEncoding::F32, storage)
+    }
+
+    /// Loads an index written by [`AnnoyIndex::save_quantized`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_quantized(
+        dimension: usize,
+        index_file_path: &str,
+        index_type: IndexType,
+        vector_encoding: VectorEncoding,
+    ) -> Result<AnnoyIndex, Box<dyn Error>> {
+        let file = std::fs::File::open(index_file_path)?;
+        let file_metadata = std::fs::metadata(index_file_path)?;
+        let file_size = file_metadata.len() as i64;
+        let storage = Storage::Mmap(Box::new(unsafe { memmap2::MmapOptions::new().map(&file)? }));
+        Self::load_inner(dimension, file_size, index_type, vector_encoding, storage)
+    }
+
+    /// Loads an index produced by [`AnnoyIndex::quantize`].
+    pub fn load_quantized_from_buffer(
+        buffer: Vec<u8>,
+        dimension: usize,
+        index_type: IndexType,
+        vector_encoding: VectorEncoding,
+    ) -> Result<AnnoyIndex, Box<dyn Error>> {
+        let size = buffer.len() as i64;
+        let storage = Storage::Buffer(buffer);
+        Self::load_inner(dimension, size, index_type, vector_encoding, storage)
+    }
+
+    /// Loads an index built with a custom metric, its [`AnnoyIndex::index_type`] is [`IndexType::Custom`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_with_distance(
+        dimension: usize,
+        index_file_path: &str,
+        distance: impl Distance + 'static,
+        vector_encoding: VectorEncoding,
+    ) -> Result<AnnoyIndex, Box<dyn Error>> {
+        let file = std::fs::File::open(index_file_path)?;
+        let file_metadata = std::fs::metadata(index_file_path)?;
+        let file_size = file_metadata.len() as i64;
+        let storage = Storage::Mmap(Box::new(unsafe { memmap2::MmapOptions::new().map(&file)? }));
+        Self::load_inner_with_distance(
+            dimension,
+            file_size,
+            IndexType::Custom,
+            Metric::Custom(Arc::new(distance)),
+            vector_encoding,
+            storage,
+        )
+    }
+
+    /// Same as [`AnnoyIndex::load_with_distance`], from an in-memory buffer.
+    pub fn load_from_buffer_with_distance(
+        buffer: Vec<u8>,
+        dimension: usize,
+        distance: impl Distance + 'static,
+        vector_encoding: VectorEncoding,
+    ) -> Result<AnnoyIndex, Box<dyn Error>> {
+        let size = buffer.len() as i64;
+        let storage = Storage::Buffer(buffer);
+        Self::load_inner_with_distance(
+            dimension,
+            size,
+            IndexType::Custom,
+            Metric::Custom(Arc::new(distance)),
+            vector_encoding,
+            storage,
+        )
     }
 
     fn load_inner(
         dimension: usize,
         index_size: i64,
         index_type: IndexType,
+        vector_encoding: VectorEncoding,
         storage: Storage,
     ) -> Result<AnnoyIndex, Box<dyn Error>> {
-        let (offset_before_children, node_header_size, max_descendants): (usize, usize, usize) =
-            match index_type {
-                IndexType::Angular => (4, NodeHeaderAngular::header_size(), dimension + 2),
-                IndexType::Euclidean | IndexType::Manhattan => {
-                    (8, NodeHeaderMinkowski::header_size(), dimension + 2)
-                }
-                // IndexType::Hamming => (4, 12),
-                IndexType::Dot => (4, NodeHeaderDot::header_size(), dimension + 3),
-                _ => unimplemented!("Index type not supported"),
-            };
-
-        let node_size = node_header_size as i64 + (FLOAT32_SIZE * dimension) as i64;
+        let distance = Metric::builtin(&index_type)
+            .ok_or_else(|| format!("Index type {index_type} not supported"))?;
+        Self::load_inner_with_distance(
+            dimension,
+            index_size,
+            index_type,
+            distance,
+            vector_encoding,
+            storage,
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(distance, storage), err)
+    )]
+    fn load_inner_with_distance(
+        dimension: usize,
+        index_size: i64,
+        index_type: IndexType,
+        distance: Metric,
+        vector_encoding: VectorEncoding,
+        storage: Storage,
+    ) -> Result<AnnoyIndex, Box<dyn Error>> {
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        let started = metrics_recorder().map(|_| (Instant::now(), major_page_faults()));
+
+        let header_kind = distance.header_kind();
+        let offset_before_children = header_kind.offset_before_children();
+        let node_header_size = header_kind.header_size();
+
+        let node_size = vector_encoding.node_size(node_header_size, dimension) as i64;
+        // Leaves store descendant ids from the children field up to the end of the node
+        let max_descendants = (node_size as usize - offset_before_children) / INT32_SIZE;
+
+        // Roots are found from the end of the nodes, before the footer if any
+        let metadata = IndexMetadata::split(storage.read_slice(0, index_size as usize))?;
+        let index_size = match &metadata {
+            Some((metadata, nodes_size)) => {
+                metadata.check(dimension, &index_type, vector_encoding)?;
+                *nodes_size as i64
+            }
+            None => index_size,
+        };
+        if index_size % node_size != 0 {
+            return Err(format!(
+                "Index size {index_size} isn't a multiple of the node size {node_size}, \
+                 check the dimension and vector encoding"
+            )
+            .into());
+        }
 
         let mut roots = Vec::new();
         let mut m: i32 = -1;
@@ -91,9 +228,15 @@ impl AnnoyIndex {
             roots.pop();
         }
 
+        if let Some((metadata, nodes_size)) = &metadata {
+            metadata.check_nodes(*nodes_size, node_size as usize, m as usize, roots.len())?;
+        }
+
         let index = AnnoyIndex {
             dimension,
             index_type,
+            vector_encoding,
+            distance,
             offset_before_children,
             node_header_size,
             max_descendants: max_descendants as i32,
@@ -101,13 +244,66 @@ impl AnnoyIndex {
             storage,
             roots,
             size: m as usize,
+            tombstones: Tombstones::default(),
+            n_nodes: (index_size / node_size) as usize,
+            metadata: metadata.map(|(metadata, _)| metadata),
         };
 
+        index.check_roots()?;
+
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        if let (Some(recorder), Some((started, faults_before))) = (metrics_recorder(), started) {
+            recorder.record_load(&LoadMetrics {
+                storage_kind: index.storage.kind(),
+                index_size: index.storage.len(),
+                duration: started.elapsed(),
+                major_page_faults: major_page_faults()
+                    .zip(faults_before)
+                    .map(|(after, before)| after.saturating_sub(before)),
+            });
+        }
         Ok(index)
     }
 
+    /// Plain files don't record the dimension nor the vector encoding, loading with wrong ones
+    /// misreads the roots, which this rejects instead of serving garbage.
+    fn check_roots(&self) -> Result<(), Box<dyn Error>> {
+        let n_nodes = self.n_nodes;
+        let is_valid_root = |root: usize| {
+            let node = self.get_node_from_id(root);
+            if node.header.get_n_descendant() <= self.max_descendants {
+                return true;
+            }
+            node.header
+                .get_children_id_slice()
+                .iter()
+                .all(|&child| child >= 0 && (child as usize) < n_nodes)
+        };
+        if self.roots.is_empty()
+            || self.size == 0
+            || self.size > n_nodes - self.roots.len()
+            || !self.roots.iter().all(|&root| is_valid_root(root))
+        {
+            return Err(format!(
+                "Invalid index layout for dimension {} and {} vectors",
+                self.dimension, self.vector_encoding
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    pub fn n_trees(&self) -> usize {
+        self.roots.len()
+    }
+
     pub(crate) fn get_node_from_id(&self, id: usize) -> Node {
-        Node::new_with_id(id, self.node_size, &self.index_type, &self.storage)
+        Node::new_with_id(
+            id,
+            self.node_size,
+            self.distance.header_kind(),
+            &self.storage,
+        )
     }
 
     pub(crate) fn get_descendant_id_slice(&self, node_offset: usize, n: usize) -> &[i32] {
@@ -115,38 +311,20 @@ impl AnnoyIndex {
             .read_slice(node_offset + self.offset_before_children, n)
     }
 
-    pub(crate) fn get_margin(&self, v1: &[f32], v2: &[f32], node_offset: usize) -> f32 {
-        match self.index_type {
-            IndexType::Angular => dot_product(v1, v2),
-            IndexType::Euclidean | IndexType::Manhattan => {
-                minkowski_margin(v1, v2, self.storage.read_f32(node_offset + 4))
-            }
-            IndexType::Dot => dot_product(v1, v2) + self.storage.read_f32(node_offset + 12).powi(2),
-            _ => unimplemented!("Index type not supported"),
-        }
+    pub(crate) fn get_margin(&self, header: &NodeHeader, v1: &NodeVector, v2: &[f32]) -> f32 {
+        self.distance.margin(header, v1, v2)
     }
 
-    pub(crate) fn get_distance_no_norm(&self, v1: &[f32], v2: &[f32]) -> f32 {
-        match self.index_type {
-            IndexType::Angular => cosine_distance(v1, v2),
-            IndexType::Euclidean => euclidean_distance(v1, v2),
-            IndexType::Manhattan => manhattan_distance(v1, v2),
-            IndexType::Dot => -dot_product(v1, v2),
-            _ => unimplemented!("Index type not supported"),
-        }
+    pub(crate) fn get_distance_no_norm(&self, v1: &NodeVector, v2: &[f32]) -> f32 {
+        self.distance.distance(v1, v2)
     }
 
     pub(crate) fn normalized_distance(&self, d: f32) -> f32 {
-        match self.index_type {
-            IndexType::Angular | IndexType::Euclidean => d.sqrt(),
-            IndexType::Dot => -d,
-            _ => d,
-        }
+        self.distance.normalized_distance(d)
     }
 
-    pub(crate) fn get_node_slice_with_offset(&self, node_offset: usize) -> &[f32] {
-        let dimension = self.dimension;
+    pub(crate) fn get_node_vector(&self, node_offset: usize) -> NodeVector<'_> {
         let offset = node_offset + self.node_header_size;
-        self.storage.read_slice::<f32>(offset, dimension)
+        NodeVector::read(&self.storage, offset, self.dimension, self.vector_encoding)
     }
 }
@@ -0,0 +1,255 @@
+//! [Apache Arrow](https://arrow.apache.org/) and [Parquet](https://parquet.apache.org/) interop,
+//! enabled by the `arrow` feature.
+
+use super::*;
+use arrow::array::{
+    Array, ArrayRef, AsArray, FixedSizeListArray, Float32Array, UInt32Array, UInt64Array,
+};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Float32Type, Schema, UInt64Type};
+use arrow::error::ArrowError;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use std::error::Error;
+use std::sync::Arc;
+
+impl Vectors {
+    /// Columns `id: UInt64` and `vector: FixedSizeList<Float32>`.
+    pub fn to_record_batch(&self) -> std::result::Result<RecordBatch, ArrowError> {
+        let item = Arc::new(Field::new_list_field(DataType::Float32, false));
+        let vectors = FixedSizeListArray::try_new(
+            item.clone(),
+            self.dimension as i32,
+            Arc::new(Float32Array::from(self.data.clone())),
+            None,
+        )?;
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::UInt64, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(item, self.dimension as i32),
+                false,
+            ),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(UInt64Array::from(self.ids.clone())) as ArrayRef,
+                Arc::new(vectors),
+            ],
+        )
+    }
+
+    /// Arrow IPC file holding [`Vectors::to_record_batch`].
+    pub fn to_arrow_ipc(&self) -> std::result::Result<Vec<u8>, Box<dyn Error>> {
+        let batch = self.to_record_batch()?;
+        let mut writer = FileWriter::try_new(Vec::new(), &batch.schema())?;
+        writer.write(&batch)?;
+        writer.finish()?;
+        Ok(writer.into_inner()?)
+    }
+}
+
+impl Vectors {
+    /// Reads a `FixedSizeList`, `List` or `LargeList` array of floats, one vector per row,
+    /// numbered from 0.
+    pub fn from_arrow(array: &dyn Array) -> std::result::Result<Vectors, Box<dyn Error>> {
+        let mut vectors = empty_vectors();
+        vectors.extend_from_arrow(array)?;
+        Ok(vectors)
+    }
+
+    /// Reads the vectors of `column`, as [`Vectors::from_arrow`], with ids from the integers of
+    /// `id_column` if any.
+    pub fn from_record_batch(
+        batch: &RecordBatch,
+        column: &str,
+        id_column: Option<&str>,
+    ) -> std::result::Result<Vectors, Box<dyn Error>> {
+        let mut vectors = empty_vectors();
+        vectors.extend_from_record_batch(batch, column, id_column)?;
+        Ok(vectors)
+    }
+
+    /// Reads an embedding column of a Parquet file, see [`Vectors::from_record_batch`].
+    pub fn read_parquet(
+        path: &str,
+        column: &str,
+        id_column: Option<&str>,
+    ) -> std::result::Result<Vectors, Box<dyn Error>> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path)?)?;
+        let columns = [Some(column), id_column];
+        let projection =
+            ProjectionMask::columns(builder.parquet_schema(), columns.into_iter().flatten());
+        let mut vectors = empty_vectors();
+        for batch in builder.with_projection(projection).build()? {
+            vectors.extend_from_record_batch(&batch?, column, id_column)?;
+        }
+        Ok(vectors)
+    }
+
+    fn extend_from_record_batch(
+        &mut self,
+        batch: &RecordBatch,
+        column: &str,
+        id_column: Option<&str>,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        let column_by_name = |name: &str| {
+            batch
+                .column_by_name(name)
+                .ok_or_else(|| format!("No column {name}"))
+        };
+        let start = self.len();
+        self.extend_from_arrow(column_by_name(column)?)?;
+        if let Some(id_column) = id_column {
+            let ids = cast(column_by_name(id_column)?, &DataType::UInt64)?;
+            let ids = ids.as_primitive::<UInt64Type>();
+            if ids.null_count() > 0 {
+                return Err(format!("Null ids in column {id_column}").into());
+            }
+            self.ids[start..].copy_from_slice(ids.values());
+        }
+        Ok(())
+    }
+
+    fn extend_from_arrow(&mut self, array: &dyn Array) -> std::result::Result<(), Box<dyn Error>> {
+        // Start and length of each row in the values
+        let (values, rows): (&ArrayRef, Vec<(usize, usize)>) = match array.data_type() {
+            DataType::FixedSizeList(_, _) => {
+                let list = array.as_fixed_size_list();
+                let rows = (0..list.len())
+                    .map(|i| (list.value_offset(i) as usize, list.value_length() as usize))
+                    .collect();
+                (list.values(), rows)
+            }
+            DataType::List(_) => {
+                let list = array.as_list::<i32>();
+                let rows = list
+                    .value_offsets()
+                    .windows(2)
+                    .map(|w| (w[0] as usize, (w[1] - w[0]) as usize))
+                    .collect();
+                (list.values(), rows)
+            }
+            DataType::LargeList(_) => {
+                let list = array.as_list::<i64>();
+                let rows = list
+                    .value_offsets()
+                    .windows(2)
+                    .map(|w| (w[0] as usize, (w[1] - w[0]) as usize))
+                    .collect();
+                (list.values(), rows)
+            }
+            data_type => {
+                return Err(
+                    format!("Vectors of type {data_type}, a list of floats expected").into(),
+                )
+            }
+        };
+        if array.null_count() > 0 {
+            return Err("Null vectors".into());
+        }
+        let values = cast(values, &DataType::Float32)?;
+        let values = values.as_primitive::<Float32Type>();
+        for (start, len) in rows {
+            if values
+                .nulls()
+                .is_some_and(|nulls| (start..start + len).any(|i| nulls.is_null(i)))
+            {
+                return Err(format!("Null values in vector {}", self.len()).into());
+            }
+            if self.is_empty() {
+                self.dimension = len;
+            } else if len != self.dimension {
+                return Err(format!(
+                    "Vector {} has dimension {len}, {} expected",
+                    self.len(),
+                    self.dimension
+                )
+                .into());
+            }
+            self.ids.push(self.len() as u64);
+            self.data
+                .extend_from_slice(&values.values()[start..start + len]);
+        }
+        Ok(())
+    }
+}
+
+fn empty_vectors() -> Vectors {
+    Vectors {
+        dimension: 0,
+        ids: Vec::new(),
+        data: Vec::new(),
+    }
+}
+
+/// Results of a batch of queries, query `i` having `results[i]`, as columns `query_idx: UInt32`,
+/// `rank: UInt32`, `id: UInt64` and `distance: Float32`. Distances are null for results that
+/// don't include them.
+pub fn results_to_record_batch(
+    results: &[AnnoyIndexSearchResult],
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let n_rows = results.iter().map(|result| result.id_list.len()).sum();
+    let mut query_idx = Vec::with_capacity(n_rows);
+    let mut rank = Vec::with_capacity(n_rows);
+    let mut id = Vec::with_capacity(n_rows);
+    let mut distance = Vec::with_capacity(n_rows);
+    for (i, result) in results.iter().enumerate() {
+        for (r, &item) in result.id_list.iter().enumerate() {
+            query_idx.push(i as u32);
+            rank.push(r as u32);
+            id.push(item);
+            distance.push(result.distance_list.get(r).copied());
+        }
+    }
+    let schema = Schema::new(vec![
+        Field::new("query_idx", DataType::UInt32, false),
+        Field::new("rank", DataType::UInt32, false),
+        Field::new("id", DataType::UInt64, false),
+        Field::new("distance", DataType::Float32, true),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(UInt32Array::from(query_idx)) as ArrayRef,
+            Arc::new(UInt32Array::from(rank)),
+            Arc::new(UInt64Array::from(id)),
+            Arc::new(Float32Array::from(distance)),
+        ],
+    )
+}
+
+impl AnnoyIndex {
+    /// Searches each vector of `queries`, see [`Vectors::from_arrow`], with results as in
+    /// [`results_to_record_batch`].
+    pub fn get_nearest_arrow(
+        &self,
+        queries: &dyn Array,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> std::result::Result<RecordBatch, Box<dyn Error>> {
+        let queries = Vectors::from_arrow(queries)?;
+        if !queries.is_empty() && queries.dimension != self.dimension {
+            return Err(format!(
+                "Wrong input dimension, {} expected, {} provided.",
+                self.dimension, queries.dimension
+            )
+            .into());
+        }
+        let results: Vec<AnnoyIndexSearchResult> = (0..queries.len())
+            .map(|i| {
+                self.get_nearest(
+                    queries.vector(i),
+                    n_results,
+                    search_k,
+                    should_include_distance,
+                )
+            })
+            .collect();
+        Ok(results_to_record_batch(&results)?)
+    }
+}
@@ -0,0 +1,152 @@
+use super::*;
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::Semaphore;
+
+pub type AsyncResult<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Wraps an [`AnnoyIndex`] for async services, searches run on the tokio blocking pool so that
+/// page faults of a cold mmap never stall the runtime.
+///
+/// At most `max_concurrency` searches run at a time, further calls wait for a slot. Dropping a
+/// future before its search starts skips the search, and a dropped batch search stops after
+/// the query in progress.
+#[derive(Clone)]
+pub struct AsyncAnnoyIndex {
+    index: Arc<AnnoyIndex>,
+    permits: Arc<Semaphore>,
+}
+
+impl AsyncAnnoyIndex {
+    pub fn new(index: AnnoyIndex, max_concurrency: usize) -> AsyncAnnoyIndex {
+        AsyncAnnoyIndex {
+            index: Arc::new(index),
+            permits: Arc::new(Semaphore::new(max_concurrency.max(1))),
+        }
+    }
+
+    /// Same as [`AnnoyIndex::load`], on the blocking pool.
+    pub async fn load(
+        dimension: usize,
+        index_file_path: &str,
+        index_type: IndexType,
+        max_concurrency: usize,
+    ) -> AsyncResult<AsyncAnnoyIndex> {
+        let index_file_path = index_file_path.to_owned();
+        let index = tokio::task::spawn_blocking(move || {
+            AnnoyIndex::load(dimension, &index_file_path, index_type).map_err(|e| e.to_string())
+        })
+        .await??;
+        Ok(AsyncAnnoyIndex::new(index, max_concurrency))
+    }
+
+    /// The wrapped index, for cheap calls like [`AnnoyIndexSearchApi::get_item_vector`] on a warm index.
+    pub fn index(&self) -> &Arc<AnnoyIndex> {
+        &self.index
+    }
+
+    pub async fn get_nearest(
+        &self,
+        query_vector: Vec<f32>,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> AsyncResult<AnnoyIndexSearchResult> {
+        self.check_dimension(&query_vector)?;
+        self.run(move |index, _| {
+            index.get_nearest(&query_vector, n_results, search_k, should_include_distance)
+        })
+        .await
+    }
+
+    pub async fn get_nearest_to_item(
+        &self,
+        item_index: u64,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> AsyncResult<AnnoyIndexSearchResult> {
+        if item_index as usize >= self.index.size {
+            return Err(format!("Item {item_index} out of range").into());
+        }
+        self.run(move |index, _| {
+            index.get_nearest_to_item(item_index, n_results, search_k, should_include_distance)
+        })
+        .await
+    }
+
+    /// Searches all `query_vectors` in one blocking task, results are in the same order.
+    pub async fn get_nearest_batch(
+        &self,
+        query_vectors: Vec<Vec<f32>>,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> AsyncResult<Vec<AnnoyIndexSearchResult>> {
+        for query_vector in query_vectors.iter() {
+            self.check_dimension(query_vector)?;
+        }
+        let results = self
+            .run(move |index, cancelled| {
+                let mut results = Vec::with_capacity(query_vectors.len());
+                for query_vector in query_vectors.iter() {
+                    if cancelled.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    results.push(index.get_nearest(
+                        query_vector,
+                        n_results,
+                        search_k,
+                        should_include_distance,
+                    ));
+                }
+                results
+            })
+            .await?;
+        Ok(results)
+    }
+
+    fn check_dimension(&self, query_vector: &[f32]) -> AsyncResult<()> {
+        if query_vector.len() != self.index.dimension {
+            return Err(format!(
+                "Wrong input dimension, {} expected, {} provided.",
+                self.index.dimension,
+                query_vector.len()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&AnnoyIndex, &AtomicBool) -> T + Send + 'static,
+    ) -> AsyncResult<T> {
+        let permit = self.permits.clone().acquire_owned().await?;
+        let index = self.index.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _guard = CancelOnDrop(cancelled.clone());
+        let task = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            if cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            Some(f(&index, &cancelled))
+        });
+        task.await?.ok_or_else(|| "Search was cancelled".into())
+    }
+}
+
+/// Flags the blocking task when the future awaiting it is dropped.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,246 @@
+use super::*;
+use crate::internals::storage_ext::StorageExtensions;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
+
+/// Builds an index straight into a file, like annoy's `on_disk_build`, for item sets that don't
+/// fit in memory.
+///
+/// Items are streamed into the node file as they're added and trees are built against a memory
+/// map of it, each thread writing its tree nodes to a temporary file next to the index that's
+/// appended once done. Heap usage is bounded by the item ids of the tree being split, not by the
+/// vectors. Built trees are the same as those of an [`AnnoyIndexBuilder`] with the same items,
+/// seed and threads, and the file is served directly by [`AnnoyIndex::load`].
+pub struct DiskIndexBuilder {
+    builder: AnnoyIndexBuilder,
+    path: String,
+    file: BufWriter<File>,
+    position: u64,
+    n_items: usize,
+    node: Vec<u8>,
+}
+
+impl DiskIndexBuilder {
+    /// Creates, or truncates, the index file at `index_file_path`.
+    pub fn new(
+        dimension: usize,
+        index_type: IndexType,
+        index_file_path: &str,
+    ) -> std::result::Result<Self, Box<dyn Error>> {
+        Self::with_builder(
+            AnnoyIndexBuilder::new(dimension, index_type)?,
+            index_file_path,
+        )
+    }
+
+    /// Builds an index served by [`AnnoyIndex::load_with_distance`] with the same `distance`.
+    pub fn with_distance(
+        dimension: usize,
+        distance: impl Distance + 'static,
+        index_file_path: &str,
+    ) -> std::result::Result<Self, Box<dyn Error>> {
+        Self::with_builder(
+            AnnoyIndexBuilder::with_distance(dimension, distance),
+            index_file_path,
+        )
+    }
+
+    fn with_builder(
+        builder: AnnoyIndexBuilder,
+        index_file_path: &str,
+    ) -> std::result::Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(index_file_path)?;
+        Ok(DiskIndexBuilder {
+            builder,
+            path: index_file_path.to_string(),
+            file: BufWriter::new(file),
+            position: 0,
+            n_items: 0,
+            node: Vec::new(),
+        })
+    }
+
+    pub fn index_type(&self) -> &IndexType {
+        self.builder.index_type()
+    }
+
+    /// See [`AnnoyIndexBuilder::set_seed`].
+    pub fn set_seed(&mut self, seed: u64) {
+        self.builder.set_seed(seed);
+    }
+
+    /// See [`AnnoyIndexBuilder::set_n_threads`].
+    pub fn set_n_threads(&mut self, n_threads: usize) {
+        self.builder.set_n_threads(n_threads);
+    }
+
+    /// See [`AnnoyIndexBuilder::set_write_metadata`].
+    pub fn set_write_metadata(&mut self, write_metadata: bool) {
+        self.builder.set_write_metadata(write_metadata);
+    }
+
+    /// See [`AnnoyIndexBuilder::insert_metadata`].
+    pub fn insert_metadata(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.builder.insert_metadata(key, value);
+    }
+
+    /// Writes the item node, ids that are never added are left as holes.
+    pub fn add_item(
+        &mut self,
+        item: u64,
+        vector: &[f32],
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        self.builder.check_dimension(vector)?;
+        let node_size = self.builder.node_size() as u64;
+        let offset = item * node_size;
+        if offset != self.position {
+            self.file.seek(SeekFrom::Start(offset))?;
+        }
+        self.node.clear();
+        self.builder
+            .push_item_node(&mut self.node, Some(vector), 0.0);
+        self.file.write_all(&self.node)?;
+        self.position = offset + node_size;
+        self.n_items = self.n_items.max(item as usize + 1);
+        Ok(())
+    }
+
+    /// Number of item slots, i.e. the largest added id plus one.
+    pub fn n_items(&self) -> usize {
+        self.n_items
+    }
+
+    /// Builds `n_trees` trees and completes the index file.
+    pub fn build(mut self, n_trees: usize) -> std::result::Result<(), Box<dyn Error>> {
+        self.file.flush()?;
+        let path = self.path.clone();
+        let tree_file_path = |thread: usize| format!("{path}.tree{thread}.tmp");
+        let result = self.build_inner(n_trees, &tree_file_path);
+        for thread in 0..self.builder.n_threads {
+            // Files are missing if building failed early
+            let _ = std::fs::remove_file(tree_file_path(thread));
+        }
+        result
+    }
+
+    fn build_inner(
+        &self,
+        n_trees: usize,
+        tree_file_path: &(dyn Fn(usize) -> String + Sync),
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        let node_size = self.builder.node_size();
+        let mut file = self.file.get_ref();
+        file.set_len((self.n_items * node_size) as u64)?;
+        if self.builder.index_type == IndexType::Dot {
+            self.write_dot_factors(file)?;
+        }
+
+        let mmap = unsafe { memmap2::MmapOptions::new().map(file)? };
+        let items = MmapItems {
+            storage: Storage::Mmap(Box::new(mmap)),
+            header_kind: self.builder.header_kind(),
+            dimension: self.builder.dimension,
+            node_size,
+            n_items: self.n_items,
+        };
+        let trees = self.builder.build_trees(&items, n_trees, |thread| {
+            let tree_file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(tree_file_path(thread))?;
+            Ok(BufWriter::new(tree_file))
+        })?;
+
+        // Append the trees of each thread, renumbered as in `AnnoyIndexBuilder::assemble`
+        file.seek(SeekFrom::End(0))?;
+        let mut out = BufWriter::new(file);
+        let mut roots = Vec::new();
+        let mut offset = 0;
+        let mut node = vec![0; node_size];
+        for trees in trees {
+            let mut tree_file = trees.nodes.into_inner().map_err(|e| e.into_error())?;
+            tree_file.seek(SeekFrom::Start(0))?;
+            let mut tree_file = BufReader::new(tree_file);
+            for _ in 0..trees.n_nodes {
+                tree_file.read_exact(&mut node)?;
+                self.builder.relocate(&mut node, self.n_items, offset);
+                out.write_all(&node)?;
+            }
+            roots.extend(trees.roots.iter().map(|root| root + offset));
+            offset += trees.n_nodes as i32;
+        }
+        out.flush()?;
+        drop(out);
+
+        let mut root_nodes = vec![0; roots.len() * node_size];
+        for (root, root_node) in roots.iter().zip(root_nodes.chunks_exact_mut(node_size)) {
+            file.seek(SeekFrom::Start((*root as usize * node_size) as u64))?;
+            file.read_exact(root_node)?;
+        }
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&root_nodes)?;
+        if self.builder.write_metadata {
+            let nodes = unsafe { memmap2::MmapOptions::new().map(file)? };
+            let metadata = self.builder.metadata(self.n_items, n_trees, &nodes);
+            drop(nodes);
+            file.write_all(&metadata.unwrap().to_bytes())?;
+        }
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Dot factors depend on the largest norm, so they're only known once all items are added.
+    fn write_dot_factors(&self, file: &File) -> std::result::Result<(), Box<dyn Error>> {
+        let node_size = self.builder.node_size();
+        let header_size = NodeHeaderKind::Dot.header_size();
+        let mut mmap = unsafe { memmap2::MmapOptions::new().map_mut(file)? };
+        let dot_factors = dot_factors((0..self.n_items).map(|id| {
+            mmap.read_slice::<f32>(id * node_size + header_size, self.builder.dimension)
+        }));
+        for (id, dot_factor) in dot_factors.into_iter().enumerate() {
+            let offset = id * node_size;
+            let n_descendants = mmap.read_i32(offset);
+            let mut header = NodeHeader::new_split(NodeHeaderKind::Dot, n_descendants, [0, 0], 0.0);
+            header.set_dot_factor(dot_factor);
+            mmap[offset..offset + header_size].copy_from_slice(header.as_bytes());
+        }
+        mmap.flush()?;
+        Ok(())
+    }
+}
+
+/// Item nodes of the index file being built.
+struct MmapItems {
+    storage: Storage,
+    header_kind: NodeHeaderKind,
+    dimension: usize,
+    node_size: usize,
+    n_items: usize,
+}
+
+impl BuildItems for MmapItems {
+    fn n_items(&self) -> usize {
+        self.n_items
+    }
+
+    fn is_item(&self, id: usize) -> bool {
+        self.storage.read_i32(id * self.node_size) == 1
+    }
+
+    fn node(&self, id: i32) -> BuildNode<'_> {
+        let offset = id as usize * self.node_size;
+        BuildNode {
+            v: self
+                .storage
+                .read_slice(offset + self.header_kind.header_size(), self.dimension),
+            dot_factor: NodeHeader::new(offset, self.header_kind, &self.storage).get_dot_factor(),
+        }
+    }
+}
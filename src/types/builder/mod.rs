@@ -0,0 +1,561 @@
+use super::node::NodeHeader;
+use super::utils::*;
+use super::*;
+use crate::internals::random::Kiss64Random;
+use split::*;
+use std::error::Error;
+use std::io::{self, Write};
+use std::{collections::BTreeMap, sync::Arc};
+
+cfg_if! {
+    if #[cfg(not(target_arch = "wasm32"))] {
+        mod disk;
+        pub use disk::*;
+    }
+}
+mod split;
+
+/// Builds f32 index files that [`AnnoyIndex`] and annoy itself can serve.
+///
+/// Items are added by id, ids that are never added are left as holes. Trees are built like annoy
+/// does, with the same [two means](https://github.com/spotify/annoy/blob/main/src/annoylib.h)
+/// splits and random number generator, so that the same items and seed give trees of the same
+/// structure as annoy. Custom distances split with [`Distance::create_split`] instead.
+///
+/// Trees are built on [`AnnoyIndexBuilder::set_n_threads`] threads, thread `i` seeded with
+/// `seed + i` as in annoy. The output only depends on the items, the seed and the number of threads.
+pub struct AnnoyIndexBuilder {
+    dimension: usize,
+    index_type: IndexType,
+    distance: Metric,
+    items: Vec<Option<Vec<f32>>>,
+    seed: u64,
+    n_threads: usize,
+    write_metadata: bool,
+    user_metadata: BTreeMap<String, String>,
+}
+
+impl AnnoyIndexBuilder {
+    pub fn new(
+        dimension: usize,
+        index_type: IndexType,
+    ) -> std::result::Result<Self, Box<dyn Error>> {
+        let distance = Metric::builtin(&index_type)
+            .ok_or_else(|| format!("Index type {index_type} not supported"))?;
+        Ok(Self::new_inner(dimension, index_type, distance))
+    }
+
+    /// Builds an index served by [`AnnoyIndex::load_with_distance`] with the same `distance`.
+    pub fn with_distance(dimension: usize, distance: impl Distance + 'static) -> Self {
+        Self::new_inner(
+            dimension,
+            IndexType::Custom,
+            Metric::Custom(Arc::new(distance)),
+        )
+    }
+
+    pub(crate) fn new_inner(dimension: usize, index_type: IndexType, distance: Metric) -> Self {
+        AnnoyIndexBuilder {
+            dimension,
+            index_type,
+            distance,
+            items: Vec::new(),
+            seed: Kiss64Random::DEFAULT_SEED,
+            n_threads: 1,
+            write_metadata: false,
+            user_metadata: BTreeMap::new(),
+        }
+    }
+
+    pub fn index_type(&self) -> &IndexType {
+        &self.index_type
+    }
+
+    /// Seed of the random choices made while building, annoy's default seed unless set.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Number of threads building trees, 1 by default. Builds with one thread are meant to lay
+    /// out nodes like annoy built with the same seed and `n_jobs=1`, others keep the trees of
+    /// each thread together in thread order.
+    pub fn set_n_threads(&mut self, n_threads: usize) {
+        self.n_threads = n_threads.max(1);
+    }
+
+    /// Appends an [`IndexMetadata`] footer to built files, with the build parameters and
+    /// entries from [`AnnoyIndexBuilder::insert_metadata`]. Annoy itself can't load such files.
+    pub fn set_write_metadata(&mut self, write_metadata: bool) {
+        self.write_metadata = write_metadata;
+    }
+
+    /// Adds a free-form entry to the footer, which is then written.
+    pub fn insert_metadata(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.user_metadata.insert(key.into(), value.into());
+        self.write_metadata = true;
+    }
+
+    pub fn add_item(
+        &mut self,
+        item: u64,
+        vector: &[f32],
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        self.check_dimension(vector)?;
+        let item = item as usize;
+        if item >= self.items.len() {
+            self.items.resize(item + 1, None);
+        }
+        self.items[item] = Some(vector.to_vec());
+        Ok(())
+    }
+
+    /// Number of item slots, i.e. the largest added id plus one.
+    pub fn n_items(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns the bytes of the index file.
+    pub fn build(&self, n_trees: usize) -> std::result::Result<Vec<u8>, Box<dyn Error>> {
+        let dot_factors = self.dot_factors();
+        let items = self.memory_items(&dot_factors);
+        let trees = self.build_trees(&items, n_trees, |_| Ok(Vec::new()))?;
+        let mut nodes = self.assemble(&dot_factors, trees);
+        if let Some(metadata) = self.metadata(self.items.len(), n_trees, &nodes) {
+            nodes.extend(metadata.to_bytes());
+        }
+        Ok(nodes)
+    }
+
+    /// Footer of the index whose nodes are `nodes`, if [`AnnoyIndexBuilder::set_write_metadata`].
+    pub(crate) fn metadata(
+        &self,
+        n_items: usize,
+        n_trees: usize,
+        nodes: &[u8],
+    ) -> Option<IndexMetadata> {
+        if !self.write_metadata {
+            return None;
+        }
+        let mut metadata = IndexMetadata::new(
+            self.index_type.clone(),
+            VectorEncoding::F32,
+            self.dimension,
+            n_items,
+            n_trees,
+            nodes,
+        );
+        metadata.build_params = Some(BuildParams {
+            seed: self.seed,
+            n_threads: self.n_threads,
+        });
+        metadata.user = self.user_metadata.clone();
+        Some(metadata)
+    }
+
+    pub(crate) fn check_dimension(
+        &self,
+        vector: &[f32],
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        if vector.len() != self.dimension {
+            return Err(format!(
+                "Wrong input dimension, {} expected, {} provided.",
+                self.dimension,
+                vector.len()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn memory_items<'a>(&'a self, dot_factors: &'a [f32]) -> MemoryItems<'a> {
+        MemoryItems {
+            items: &self.items,
+            dot_factors,
+        }
+    }
+
+    fn header_kind(&self) -> NodeHeaderKind {
+        self.distance.header_kind()
+    }
+
+    pub(crate) fn node_size(&self) -> usize {
+        VectorEncoding::F32.node_size(self.header_kind().header_size(), self.dimension)
+    }
+
+    fn max_descendants(&self) -> usize {
+        (self.node_size() - self.header_kind().offset_before_children()) / INT32_SIZE
+    }
+
+    /// Builds the trees on [`AnnoyIndexBuilder::set_n_threads`] threads, each writing its nodes
+    /// to the output `output` creates for it.
+    pub(crate) fn build_trees<W: Write + Send>(
+        &self,
+        items: &dyn BuildItems,
+        n_trees: usize,
+        output: impl Fn(usize) -> io::Result<W> + Sync,
+    ) -> std::result::Result<Vec<Trees<W>>, Box<dyn Error>> {
+        let live: Vec<i32> = (0..items.n_items())
+            .filter(|&id| items.is_item(id))
+            .map(|id| id as i32)
+            .collect();
+        if live.len() < 2 {
+            return Err("At least 2 items are required to build an index".into());
+        }
+        if n_trees == 0 {
+            return Err("n_trees must be positive".into());
+        }
+        let build_trees = |thread: usize| {
+            let seed = self.seed.wrapping_add(thread as u64);
+            let mut writer = TreeWriter::new(self, items, output(thread)?, seed);
+            let n_thread_trees = (n_trees + thread) / self.n_threads;
+            let roots = (0..n_thread_trees)
+                .map(|_| writer.make_tree(&live, true))
+                .collect::<io::Result<Vec<i32>>>()?;
+            Ok(writer.finish(roots))
+        };
+        cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                let trees: io::Result<Vec<Trees<W>>> = (0..self.n_threads).map(build_trees).collect();
+            } else {
+                let trees: io::Result<Vec<Trees<W>>> = std::thread::scope(|scope| {
+                    let handles: Vec<_> = (0..self.n_threads)
+                        .map(|thread| scope.spawn(move || build_trees(thread)))
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|handle| handle.join().unwrap())
+                        .collect()
+                });
+            }
+        }
+        Ok(trees?)
+    }
+
+    /// Extra dimension of each item slot for dot indexes, empty for other metrics.
+    pub(crate) fn dot_factors(&self) -> Vec<f32> {
+        if self.index_type != IndexType::Dot {
+            return Vec::new();
+        }
+        let zeros = vec![0.0; self.dimension];
+        dot_factors(
+            self.items
+                .iter()
+                .map(|item| item.as_deref().unwrap_or(&zeros)),
+        )
+    }
+
+    /// Writes the item nodes followed by the trees of each writer and copies of all roots, which
+    /// the loader finds at the end of the file as in annoy.
+    pub(crate) fn assemble(&self, dot_factors: &[f32], trees: Vec<Trees<Vec<u8>>>) -> Vec<u8> {
+        let node_size = self.node_size();
+        let n_tree_nodes: usize = trees.iter().map(|trees| trees.nodes.len()).sum();
+        let n_roots: usize = trees.iter().map(|trees| trees.roots.len()).sum();
+        let mut nodes = Vec::with_capacity((self.items.len() + n_roots) * node_size + n_tree_nodes);
+        for (id, item) in self.items.iter().enumerate() {
+            let dot_factor = dot_factors.get(id).copied().unwrap_or(0.0);
+            self.push_item_node(&mut nodes, item.as_deref(), dot_factor);
+        }
+
+        // Nodes of each writer are numbered as if they directly followed the items
+        let mut roots = Vec::with_capacity(n_roots);
+        let mut offset = 0;
+        for trees in trees.iter() {
+            for node in trees.nodes.chunks_exact(node_size) {
+                let start = nodes.len();
+                nodes.extend_from_slice(node);
+                self.relocate(&mut nodes[start..], self.items.len(), offset);
+            }
+            roots.extend(trees.roots.iter().map(|root| root + offset));
+            offset += trees.n_nodes as i32;
+        }
+        for root in roots {
+            let start = root as usize * node_size;
+            nodes.extend_from_within(start..start + node_size);
+        }
+        nodes
+    }
+
+    /// Appends the node of an item, or of a hole when `v` is `None`.
+    pub(crate) fn push_item_node(&self, nodes: &mut Vec<u8>, v: Option<&[f32]>, dot_factor: f32) {
+        let header_kind = self.header_kind();
+        let mut header = match v {
+            // annoy caches the squared norm of angular items in place of the children
+            Some(v) if header_kind == NodeHeaderKind::Angular => {
+                NodeHeader::new_split(header_kind, 1, [dot(v, v).to_bits() as i32, 0], 0.0)
+            }
+            _ => NodeHeader::new_split(header_kind, v.is_some() as i32, [0, 0], 0.0),
+        };
+        header.set_dot_factor(dot_factor);
+        let zeros;
+        let v = match v {
+            Some(v) => v,
+            None => {
+                zeros = vec![0.0; self.dimension];
+                &zeros
+            }
+        };
+        push_vector_node(nodes, self.node_size(), &header, v);
+    }
+
+    /// Renumbers the children of a split node written by a [`TreeWriter`] whose nodes end up
+    /// `offset` nodes after the items.
+    pub(crate) fn relocate(&self, node: &mut [u8], n_items: usize, offset: i32) {
+        let n_descendants = i32::from_ne_bytes(node[..INT32_SIZE].try_into().unwrap());
+        if offset == 0 || n_descendants as usize <= self.max_descendants() {
+            return;
+        }
+        let children_offset = self.header_kind().offset_before_children();
+        for child in 0..2 {
+            let at = children_offset + child * INT32_SIZE;
+            let id = i32::from_ne_bytes(node[at..at + INT32_SIZE].try_into().unwrap());
+            if id as usize >= n_items {
+                node[at..at + INT32_SIZE].copy_from_slice(&(id + offset).to_ne_bytes());
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(
+        &self,
+        n_trees: usize,
+        index_file_path: &str,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        let buffer = self.build(n_trees)?;
+        std::fs::write(index_file_path, buffer)?;
+        Ok(())
+    }
+}
+
+fn push_vector_node(nodes: &mut Vec<u8>, node_size: usize, header: &NodeHeader, v: &[f32]) {
+    let start = nodes.len();
+    nodes.extend_from_slice(header.as_bytes());
+    VectorEncoding::F32.encode(v, nodes);
+    nodes.resize(start + node_size, 0);
+}
+
+/// Item slots trees are built from, kept in memory or in the node file of [`DiskIndexBuilder`].
+pub(crate) trait BuildItems: Sync {
+    /// Number of item slots, including holes.
+    fn n_items(&self) -> usize;
+
+    fn is_item(&self, id: usize) -> bool;
+
+    /// Vector of a slot that [`BuildItems::is_item`].
+    fn node(&self, id: i32) -> BuildNode<'_>;
+}
+
+pub(crate) struct MemoryItems<'a> {
+    items: &'a [Option<Vec<f32>>],
+    dot_factors: &'a [f32],
+}
+
+impl BuildItems for MemoryItems<'_> {
+    fn n_items(&self) -> usize {
+        self.items.len()
+    }
+
+    fn is_item(&self, id: usize) -> bool {
+        self.items[id].is_some()
+    }
+
+    fn node(&self, id: i32) -> BuildNode<'_> {
+        BuildNode {
+            v: self.items[id as usize].as_deref().unwrap(),
+            dot_factor: self.dot_factors.get(id as usize).copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Tree nodes written by a [`TreeWriter`], numbered after the items as if no other writer existed.
+pub(crate) struct Trees<W> {
+    pub(crate) nodes: W,
+    pub(crate) n_nodes: usize,
+    pub(crate) roots: Vec<i32>,
+}
+
+/// Writes the nodes of trees, numbered after the items, as annoy's `_make_tree` does.
+pub(crate) struct TreeWriter<'a, W> {
+    builder: &'a AnnoyIndexBuilder,
+    items: &'a dyn BuildItems,
+    split: Option<AnnoySplit>,
+    header_kind: NodeHeaderKind,
+    node_size: usize,
+    pub(crate) max_descendants: usize,
+    out: W,
+    n_tree_nodes: usize,
+    node: Vec<u8>,
+    rng: Kiss64Random,
+}
+
+impl TreeWriter<'_, Vec<u8>> {
+    /// Overwrites the n_descendants of the last written node, e.g. when it turns out to be a root.
+    pub(crate) fn set_last_n_descendants(&mut self, n_descendants: usize) {
+        let start = self.out.len() - self.node_size;
+        self.out[start..start + 4].copy_from_slice(&(n_descendants as i32).to_ne_bytes());
+    }
+}
+
+impl<'a, W: Write> TreeWriter<'a, W> {
+    pub(crate) fn new(
+        builder: &'a AnnoyIndexBuilder,
+        items: &'a dyn BuildItems,
+        out: W,
+        seed: u64,
+    ) -> TreeWriter<'a, W> {
+        TreeWriter {
+            builder,
+            items,
+            split: AnnoySplit::for_index_type(&builder.index_type),
+            header_kind: builder.header_kind(),
+            node_size: builder.node_size(),
+            max_descendants: builder.max_descendants(),
+            out,
+            n_tree_nodes: 0,
+            node: Vec::new(),
+            rng: Kiss64Random::new(seed),
+        }
+    }
+
+    pub(crate) fn finish(self, roots: Vec<i32>) -> Trees<W> {
+        Trees {
+            nodes: self.out,
+            n_nodes: self.n_tree_nodes,
+            roots,
+        }
+    }
+
+    /// Writes the node in `self.node` and returns its id.
+    fn write_node(&mut self) -> io::Result<i32> {
+        self.node.resize(self.node_size, 0);
+        self.out.write_all(&self.node)?;
+        self.node.clear();
+        self.n_tree_nodes += 1;
+        Ok((self.items.n_items() + self.n_tree_nodes - 1) as i32)
+    }
+
+    pub(crate) fn push_vector_node(&mut self, header: &NodeHeader, v: &[f32]) -> io::Result<i32> {
+        push_vector_node(&mut self.node, self.node_size, header, v);
+        self.write_node()
+    }
+
+    /// Leaves list their descendants from the children field on, the rest of the node is zeroed.
+    fn push_leaf_node_with_descendants(
+        &mut self,
+        n_descendants: usize,
+        ids: &[i32],
+    ) -> io::Result<i32> {
+        self.node.extend((n_descendants as i32).to_ne_bytes());
+        self.node
+            .resize(self.header_kind.offset_before_children(), 0);
+        ids.iter().for_each(|id| self.node.extend(id.to_ne_bytes()));
+        self.write_node()
+    }
+
+    pub(crate) fn push_leaf_node(&mut self, ids: &[i32]) -> io::Result<i32> {
+        self.push_leaf_node_with_descendants(ids.len(), ids)
+    }
+
+    /// The loader reads the index size from the roots' n_descendants, a leaf root claims all
+    /// item slots while listing fewer ids, followed by zeros that search dedups.
+    pub(crate) fn push_root_leaf_node(&mut self, ids: &[i32]) -> io::Result<i32> {
+        self.push_leaf_node_with_descendants(self.items.n_items(), ids)
+    }
+
+    /// Children are written before their parent, so the root is the last node of each tree.
+    fn make_tree(&mut self, ids: &[i32], is_root: bool) -> io::Result<i32> {
+        let n_items = self.items.n_items();
+        if ids.len() == 1 && !is_root {
+            return Ok(ids[0]);
+        }
+        if ids.len() <= self.max_descendants
+            && (!is_root || n_items <= self.max_descendants || ids.len() == 1)
+        {
+            return if is_root {
+                self.push_root_leaf_node(ids)
+            } else {
+                self.push_leaf_node(ids)
+            };
+        }
+
+        let mut sides: [Vec<i32>; 2] = Default::default();
+        let mut split = self.create_split(ids);
+        for attempt in 0..3 {
+            if attempt > 0 {
+                split = self.create_split(ids);
+            }
+            sides.iter_mut().for_each(Vec::clear);
+            for &id in ids {
+                let side = self.side(&split, &self.items.node(id));
+                sides[side as usize].push(id);
+            }
+            if split_imbalance(&sides) < 0.95 {
+                break;
+            }
+        }
+        // If no hyperplane separates the items, e.g. identical ones, randomize sides instead
+        while split_imbalance(&sides) > 0.99 {
+            sides.iter_mut().for_each(Vec::clear);
+            split.normal.fill(0.0);
+            for &id in ids {
+                sides[self.rng.flip() as usize].push(id);
+            }
+        }
+
+        // Build the smaller child first, as annoy does for cache locality
+        let flip = (sides[0].len() > sides[1].len()) as usize;
+        let mut children = [0; 2];
+        for side in [flip, 1 - flip] {
+            children[side] = self.make_tree(&sides[side], false)?;
+        }
+        let n_descendants = if is_root { n_items } else { ids.len() };
+        let mut header =
+            NodeHeader::new_split(self.header_kind, n_descendants as i32, children, split.bias);
+        header.set_dot_factor(split.dot_factor);
+        self.push_vector_node(&header, &split.normal)
+    }
+
+    fn create_split(&mut self, ids: &[i32]) -> Split {
+        match self.split {
+            Some(split) => split.create_split(ids, self.items, &mut self.rng),
+            None => {
+                let mut vectors: Vec<Vec<f32>> = ids
+                    .iter()
+                    .map(|&id| self.items.node(id).v.to_vec())
+                    .collect();
+                let anchor = self.rng.index(vectors.len());
+                vectors.swap(0, anchor);
+                let (normal, bias) = self.builder.distance.create_split(&vectors);
+                Split {
+                    normal,
+                    bias,
+                    dot_factor: 0.0,
+                }
+            }
+        }
+    }
+
+    fn side(&mut self, split: &Split, node: &BuildNode) -> bool {
+        match self.split {
+            Some(annoy_split) => annoy_split.side(split, node, &mut self.rng),
+            None => {
+                let header = NodeHeader::new_split(self.header_kind, 0, [0, 0], split.bias);
+                let margin =
+                    self.builder
+                        .distance
+                        .margin(&header, &NodeVector::F32(&split.normal), node.v);
+                if margin != 0.0 {
+                    margin > 0.0
+                } else {
+                    self.rng.flip()
+                }
+            }
+        }
+    }
+}
+
+/// Computed in f32 and compared in f64 as annoy does, so that e.g. 1 item out of 20 is accepted.
+fn split_imbalance(sides: &[Vec<i32>; 2]) -> f64 {
+    let (ls, rs) = (sides[0].len() as f64, sides[1].len() as f64);
+    let f = (ls / (ls + rs + 1e-9)) as f32;
+    f64::from(f.max(1.0 - f))
+}
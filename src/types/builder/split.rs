@@ -0,0 +1,214 @@
+//! Port of the split logic of annoy's `_make_tree` (annoylib.h), so that trees built from the same
+//! items and seed have the same structure as those built by annoy.
+
+use super::BuildItems;
+use crate::internals::random::Kiss64Random;
+use crate::IndexType;
+
+const TWO_MEANS_ITERATION_STEPS: usize = 200;
+
+/// An item while building, `dot_factor` is the extra dimension annoy adds to dot indexes.
+#[derive(Clone, Copy)]
+pub(crate) struct BuildNode<'a> {
+    pub v: &'a [f32],
+    pub dot_factor: f32,
+}
+
+/// Hyperplane of a split node, `bias` is only kept by minkowski metrics and `dot_factor` by dot.
+pub(crate) struct Split {
+    pub normal: Vec<f32>,
+    pub bias: f32,
+    pub dot_factor: f32,
+}
+
+struct Centroid {
+    v: Vec<f32>,
+    dot_factor: f32,
+}
+
+impl Centroid {
+    fn update_mean(&mut self, node: &BuildNode, norm: f32, c: usize) {
+        let c = c as f32;
+        for (m, x) in self.v.iter_mut().zip(node.v) {
+            *m = (*m * c + x / norm) / (c + 1.0);
+        }
+        self.dot_factor = (self.dot_factor * c + node.dot_factor / norm) / (c + 1.0);
+    }
+}
+
+/// Splits of the built-in metrics, each mirroring its annoy counterpart.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum AnnoySplit {
+    Angular,
+    Euclidean,
+    Manhattan,
+    Dot,
+}
+
+impl AnnoySplit {
+    pub fn for_index_type(index_type: &IndexType) -> Option<AnnoySplit> {
+        match index_type {
+            IndexType::Angular => Some(AnnoySplit::Angular),
+            IndexType::Euclidean => Some(AnnoySplit::Euclidean),
+            IndexType::Manhattan => Some(AnnoySplit::Manhattan),
+            IndexType::Dot => Some(AnnoySplit::Dot),
+            _ => None,
+        }
+    }
+
+    pub fn create_split(
+        &self,
+        ids: &[i32],
+        items: &dyn BuildItems,
+        rng: &mut Kiss64Random,
+    ) -> Split {
+        let is_cosine = matches!(self, AnnoySplit::Angular | AnnoySplit::Dot);
+        let (p, q) = self.two_means(ids, items, rng, is_cosine);
+        let normal: Vec<f32> = p.v.iter().zip(q.v.iter()).map(|(a, b)| a - b).collect();
+        let mut split = Split {
+            normal,
+            bias: 0.0,
+            dot_factor: 0.0,
+        };
+        match self {
+            AnnoySplit::Angular => normalize(&mut split.normal),
+            AnnoySplit::Euclidean | AnnoySplit::Manhattan => {
+                normalize(&mut split.normal);
+                for ((n, a), b) in split.normal.iter().zip(p.v.iter()).zip(q.v.iter()) {
+                    split.bias += -n * (a + b) / 2.0;
+                }
+            }
+            AnnoySplit::Dot => {
+                split.dot_factor = p.dot_factor - q.dot_factor;
+                normalize_dot(&mut split.normal, &mut split.dot_factor);
+            }
+        }
+        split
+    }
+
+    /// Side of the split an item goes to, ties are broken randomly.
+    pub fn side(&self, split: &Split, node: &BuildNode, rng: &mut Kiss64Random) -> bool {
+        let margin = match self {
+            AnnoySplit::Angular => dot(&split.normal, node.v),
+            AnnoySplit::Euclidean | AnnoySplit::Manhattan => {
+                split.bias + dot(&split.normal, node.v)
+            }
+            AnnoySplit::Dot => dot(&split.normal, node.v) + split.dot_factor * split.dot_factor,
+        };
+        if margin != 0.0 {
+            margin > 0.0
+        } else {
+            rng.flip()
+        }
+    }
+
+    fn two_means(
+        &self,
+        ids: &[i32],
+        items: &dyn BuildItems,
+        rng: &mut Kiss64Random,
+        is_cosine: bool,
+    ) -> (Centroid, Centroid) {
+        let count = ids.len();
+        let i = rng.index(count);
+        let mut j = rng.index(count - 1);
+        // Ensure that i != j
+        j += (j >= i) as usize;
+
+        let centroid = |node: &BuildNode| {
+            let mut c = Centroid {
+                v: node.v.to_vec(),
+                dot_factor: node.dot_factor,
+            };
+            if is_cosine {
+                match self {
+                    AnnoySplit::Dot => normalize_dot(&mut c.v, &mut c.dot_factor),
+                    _ => normalize(&mut c.v),
+                }
+            }
+            c
+        };
+        let mut p = centroid(&items.node(ids[i]));
+        let mut q = centroid(&items.node(ids[j]));
+
+        let (mut ic, mut jc) = (1, 1);
+        for _ in 0..TWO_MEANS_ITERATION_STEPS {
+            let node = items.node(ids[rng.index(count)]);
+            let di = ic as f32 * self.distance(&p.v, node.v);
+            let dj = jc as f32 * self.distance(&q.v, node.v);
+            let norm = if is_cosine {
+                dot(node.v, node.v).sqrt()
+            } else {
+                1.0
+            };
+            if norm.is_nan() || norm <= 0.0 {
+                continue;
+            }
+            if di < dj {
+                p.update_mean(&node, norm, ic);
+                ic += 1;
+            } else if dj < di {
+                q.update_mean(&node, norm, jc);
+                jc += 1;
+            }
+        }
+        (p, q)
+    }
+
+    fn distance(&self, x: &[f32], y: &[f32]) -> f32 {
+        match self {
+            AnnoySplit::Angular => {
+                let ppqq = dot(x, x) * dot(y, y);
+                if ppqq > 0.0 {
+                    (2.0 - 2.0 * dot(x, y) as f64 / (ppqq as f64).sqrt()) as f32
+                } else {
+                    2.0
+                }
+            }
+            AnnoySplit::Euclidean => x.iter().zip(y).fold(0.0, |d, (a, b)| {
+                let diff = a - b;
+                d + diff * diff
+            }),
+            AnnoySplit::Manhattan => x.iter().zip(y).fold(0.0, |d, (a, b)| d + (a - b).abs()),
+            AnnoySplit::Dot => -dot(x, y),
+        }
+    }
+}
+
+/// Extra dimensions annoy gives items of dot indexes, so that all of them have the same norm.
+pub(crate) fn dot_factors<'a>(vectors: impl Iterator<Item = &'a [f32]> + Clone) -> Vec<f32> {
+    let max_norm = vectors
+        .clone()
+        .map(|v| dot(v, v).max(0.0).sqrt())
+        .fold(0.0, f32::max);
+    vectors
+        .map(|v| {
+            let diff = (max_norm as f64).powi(2) - (dot(v, v).sqrt() as f64).powi(2);
+            if diff < 0.0 {
+                0.0
+            } else {
+                diff.sqrt() as f32
+            }
+        })
+        .collect()
+}
+
+/// Sequential dot product without fused multiply-add, as annoy computes it for reproducible splits.
+pub(crate) fn dot(x: &[f32], y: &[f32]) -> f32 {
+    x.iter().zip(y).fold(0.0, |s, (a, b)| s + a * b)
+}
+
+fn normalize(v: &mut [f32]) {
+    let norm = dot(v, v).sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn normalize_dot(v: &mut [f32], dot_factor: &mut f32) {
+    let norm = (dot(v, v) as f64 + (*dot_factor as f64).powi(2)).sqrt() as f32;
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+        *dot_factor /= norm;
+    }
+}
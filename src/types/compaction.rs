@@ -0,0 +1,186 @@
+use super::builder::TreeWriter;
+use super::*;
+use std::error::Error;
+
+/// A dense index rebuilt from the live items of another one, see [`AnnoyIndex::compact`].
+pub struct Compaction {
+    /// Bytes of the compacted f32 index file.
+    pub buffer: Vec<u8>,
+    /// New id of each old id, `None` for holes and deleted items.
+    pub id_map: Vec<Option<u64>>,
+}
+
+impl Compaction {
+    /// Number of items of the compacted index.
+    pub fn size(&self) -> usize {
+        self.id_map.iter().flatten().count()
+    }
+
+    /// Old id of each new id.
+    pub fn old_ids(&self) -> Vec<u64> {
+        let mut old_ids = vec![0; self.size()];
+        for (old, new) in self.id_map.iter().enumerate() {
+            if let Some(new) = new {
+                old_ids[*new as usize] = old as u64;
+            }
+        }
+        old_ids
+    }
+
+    /// The mapping as text, one `old_id<TAB>new_id` line per live item.
+    pub fn id_map_text(&self) -> String {
+        self.id_map
+            .iter()
+            .enumerate()
+            .filter_map(|(old, new)| new.map(|new| format!("{old}\t{new}\n")))
+            .collect()
+    }
+
+    /// Writes the compacted index and the mapping file of [`Compaction::id_map_text`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(
+        &self,
+        index_file_path: &str,
+        id_map_file_path: &str,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        std::fs::write(index_file_path, &self.buffer)?;
+        std::fs::write(id_map_file_path, self.id_map_text())?;
+        Ok(())
+    }
+}
+
+/// Part of an original tree rewritten with new ids, small ones are merged into leaves.
+enum Subtree {
+    Items(Vec<i32>),
+    Node(i32, usize),
+}
+
+impl Subtree {
+    fn count(&self) -> usize {
+        match self {
+            Subtree::Items(ids) => ids.len(),
+            Subtree::Node(_, count) => *count,
+        }
+    }
+}
+
+impl AnnoyIndex {
+    /// Rebuilds a dense index from the items that are neither holes nor deleted, assigning new
+    /// ids in the order of the old ones. The compacted index has as many trees as this one.
+    ///
+    /// With `preserve_splits`, the original trees are kept with removed items dropped from them,
+    /// so search quality stays comparable, otherwise trees are built anew.
+    /// Quantized indexes are compacted into f32 indexes.
+    pub fn compact(
+        &self,
+        preserve_splits: bool,
+    ) -> std::result::Result<Compaction, Box<dyn Error>> {
+        let mut builder = AnnoyIndexBuilder::new_inner(
+            self.dimension,
+            self.index_type.clone(),
+            self.distance.clone(),
+        );
+        let mut id_map = vec![None; self.size];
+        let mut n_live = 0;
+        for (id, new_id) in id_map.iter_mut().enumerate() {
+            let is_item = self.get_node_from_id(id).header.get_n_descendant() == 1;
+            if is_item && !self.is_deleted(id as u64) {
+                builder.add_item(n_live, &self.get_item_vector(id as u64))?;
+                *new_id = Some(n_live);
+                n_live += 1;
+            }
+        }
+
+        let buffer = if preserve_splits {
+            if n_live < 2 {
+                return Err("At least 2 items are required to build an index".into());
+            }
+            let dot_factors = builder.dot_factors();
+            let items = builder.memory_items(&dot_factors);
+            let mut writer = TreeWriter::new(&builder, &items, Vec::new(), 0);
+            let mut roots = Vec::with_capacity(self.roots.len());
+            for &root in self.roots.iter() {
+                roots.push(
+                    match self.copy_subtree(&mut writer, root as i32, &id_map, true)? {
+                        Some(Subtree::Node(id, _)) => id,
+                        Some(Subtree::Items(ids)) => writer.push_root_leaf_node(&ids)?,
+                        None => unreachable!("a tree holds all items"),
+                    },
+                );
+            }
+            builder.assemble(&dot_factors, vec![writer.finish(roots)])
+        } else {
+            builder.build(self.roots.len())?
+        };
+        Ok(Compaction { buffer, id_map })
+    }
+
+    fn copy_subtree(
+        &self,
+        writer: &mut TreeWriter<Vec<u8>>,
+        node_id: i32,
+        id_map: &[Option<u64>],
+        is_root: bool,
+    ) -> std::io::Result<Option<Subtree>> {
+        let new_id = |id: i32| id_map[id as usize].map(|new| new as i32);
+        let node = self.get_node_from_id(node_id as usize);
+        let n_descendants = node.header.get_n_descendant();
+        if n_descendants == 1 && (node_id as usize) < self.size {
+            return Ok(new_id(node_id).map(|id| Subtree::Items(vec![id])));
+        }
+        if n_descendants <= self.max_descendants {
+            let mut ids: Vec<i32> = self
+                .get_descendant_id_slice(node.offset, n_descendants as usize)
+                .iter()
+                .filter_map(|&id| new_id(id))
+                .collect();
+            // Leaf roots of small indexes are padded with duplicates
+            ids.sort_unstable();
+            ids.dedup();
+            return Ok((!ids.is_empty()).then_some(Subtree::Items(ids)));
+        }
+
+        let [child_0, child_1] = node.header.get_children_id_slice();
+        let left = self.copy_subtree(writer, child_0, id_map, false)?;
+        let right = self.copy_subtree(writer, child_1, id_map, false)?;
+        let (left, right) = match (left, right) {
+            (Some(left), Some(right)) => (left, right),
+            // The split separates nothing anymore. A surviving split node, the last one written,
+            // becomes the root, and the loader reads the index size from the roots.
+            (Some(Subtree::Node(id, count)), None) | (None, Some(Subtree::Node(id, count)))
+                if is_root =>
+            {
+                writer.set_last_n_descendants(id_map.iter().flatten().count());
+                return Ok(Some(Subtree::Node(id, count)));
+            }
+            (subtree, None) | (None, subtree) => return Ok(subtree),
+        };
+        let count = left.count() + right.count();
+        if count <= writer.max_descendants {
+            if let (Subtree::Items(mut left), Subtree::Items(right)) = (left, right) {
+                left.extend(right);
+                return Ok(Some(Subtree::Items(left)));
+            }
+            unreachable!("subtrees with fewer items than a leaf are leaves");
+        }
+
+        let mut write = |subtree: Subtree| match subtree {
+            Subtree::Items(ids) if ids.len() == 1 => Ok(ids[0]),
+            Subtree::Items(ids) => writer.push_leaf_node(&ids),
+            Subtree::Node(id, _) => Ok(id),
+        };
+        let children = [write(left)?, write(right)?];
+        // The loader reads the index size from the roots
+        let n_descendants = if is_root {
+            id_map.iter().flatten().count()
+        } else {
+            count
+        };
+        let header = node.header.with_descendants(n_descendants as i32, children);
+        let normal = self.get_node_vector(node.offset).to_vec();
+        Ok(Some(Subtree::Node(
+            writer.push_vector_node(&header, &normal)?,
+            count,
+        )))
+    }
+}
@@ -0,0 +1,521 @@
+use super::*;
+use std::error::Error;
+
+/// File formats of item vectors, as exported by embedding pipelines and
+/// [ANN benchmarks](http://corpus-texmex.irisa.fr/) datasets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorFormat {
+    /// Each vector as a little endian i32 dimension followed by as many f32.
+    Fvecs,
+    /// Each vector as a little endian i32 dimension followed by as many u8.
+    Bvecs,
+    /// A 2-d numpy array of f2, f4, f8, u1 or i1 in C order.
+    Npy,
+    /// One comma separated vector per line.
+    Csv,
+}
+
+impl VectorFormat {
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &str) -> Option<VectorFormat> {
+        let (_, extension) = path.rsplit_once('.')?;
+        extension.to_lowercase().parse().ok()
+    }
+}
+
+impl Display for VectorFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let r = format!("{self:?}").to_lowercase();
+        f.write_str(&r)
+    }
+}
+
+impl std::str::FromStr for VectorFormat {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "fvecs" => Ok(VectorFormat::Fvecs),
+            "bvecs" => Ok(VectorFormat::Bvecs),
+            "npy" => Ok(VectorFormat::Npy),
+            "csv" => Ok(VectorFormat::Csv),
+            _ => Err(format!("Invalid vector format {s}").into()),
+        }
+    }
+}
+
+/// Layout of vector files, also used by [`Vectors::to_bytes`] so that written files are read
+/// back with the same options.
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Column holding the item ids, which is removed from the vectors. Without one, items are
+    /// numbered in file order.
+    pub id_column: Option<usize>,
+    /// Whether the first line of a CSV file is a header.
+    pub has_header: bool,
+}
+
+/// Item vectors read from a file, see [`Vectors::read`].
+#[derive(Debug, Clone)]
+pub struct Vectors {
+    pub dimension: usize,
+    pub ids: Vec<u64>,
+    /// Vectors one after the other.
+    pub data: Vec<f32>,
+}
+
+impl Vectors {
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn vector(&self, i: usize) -> &[f32] {
+        &self.data[i * self.dimension..(i + 1) * self.dimension]
+    }
+
+    /// Ids and vectors in file order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &[f32])> + '_ {
+        self.ids
+            .iter()
+            .copied()
+            .zip(self.data.chunks_exact(self.dimension.max(1)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read(
+        path: &str,
+        format: VectorFormat,
+        options: &ImportOptions,
+    ) -> std::result::Result<Vectors, Box<dyn Error>> {
+        Self::from_bytes(&std::fs::read(path)?, format, options)
+    }
+
+    pub fn from_bytes(
+        bytes: &[u8],
+        format: VectorFormat,
+        options: &ImportOptions,
+    ) -> std::result::Result<Vectors, Box<dyn Error>> {
+        let mut rows = Rows::new(options.id_column);
+        match format {
+            VectorFormat::Fvecs => {
+                for row in read_vecs(bytes, 4)? {
+                    let values = row.chunks_exact(4);
+                    rows.push(values.map(|v| f32::from_le_bytes(v.try_into().unwrap()) as f64))?;
+                }
+            }
+            VectorFormat::Bvecs => {
+                for row in read_vecs(bytes, 1)? {
+                    rows.push(row.iter().map(|&v| v as f64))?;
+                }
+            }
+            VectorFormat::Npy => read_npy(bytes, &mut rows)?,
+            VectorFormat::Csv => read_csv(bytes, options.has_header, &mut rows)?,
+        }
+        Ok(rows.vectors)
+    }
+
+    /// Encodes the vectors, with ids in `options.id_column` if any. Ids are stored as floats in
+    /// fvecs and npy files, npy arrays are f8 when they hold ids so that those survive.
+    pub fn to_bytes(
+        &self,
+        format: VectorFormat,
+        options: &ImportOptions,
+    ) -> std::result::Result<Vec<u8>, Box<dyn Error>> {
+        let n_columns = self.dimension + options.id_column.is_some() as usize;
+        if options.id_column.is_some_and(|column| column >= n_columns) {
+            return Err(format!("Id column out of {n_columns} columns").into());
+        }
+        let row = |i: usize| {
+            let mut row: Vec<f64> = self.vector(i).iter().map(|&x| x as f64).collect();
+            if let Some(column) = options.id_column {
+                row.insert(column, self.ids[i] as f64);
+            }
+            row
+        };
+        let exact_ids = |max_id: u64| match self.ids.iter().find(|&&id| id > max_id) {
+            Some(id) if options.id_column.is_some() => {
+                Err(format!("Id {id} can't be stored exactly in {format}"))
+            }
+            _ => Ok(()),
+        };
+
+        let mut bytes = Vec::new();
+        match format {
+            VectorFormat::Fvecs => {
+                exact_ids(1 << f32::MANTISSA_DIGITS)?;
+                for i in 0..self.len() {
+                    bytes.extend((n_columns as i32).to_le_bytes());
+                    row(i)
+                        .into_iter()
+                        .for_each(|x| bytes.extend((x as f32).to_le_bytes()));
+                }
+            }
+            VectorFormat::Bvecs => {
+                for i in 0..self.len() {
+                    bytes.extend((n_columns as i32).to_le_bytes());
+                    for x in row(i) {
+                        if !(0.0..=255.0).contains(&x) || x.fract() != 0.0 {
+                            return Err(format!("Value {x} is not a byte").into());
+                        }
+                        bytes.push(x as u8);
+                    }
+                }
+            }
+            VectorFormat::Npy => {
+                exact_ids(1 << f64::MANTISSA_DIGITS)?;
+                let descr = if options.id_column.is_some() {
+                    "<f8"
+                } else {
+                    "<f4"
+                };
+                let header = format!(
+                    "{{'descr': '{descr}', 'fortran_order': False, 'shape': ({}, {n_columns}), }}",
+                    self.len()
+                );
+                // The magic, version and length take 10 bytes, data is aligned on 64 bytes
+                let header_len = (10 + header.len() + 1).next_multiple_of(64) - 10;
+                bytes.extend(b"\x93NUMPY\x01\x00");
+                bytes.extend((header_len as u16).to_le_bytes());
+                bytes.extend(format!("{header:<width$}\n", width = header_len - 1).as_bytes());
+                for i in 0..self.len() {
+                    for x in row(i) {
+                        match options.id_column {
+                            Some(_) => bytes.extend(x.to_le_bytes()),
+                            None => bytes.extend((x as f32).to_le_bytes()),
+                        }
+                    }
+                }
+            }
+            VectorFormat::Csv => {
+                if options.has_header {
+                    let names: Vec<String> = (0..n_columns)
+                        .map(|column| match options.id_column {
+                            Some(id_column) if id_column == column => "id".to_string(),
+                            Some(id_column) if id_column < column => format!("v{}", column - 1),
+                            _ => format!("v{column}"),
+                        })
+                        .collect();
+                    bytes.extend(names.join(",").as_bytes());
+                    bytes.push(b'\n');
+                }
+                for i in 0..self.len() {
+                    let mut fields: Vec<String> =
+                        self.vector(i).iter().map(f32::to_string).collect();
+                    if let Some(column) = options.id_column {
+                        fields.insert(column, self.ids[i].to_string());
+                    }
+                    bytes.extend(fields.join(",").as_bytes());
+                    bytes.push(b'\n');
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(
+        &self,
+        path: &str,
+        format: VectorFormat,
+        options: &ImportOptions,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        std::fs::write(path, self.to_bytes(format, options)?)?;
+        Ok(())
+    }
+
+    pub fn add_to(
+        &self,
+        builder: &mut AnnoyIndexBuilder,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        self.iter()
+            .try_for_each(|(id, vector)| builder.add_item(id, vector))
+    }
+}
+
+/// Collects rows of a file into [`Vectors`], checking that all have the same dimension.
+struct Rows {
+    id_column: Option<usize>,
+    vectors: Vectors,
+}
+
+impl Rows {
+    fn new(id_column: Option<usize>) -> Rows {
+        Rows {
+            id_column,
+            vectors: Vectors {
+                dimension: 0,
+                ids: Vec::new(),
+                data: Vec::new(),
+            },
+        }
+    }
+
+    fn push(
+        &mut self,
+        values: impl Iterator<Item = f64>,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        let start = self.vectors.data.len();
+        let mut id = None;
+        for (column, value) in values.enumerate() {
+            if Some(column) == self.id_column {
+                id = Some(parse_id(value)?);
+            } else {
+                self.vectors.data.push(value as f32);
+            }
+        }
+        self.push_row(start, id)
+    }
+
+    /// Completes a row whose values were appended to `data` from `start` on.
+    fn push_row(
+        &mut self,
+        start: usize,
+        id: Option<u64>,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        let row = self.vectors.ids.len();
+        let dimension = self.vectors.data.len() - start;
+        if self.id_column.is_some() && id.is_none() {
+            return Err(format!("Row {row} has no id column").into());
+        }
+        if row == 0 {
+            self.vectors.dimension = dimension;
+        } else if dimension != self.vectors.dimension {
+            return Err(format!(
+                "Row {row} has dimension {dimension}, {} expected",
+                self.vectors.dimension
+            )
+            .into());
+        }
+        self.vectors.ids.push(id.unwrap_or(row as u64));
+        Ok(())
+    }
+}
+
+fn parse_id(value: f64) -> std::result::Result<u64, Box<dyn Error>> {
+    if value >= 0.0 && value.fract() == 0.0 && value <= u64::MAX as f64 {
+        Ok(value as u64)
+    } else {
+        Err(format!("Invalid id {value}").into())
+    }
+}
+
+/// Splits fvecs, bvecs or ivecs data into the bytes of each vector.
+fn read_vecs(bytes: &[u8], value_size: usize) -> std::result::Result<Vec<&[u8]>, Box<dyn Error>> {
+    let mut rows = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let Some((dimension, tail)) = rest.split_first_chunk::<4>() else {
+            return Err("Truncated vecs file".into());
+        };
+        let dimension = i32::from_le_bytes(*dimension);
+        let len = usize::try_from(dimension)
+            .ok()
+            .and_then(|dimension| dimension.checked_mul(value_size))
+            .ok_or(format!("Invalid vecs dimension {dimension}"))?;
+        if tail.len() < len {
+            return Err("Truncated vecs file".into());
+        }
+        let (row, tail) = tail.split_at(len);
+        rows.push(row);
+        rest = tail;
+    }
+    Ok(rows)
+}
+
+fn read_npy(bytes: &[u8], rows: &mut Rows) -> std::result::Result<(), Box<dyn Error>> {
+    if bytes.len() < 10 || &bytes[..6] != b"\x93NUMPY" {
+        return Err("Not a npy file".into());
+    }
+    let (header_len, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (
+            u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize,
+            12,
+        ),
+        version => return Err(format!("Unsupported npy version {version}").into()),
+    };
+    let data_start = header_start + header_len;
+    let header = std::str::from_utf8(
+        bytes
+            .get(header_start..data_start)
+            .ok_or("Truncated npy file")?,
+    )?;
+    let field = |name: &str| {
+        let start = header
+            .find(&format!("'{name}':"))
+            .ok_or(format!("npy header has no {name}"))?;
+        Ok::<_, String>(header[start + name.len() + 3..].trim_start())
+    };
+
+    let descr = field("descr")?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|d| d.split('\'').next())
+        .ok_or("Invalid npy descr")?;
+    if field("fortran_order")?.starts_with("True") {
+        return Err("Fortran ordered npy arrays are not supported".into());
+    }
+    let (shape, _) = field("shape")?
+        .strip_prefix('(')
+        .and_then(|shape| shape.split_once(')'))
+        .ok_or("Invalid npy shape")?;
+    let shape: Vec<usize> = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(str::parse)
+        .collect::<std::result::Result<_, _>>()?;
+    let [n_rows, n_columns] = shape[..] else {
+        return Err(format!("npy array of shape {shape:?}, 2 dimensions expected").into());
+    };
+
+    let Some((byte_order, kind)) = descr.split_at_checked(1) else {
+        return Err(format!("Unsupported npy dtype {descr}").into());
+    };
+    let big_endian = match byte_order {
+        "<" | "|" => false,
+        ">" => true,
+        "=" => cfg!(target_endian = "big"),
+        _ => return Err(format!("Unsupported npy dtype {descr}").into()),
+    };
+    let value_size = match kind {
+        "f2" => 2,
+        "f4" => 4,
+        "f8" => 8,
+        "u1" | "i1" => 1,
+        _ => return Err(format!("Unsupported npy dtype {descr}").into()),
+    };
+    let decode = |v: &[u8]| -> f64 {
+        let mut buf = [0; 8];
+        buf[..v.len()].copy_from_slice(v);
+        if big_endian {
+            buf[..v.len()].reverse();
+        }
+        match kind {
+            "f2" => half::f16::from_le_bytes([buf[0], buf[1]]).to_f64(),
+            "f4" => f32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            "f8" => f64::from_le_bytes(buf),
+            "u1" => buf[0] as f64,
+            _ => buf[0] as i8 as f64,
+        }
+    };
+
+    let row_size = n_columns
+        .checked_mul(value_size)
+        .ok_or("Invalid npy shape")?;
+    let data_size = n_rows.checked_mul(row_size).ok_or("Invalid npy shape")?;
+    let data = &bytes[data_start..];
+    if data.len() < data_size {
+        return Err("Truncated npy file".into());
+    }
+    if row_size == 0 {
+        return Ok(());
+    }
+    for row in data.chunks_exact(row_size).take(n_rows) {
+        rows.push(row.chunks_exact(value_size).map(decode))?;
+    }
+    Ok(())
+}
+
+fn read_csv(
+    bytes: &[u8],
+    has_header: bool,
+    rows: &mut Rows,
+) -> std::result::Result<(), Box<dyn Error>> {
+    let text = std::str::from_utf8(bytes)?;
+    let lines = text.lines().enumerate().skip(has_header as usize);
+    for (line_number, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+        let parse_error =
+            |field: &str| format!("Invalid value {field:?} on line {}", line_number + 1);
+        let start = rows.vectors.data.len();
+        let mut id = None;
+        for (column, field) in line.split(',').map(str::trim).enumerate() {
+            if Some(column) == rows.id_column {
+                // Parsed as an integer, ids above 2^53 don't survive a float
+                id = Some(field.parse().map_err(|_| parse_error(field))?);
+            } else {
+                let value: f32 = field.parse().map_err(|_| parse_error(field))?;
+                rows.vectors.data.push(value);
+            }
+        }
+        rows.push_row(start, id)?;
+    }
+    Ok(())
+}
+
+/// True nearest neighbors of queries, as in the ivecs files of ANN benchmarks, to measure the
+/// recall of searches.
+#[derive(Debug, Clone)]
+pub struct GroundTruth {
+    /// Ids of the nearest neighbors of each query, nearest first.
+    pub neighbors: Vec<Vec<u64>>,
+}
+
+impl GroundTruth {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_ivecs(path: &str) -> std::result::Result<GroundTruth, Box<dyn Error>> {
+        Self::from_ivecs_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_ivecs_bytes(bytes: &[u8]) -> std::result::Result<GroundTruth, Box<dyn Error>> {
+        let neighbors = read_vecs(bytes, 4)?
+            .into_iter()
+            .map(|row| {
+                row.chunks_exact(4)
+                    .map(|v| {
+                        let id = i32::from_le_bytes(v.try_into().unwrap());
+                        u64::try_from(id).map_err(|_| format!("Invalid id {id}").into())
+                    })
+                    .collect()
+            })
+            .collect::<std::result::Result<_, Box<dyn Error>>>()?;
+        Ok(GroundTruth { neighbors })
+    }
+
+    /// Fraction of the `k` true nearest neighbors of `query` among the first `k` of `id_list`.
+    pub fn recall(&self, query: usize, id_list: &[u64], k: usize) -> f32 {
+        let truth = &self.neighbors[query][..k.min(self.neighbors[query].len())];
+        if truth.is_empty() {
+            return 1.0;
+        }
+        let found = id_list
+            .iter()
+            .take(k)
+            .filter(|id| truth.contains(id))
+            .count();
+        found as f32 / truth.len() as f32
+    }
+
+    /// Mean recall at `k` of [`AnnoyIndexSearchApi::get_nearest`] over `queries`, which are in
+    /// the same order as the ground truth.
+    pub fn evaluate(
+        &self,
+        index: &impl AnnoyIndexSearchApi,
+        queries: &Vectors,
+        k: usize,
+        search_k: i32,
+    ) -> std::result::Result<f32, Box<dyn Error>> {
+        if queries.len() != self.neighbors.len() {
+            return Err(format!(
+                "{} queries for the ground truth of {}",
+                queries.len(),
+                self.neighbors.len()
+            )
+            .into());
+        }
+        if queries.is_empty() {
+            return Ok(1.0);
+        }
+        let total: f32 = (0..queries.len())
+            .map(|query| {
+                let result = index.get_nearest(queries.vector(query), k, search_k, false);
+                self.recall(query, &result.id_list, k)
+            })
+            .sum();
+        Ok(total / queries.len() as f32)
+    }
+}
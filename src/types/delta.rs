@@ -0,0 +1,315 @@
+use super::*;
+use ordered_float::OrderedFloat;
+use std::{collections::HashSet, error::Error};
+
+/// An immutable [`AnnoyIndex`] plus items added since it was built, searched by brute force.
+///
+/// Added items get ids starting from the base index size, search results of both are merged.
+/// Once the delta grows large, [`DeltaIndex::flush`] builds a new index containing all items.
+pub struct DeltaIndex {
+    base: AnnoyIndex,
+    delta: Vec<Vec<f32>>,
+}
+
+impl DeltaIndex {
+    pub fn new(base: AnnoyIndex) -> DeltaIndex {
+        DeltaIndex {
+            base,
+            delta: Vec::new(),
+        }
+    }
+
+    pub fn base(&self) -> &AnnoyIndex {
+        &self.base
+    }
+
+    /// The base index, e.g. to delete items of it with [`AnnoyIndex::delete_item`].
+    pub fn base_mut(&mut self) -> &mut AnnoyIndex {
+        &mut self.base
+    }
+
+    /// Number of items, including added ones.
+    pub fn size(&self) -> usize {
+        self.base.size + self.delta.len()
+    }
+
+    /// Number of items added on top of the base index.
+    pub fn delta_len(&self) -> usize {
+        self.delta.len()
+    }
+
+    /// Adds an item and returns its id.
+    pub fn add_item(&mut self, vector: &[f32]) -> std::result::Result<u64, Box<dyn Error>> {
+        if vector.len() != self.base.dimension {
+            return Err(format!(
+                "Wrong input dimension, {} expected, {} provided.",
+                self.base.dimension,
+                vector.len()
+            )
+            .into());
+        }
+        self.delta.push(vector.to_vec());
+        Ok((self.size() - 1) as u64)
+    }
+
+    /// A builder holding every live item of the base index and the delta under their current ids,
+    /// with the metric of the base index, including a custom one.
+    pub fn to_builder(&self) -> std::result::Result<AnnoyIndexBuilder, Box<dyn Error>> {
+        let mut builder = AnnoyIndexBuilder::new_inner(
+            self.base.dimension,
+            self.base.index_type.clone(),
+            self.base.distance.clone(),
+        );
+        for id in 0..self.base.size {
+            // Skip holes and deleted items of the base index
+            if self.base.get_node_from_id(id).header.get_n_descendant() == 1
+                && !self.base.is_deleted(id as u64)
+            {
+                builder.add_item(id as u64, &self.base.get_item_vector(id as u64))?;
+            }
+        }
+        for (i, v) in self.delta.iter().enumerate() {
+            builder.add_item((self.base.size + i) as u64, v)?;
+        }
+        Ok(builder)
+    }
+
+    /// Builds a new index containing both the base and the added items, returning the bytes of the index file.
+    pub fn flush(&self, n_trees: usize) -> std::result::Result<Vec<u8>, Box<dyn Error>> {
+        self.to_builder()?.build(n_trees)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn flush_to_file(
+        &self,
+        n_trees: usize,
+        index_file_path: &str,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        self.to_builder()?.save(n_trees, index_file_path)
+    }
+
+    /// Vector of an item of the base index or the delta, `None` if the id is out of range.
+    pub fn try_get_item_vector(&self, item_index: u64) -> Option<Vec<f32>> {
+        match (item_index as usize).checked_sub(self.base.size) {
+            Some(i) => self.delta.get(i).cloned(),
+            None => Some(self.base.get_item_vector(item_index)),
+        }
+    }
+
+    /// Merges the nearest base items with the nearest delta items, comparing distances before normalization.
+    fn search(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        is_excluded: impl Fn(u64) -> bool,
+        budget: Option<SearchBudget>,
+    ) -> (AnnoyIndexSearchResult, SearchStats) {
+        let base = &self.base;
+        let (base_result, mut stats) = base.search_unnormalized(
+            query_vector,
+            n_results,
+            search_k,
+            true,
+            &is_excluded,
+            budget,
+        );
+        let mut candidates: Vec<(OrderedFloat<f32>, u64)> = base_result
+            .distance_list
+            .iter()
+            .zip(&base_result.id_list)
+            .map(|(&d, &id)| (OrderedFloat(d), id))
+            .collect();
+        for (i, v) in self.delta.iter().enumerate() {
+            let id = (base.size + i) as u64;
+            if !is_excluded(id) {
+                let d = base.distance.distance(&NodeVector::F32(v), query_vector);
+                stats.distance_computations += 1;
+                candidates.push((OrderedFloat(d), id));
+            }
+        }
+        candidates.sort_unstable();
+        candidates.truncate(n_results);
+
+        let mut base_trees = base_result
+            .id_list
+            .iter()
+            .zip(std::mem::take(&mut stats.result_trees))
+            .collect::<std::collections::HashMap<_, _>>();
+        stats.result_trees = candidates
+            .iter()
+            .map(|(_, id)| base_trees.remove(id).unwrap_or_default())
+            .collect();
+        let result = AnnoyIndexSearchResult {
+            count: candidates.len(),
+            is_distance_included: should_include_distance,
+            id_list: candidates.iter().map(|&(_, id)| id).collect(),
+            distance_list: if should_include_distance {
+                candidates
+                    .iter()
+                    .map(|&(d, _)| base.normalized_distance(d.0))
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            is_truncated: base_result.is_truncated,
+        };
+        (result, stats)
+    }
+
+    /// Searches the neighbours of an item, no results if the id is out of range.
+    fn search_to_item(
+        &self,
+        item_index: u64,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        is_excluded: impl Fn(u64) -> bool,
+    ) -> AnnoyIndexSearchResult {
+        match self.try_get_item_vector(item_index) {
+            Some(item_vector) => {
+                self.search(
+                    &item_vector,
+                    n_results,
+                    search_k,
+                    should_include_distance,
+                    is_excluded,
+                    None,
+                )
+                .0
+            }
+            None => AnnoyIndexSearchResult {
+                count: 0,
+                is_distance_included: should_include_distance,
+                id_list: Vec::new(),
+                distance_list: Vec::new(),
+                is_truncated: false,
+            },
+        }
+    }
+}
+
+impl AnnoyIndexSearchApi for DeltaIndex {
+    /// Panics if the id is out of range, see [`DeltaIndex::try_get_item_vector`].
+    fn get_item_vector(&self, item_index: u64) -> Vec<f32> {
+        self.try_get_item_vector(item_index)
+            .unwrap_or_else(|| panic!("Item id {item_index} out of range"))
+    }
+
+    fn metric(&self) -> Option<&dyn Distance> {
+        self.base.metric()
+    }
+
+    fn get_nearest(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> AnnoyIndexSearchResult {
+        self.search(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            |_| false,
+            None,
+        )
+        .0
+    }
+
+    /// No results if the id is out of range.
+    fn get_nearest_to_item(
+        &self,
+        item_index: u64,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> AnnoyIndexSearchResult {
+        self.search_to_item(
+            item_index,
+            n_results,
+            search_k,
+            should_include_distance,
+            |_| false,
+        )
+    }
+
+    fn get_nearest_excluding(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        excluded_ids: &HashSet<u64>,
+    ) -> AnnoyIndexSearchResult {
+        self.search(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            |id| excluded_ids.contains(&id),
+            None,
+        )
+        .0
+    }
+
+    /// No results if the id is out of range.
+    fn get_nearest_to_item_excluding(
+        &self,
+        item_index: u64,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        exclude_self: bool,
+        excluded_ids: &HashSet<u64>,
+    ) -> AnnoyIndexSearchResult {
+        self.search_to_item(
+            item_index,
+            n_results,
+            search_k,
+            should_include_distance,
+            |id| (exclude_self && id == item_index) || excluded_ids.contains(&id),
+        )
+    }
+
+    /// Stats describe the base index traversal, plus one distance computation per added item.
+    /// Added items are found in no tree.
+    fn get_nearest_with_stats(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> (AnnoyIndexSearchResult, SearchStats) {
+        self.search(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            |_| false,
+            None,
+        )
+    }
+
+    /// The budget only bounds the base index traversal, added items are always searched.
+    fn get_nearest_with_budget(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        budget: SearchBudget,
+    ) -> AnnoyIndexSearchResult {
+        self.search(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            |_| false,
+            Some(budget),
+        )
+        .0
+    }
+}
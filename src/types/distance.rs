@@ -0,0 +1,248 @@
+use super::utils::*;
+use super::{IndexType, NodeHeader, NodeHeaderKind, NodeVector};
+use ordered_float::OrderedFloat;
+use std::sync::Arc;
+
+/// Behaviour of a metric, both for serving and for building indexes.
+///
+/// Built-in metrics are [`AngularDistance`], [`EuclideanDistance`], [`ManhattanDistance`] and
+/// [`DotDistance`], custom metrics can be served via [`crate::AnnoyIndex::load_with_distance`].
+/// Vectors of quantized indexes are decoded to f32 before being passed to custom metrics.
+pub trait Distance: Send + Sync {
+    /// Layout of the node header in index files of this metric.
+    fn header_kind(&self) -> NodeHeaderKind;
+
+    /// Signed margin between the query and the split hyperplane with the given `normal`,
+    /// queries with positive margin descend into the second child.
+    ///
+    /// `bias` comes from the node header: the stored bias of [`NodeHeaderKind::Minkowski`],
+    /// the squared dot factor of [`NodeHeaderKind::Dot`] and `0` for [`NodeHeaderKind::Angular`].
+    fn margin(&self, normal: &[f32], bias: f32, query: &[f32]) -> f32;
+
+    /// Distance between an item and the query, smaller is closer.
+    fn distance(&self, v: &[f32], query: &[f32]) -> f32;
+
+    /// Converts a value returned by [`Distance::distance`] into the distance reported to callers.
+    fn normalized_distance(&self, d: f32) -> f32 {
+        d
+    }
+
+    /// Creates a hyperplane separating `vectors`, returning its normal and the bias
+    /// stored in the node header (only kept by [`NodeHeaderKind::Minkowski`]).
+    fn create_split(&self, vectors: &[Vec<f32>]) -> (Vec<f32>, f32);
+}
+
+impl IndexType {
+    /// Built-in distance of this index type, `None` if it is not supported.
+    pub fn distance(&self) -> Option<Box<dyn Distance>> {
+        match self {
+            IndexType::Angular => Some(Box::new(AngularDistance)),
+            IndexType::Euclidean => Some(Box::new(EuclideanDistance)),
+            IndexType::Manhattan => Some(Box::new(ManhattanDistance)),
+            IndexType::Dot => Some(Box::new(DotDistance)),
+            _ => None,
+        }
+    }
+}
+
+/// Metric of an index, built-in ones are dispatched statically on the hot search path.
+/// Custom metrics are shared, e.g. by a [`crate::DeltaIndex`] base and the builder it flushes to.
+#[derive(Clone)]
+pub(crate) enum Metric {
+    Angular,
+    Euclidean,
+    Manhattan,
+    Dot,
+    Custom(Arc<dyn Distance>),
+}
+
+impl Metric {
+    pub(crate) fn builtin(index_type: &IndexType) -> Option<Metric> {
+        match index_type {
+            IndexType::Angular => Some(Metric::Angular),
+            IndexType::Euclidean => Some(Metric::Euclidean),
+            IndexType::Manhattan => Some(Metric::Manhattan),
+            IndexType::Dot => Some(Metric::Dot),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_distance(&self) -> &dyn Distance {
+        match self {
+            Metric::Angular => &AngularDistance,
+            Metric::Euclidean => &EuclideanDistance,
+            Metric::Manhattan => &ManhattanDistance,
+            Metric::Dot => &DotDistance,
+            Metric::Custom(distance) => distance.as_ref(),
+        }
+    }
+
+    pub(crate) fn header_kind(&self) -> NodeHeaderKind {
+        match self {
+            Metric::Angular => NodeHeaderKind::Angular,
+            Metric::Euclidean | Metric::Manhattan => NodeHeaderKind::Minkowski,
+            Metric::Dot => NodeHeaderKind::Dot,
+            Metric::Custom(distance) => distance.header_kind(),
+        }
+    }
+
+    pub(crate) fn margin(&self, header: &NodeHeader, v: &NodeVector, query: &[f32]) -> f32 {
+        match self {
+            Metric::Angular => v.dot_product(query),
+            Metric::Euclidean | Metric::Manhattan => header.get_bias() + v.dot_product(query),
+            Metric::Dot => v.dot_product(query) + header.get_dot_factor().powi(2),
+            Metric::Custom(distance) => {
+                let bias = header.get_bias() + header.get_dot_factor().powi(2);
+                distance.margin(&v.to_f32(), bias, query)
+            }
+        }
+    }
+
+    pub(crate) fn distance(&self, v: &NodeVector, query: &[f32]) -> f32 {
+        match self {
+            Metric::Angular => v.cosine_distance(query),
+            Metric::Euclidean => v.euclidean_distance(query),
+            Metric::Manhattan => v.manhattan_distance(query),
+            Metric::Dot => -v.dot_product(query),
+            Metric::Custom(distance) => distance.distance(&v.to_f32(), query),
+        }
+    }
+
+    pub(crate) fn normalized_distance(&self, d: f32) -> f32 {
+        match self {
+            Metric::Angular | Metric::Euclidean => d.sqrt(),
+            Metric::Manhattan => d,
+            Metric::Dot => -d,
+            Metric::Custom(distance) => distance.normalized_distance(d),
+        }
+    }
+
+    pub(crate) fn create_split(&self, vectors: &[Vec<f32>]) -> (Vec<f32>, f32) {
+        match self {
+            Metric::Angular => AngularDistance.create_split(vectors),
+            Metric::Euclidean | Metric::Manhattan => two_point_split(vectors),
+            Metric::Dot => DotDistance.create_split(vectors),
+            Metric::Custom(distance) => distance.create_split(vectors),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AngularDistance;
+
+impl Distance for AngularDistance {
+    fn header_kind(&self) -> NodeHeaderKind {
+        NodeHeaderKind::Angular
+    }
+
+    fn margin(&self, normal: &[f32], _bias: f32, query: &[f32]) -> f32 {
+        dot_product(normal, query)
+    }
+
+    fn distance(&self, v: &[f32], query: &[f32]) -> f32 {
+        cosine_distance(v, query)
+    }
+
+    fn normalized_distance(&self, d: f32) -> f32 {
+        d.sqrt()
+    }
+
+    fn create_split(&self, vectors: &[Vec<f32>]) -> (Vec<f32>, f32) {
+        let normalized: Vec<Vec<f32>> = vectors.iter().map(|v| normalize(v)).collect();
+        (two_point_split(&normalized).0, 0.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EuclideanDistance;
+
+impl Distance for EuclideanDistance {
+    fn header_kind(&self) -> NodeHeaderKind {
+        NodeHeaderKind::Minkowski
+    }
+
+    fn margin(&self, normal: &[f32], bias: f32, query: &[f32]) -> f32 {
+        bias + dot_product(normal, query)
+    }
+
+    fn distance(&self, v: &[f32], query: &[f32]) -> f32 {
+        euclidean_distance(v, query)
+    }
+
+    fn normalized_distance(&self, d: f32) -> f32 {
+        d.sqrt()
+    }
+
+    fn create_split(&self, vectors: &[Vec<f32>]) -> (Vec<f32>, f32) {
+        two_point_split(vectors)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ManhattanDistance;
+
+impl Distance for ManhattanDistance {
+    fn header_kind(&self) -> NodeHeaderKind {
+        NodeHeaderKind::Minkowski
+    }
+
+    fn margin(&self, normal: &[f32], bias: f32, query: &[f32]) -> f32 {
+        bias + dot_product(normal, query)
+    }
+
+    fn distance(&self, v: &[f32], query: &[f32]) -> f32 {
+        manhattan_distance(v, query)
+    }
+
+    fn create_split(&self, vectors: &[Vec<f32>]) -> (Vec<f32>, f32) {
+        two_point_split(vectors)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DotDistance;
+
+impl Distance for DotDistance {
+    fn header_kind(&self) -> NodeHeaderKind {
+        NodeHeaderKind::Dot
+    }
+
+    fn margin(&self, normal: &[f32], bias: f32, query: &[f32]) -> f32 {
+        dot_product(normal, query) + bias
+    }
+
+    fn distance(&self, v: &[f32], query: &[f32]) -> f32 {
+        -dot_product(v, query)
+    }
+
+    fn normalized_distance(&self, d: f32) -> f32 {
+        -d
+    }
+
+    fn create_split(&self, vectors: &[Vec<f32>]) -> (Vec<f32>, f32) {
+        (two_point_split(vectors).0, 0.0)
+    }
+}
+
+/// Splits by the bisecting hyperplane between the first vector and the one farthest from it.
+pub(crate) fn two_point_split(vectors: &[Vec<f32>]) -> (Vec<f32>, f32) {
+    let a = &vectors[0];
+    let b = vectors
+        .iter()
+        .max_by_key(|v| OrderedFloat(euclidean_distance(a, v)))
+        .unwrap_or(a);
+    let diff: Vec<f32> = a.iter().zip(b.iter()).map(|(x, y)| x - y).collect();
+    let normal = normalize(&diff);
+    let mid: Vec<f32> = a.iter().zip(b.iter()).map(|(x, y)| (x + y) / 2.0).collect();
+    let bias = -dot_product(&normal, &mid);
+    (normal, bias)
+}
+
+pub(crate) fn normalize(v: &[f32]) -> Vec<f32> {
+    let norm = dot_product(v, v).sqrt();
+    if norm > 0.0 {
+        v.iter().map(|x| x / norm).collect()
+    } else {
+        v.to_vec()
+    }
+}
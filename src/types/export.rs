@@ -0,0 +1,130 @@
+use super::*;
+use std::error::Error;
+use std::io::Write;
+
+impl AnnoyIndex {
+    /// Vectors of all items, skipping holes, e.g. to rebuild an index whose source embeddings
+    /// are lost. Quantized vectors are decoded.
+    pub fn item_vectors(&self) -> Vectors {
+        let mut vectors = Vectors {
+            dimension: self.dimension,
+            ids: Vec::new(),
+            data: Vec::new(),
+        };
+        for id in 0..self.size {
+            let node = self.get_node_from_id(id);
+            if node.header.get_n_descendant() == 1 {
+                vectors.ids.push(id as u64);
+                vectors
+                    .data
+                    .extend(self.get_node_vector(node.offset).iter());
+            }
+        }
+        vectors
+    }
+
+    /// Writes the trees as JSON, one nested object per root in `trees`:
+    ///
+    /// - split nodes as `{"id", "n_descendants", "normal", "children"}`, with `"bias"` for
+    ///   minkowski metrics and `"dot_factor"` for dot,
+    /// - leaves as `{"id", "items"}`,
+    /// - single items as `{"item"}`.
+    pub fn write_tree_json(&self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(
+            out,
+            "{{\"index_type\":\"{}\",\"dimension\":{},\"size\":{},\"trees\":[",
+            self.index_type, self.dimension, self.size
+        )?;
+        for (i, &root) in self.roots.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            self.write_node_json(out, root, true)?;
+        }
+        out.write_all(b"]}")
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_tree_json(&self, path: &str) -> std::result::Result<(), Box<dyn Error>> {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_tree_json(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn write_node_json(
+        &self,
+        out: &mut impl Write,
+        id: usize,
+        is_root: bool,
+    ) -> std::io::Result<()> {
+        let node = self.get_node_from_id(id);
+        let n_descendants = node.header.get_n_descendant();
+        if n_descendants == 1 && id < self.size {
+            return write!(out, "{{\"item\":{id}}}");
+        }
+        if n_descendants <= self.max_descendants {
+            let mut items = self
+                .get_descendant_id_slice(node.offset, n_descendants as usize)
+                .to_vec();
+            if is_root {
+                // Leaf roots of small indexes are padded with duplicates
+                items.sort_unstable();
+                items.dedup();
+            }
+            write!(out, "{{\"id\":{id},\"items\":")?;
+            write_json_array(out, items.iter())?;
+            return out.write_all(b"}");
+        }
+
+        write!(
+            out,
+            "{{\"id\":{id},\"n_descendants\":{n_descendants},\"normal\":"
+        )?;
+        write_json_array(out, self.get_node_vector(node.offset).iter().map(JsonFloat))?;
+        match node.header {
+            NodeHeader::Minkowski(_) => {
+                write!(out, ",\"bias\":{}", JsonFloat(node.header.get_bias()))?
+            }
+            NodeHeader::Dot(_) => write!(
+                out,
+                ",\"dot_factor\":{}",
+                JsonFloat(node.header.get_dot_factor())
+            )?,
+            NodeHeader::Angular(_) => {}
+        }
+        out.write_all(b",\"children\":[")?;
+        let [child_0, child_1] = node.header.get_children_id_slice();
+        self.write_node_json(out, child_0 as usize, false)?;
+        out.write_all(b",")?;
+        self.write_node_json(out, child_1 as usize, false)?;
+        out.write_all(b"]}")
+    }
+}
+
+fn write_json_array(
+    out: &mut impl Write,
+    values: impl Iterator<Item = impl Display>,
+) -> std::io::Result<()> {
+    out.write_all(b"[")?;
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        write!(out, "{value}")?;
+    }
+    out.write_all(b"]")
+}
+
+/// JSON has no NaN nor infinities, those are written as `null`.
+struct JsonFloat(f32);
+
+impl Display for JsonFloat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if self.0.is_finite() {
+            write!(f, "{}", self.0)
+        } else {
+            f.write_str("null")
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use super::*;
+use crate::internals::storage_ext::StorageExtensions;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ops::Range;
+
+const METADATA_MAGIC: &[u8; 8] = b"ANNOYMET";
+const METADATA_VERSION: u32 = 1;
+/// Bytes of nodes covered by each region checksum, small enough to pinpoint corruption.
+const REGION_SIZE: usize = 1 << 16;
+/// Length of the section and magic that end a file with metadata.
+const TRAILER_SIZE: usize = 16;
+
+/// Random choices made while building, see [`AnnoyIndexBuilder::set_seed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildParams {
+    pub seed: u64,
+    pub n_threads: usize,
+}
+
+/// Self-describing footer optionally appended to index files, see
+/// [`AnnoyIndexBuilder::set_write_metadata`].
+///
+/// The footer follows the nodes: a version, the metric, vector encoding, dimension, item and tree
+/// counts, build parameters, CRC32C checksums of the nodes and free-form user entries, then the
+/// length of all that and a magic, all little endian. Loaders find it from the end of the file,
+/// plain annoy files are read as before. Annoy itself doesn't know about the footer, files with
+/// one are only served by this crate.
+///
+/// Loaders check that the footer matches the nodes, i.e. their length, items and roots, which
+/// reading all of them isn't needed for. Checksums are verified by [`AnnoyIndex::verify`] or
+/// [`AnnoyIndex::load_verified`], per region of nodes so that corrupt ones are pinpointed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexMetadata {
+    pub version: u32,
+    pub index_type: IndexType,
+    pub vector_encoding: VectorEncoding,
+    pub dimension: usize,
+    pub n_items: usize,
+    pub n_trees: usize,
+    /// Unknown for indexes that weren't built by this crate.
+    pub build_params: Option<BuildParams>,
+    /// CRC32C of the nodes, i.e. the file up to the footer.
+    pub checksum: u32,
+    /// Bytes of nodes covered by each of `region_checksums`, the last region may be shorter.
+    pub region_size: usize,
+    pub region_checksums: Vec<u32>,
+    /// Provenance such as the source dataset or the embedding model.
+    pub user: BTreeMap<String, String>,
+}
+
+impl IndexMetadata {
+    /// Metadata of the index whose nodes are `nodes`.
+    pub fn new(
+        index_type: IndexType,
+        vector_encoding: VectorEncoding,
+        dimension: usize,
+        n_items: usize,
+        n_trees: usize,
+        nodes: &[u8],
+    ) -> IndexMetadata {
+        let region_checksums: Vec<u32> = nodes.chunks(REGION_SIZE).map(crc32c::crc32c).collect();
+        IndexMetadata {
+            version: METADATA_VERSION,
+            index_type,
+            vector_encoding,
+            dimension,
+            n_items,
+            n_trees,
+            build_params: None,
+            checksum: region_checksums
+                .iter()
+                .zip(nodes.chunks(REGION_SIZE))
+                .fold(0, |crc, (&region_crc, region)| {
+                    crc32c::crc32c_combine(crc, region_crc, region.len())
+                }),
+            region_size: REGION_SIZE,
+            region_checksums,
+            user: BTreeMap::new(),
+        }
+    }
+
+    /// Byte ranges of `nodes` whose checksums don't match, adjacent corrupt regions merged.
+    pub fn corrupt_ranges(&self, nodes: &[u8]) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (i, region) in nodes.chunks(self.region_size).enumerate() {
+            if self.region_checksums.get(i) == Some(&crc32c::crc32c(region)) {
+                continue;
+            }
+            let range = i * self.region_size..i * self.region_size + region.len();
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+        ranges
+    }
+
+    /// Byte ranges of the index file `bytes` whose checksums don't match. Unlike
+    /// [`AnnoyIndex::verify`], the nodes aren't parsed, so files that loaders reject can be checked.
+    pub fn verify_file(bytes: &[u8]) -> std::result::Result<Vec<Range<usize>>, Box<dyn Error>> {
+        let (metadata, nodes_size) = Self::split(bytes)?.ok_or(NO_CHECKSUMS)?;
+        Ok(metadata.corrupt_ranges(&bytes[..nodes_size]))
+    }
+
+    /// Node size of the index, unknown for custom metrics.
+    pub fn node_size(&self) -> Option<usize> {
+        let header_size = Metric::builtin(&self.index_type)?
+            .header_kind()
+            .header_size();
+        Some(self.vector_encoding.node_size(header_size, self.dimension))
+    }
+
+    /// Returns the footer, to be appended to the nodes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&METADATA_VERSION.to_le_bytes());
+        let build_params = self.build_params.clone().unwrap_or(BuildParams {
+            seed: 0,
+            n_threads: 0,
+        });
+        bytes.extend_from_slice(&[
+            self.index_type.clone() as u8,
+            self.vector_encoding as u8,
+            self.build_params.is_some() as u8,
+            0,
+        ]);
+        bytes.extend_from_slice(&(self.dimension as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.n_items as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.n_trees as u64).to_le_bytes());
+        bytes.extend_from_slice(&build_params.seed.to_le_bytes());
+        bytes.extend_from_slice(&(build_params.n_threads as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.checksum.to_le_bytes());
+        bytes.extend_from_slice(&(self.region_size as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.region_checksums.len() as u32).to_le_bytes());
+        for region_checksum in self.region_checksums.iter() {
+            bytes.extend_from_slice(&region_checksum.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.user.len() as u32).to_le_bytes());
+        for (key, value) in self.user.iter() {
+            for s in [key, value] {
+                bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(s.as_bytes());
+            }
+        }
+        bytes.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(METADATA_MAGIC);
+        bytes
+    }
+
+    /// Finds the footer at the end of the index file `bytes`, `None` for plain annoy files.
+    pub fn find(bytes: &[u8]) -> std::result::Result<Option<IndexMetadata>, Box<dyn Error>> {
+        Ok(Self::split(bytes)?.map(|(metadata, _)| metadata))
+    }
+
+    /// Reads the footer of the index file at `path`, without reading the nodes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read(path: &str) -> std::result::Result<Option<IndexMetadata>, Box<dyn Error>> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let mut file = std::fs::File::open(path)?;
+        let file_size = file.metadata()?.len();
+        if file_size < TRAILER_SIZE as u64 {
+            return Ok(None);
+        }
+        let mut trailer = [0; TRAILER_SIZE];
+        file.seek(SeekFrom::End(-(TRAILER_SIZE as i64)))?;
+        file.read_exact(&mut trailer)?;
+        let Some(section_size) = section_size(&trailer, file_size as usize)? else {
+            return Ok(None);
+        };
+        let mut section = vec![0; section_size + TRAILER_SIZE];
+        file.seek(SeekFrom::End(-(section.len() as i64)))?;
+        file.read_exact(&mut section)?;
+        let nodes_size = file_size as usize - section.len();
+        Self::parse(&section[..section_size], nodes_size).map(Some)
+    }
+
+    /// Replaces the footer of the index file at `path`, or appends one to a plain annoy file.
+    /// The index must not be memory mapped meanwhile.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write(&self, path: &str) -> std::result::Result<(), Box<dyn Error>> {
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+        let file_size = file.metadata()?.len();
+        let mut nodes_size = file_size;
+        if file_size >= TRAILER_SIZE as u64 {
+            let mut trailer = [0; TRAILER_SIZE];
+            file.seek(SeekFrom::End(-(TRAILER_SIZE as i64)))?;
+            file.read_exact(&mut trailer)?;
+            if let Some(section_size) = section_size(&trailer, file_size as usize)? {
+                nodes_size -= (section_size + TRAILER_SIZE) as u64;
+            }
+        }
+        file.set_len(nodes_size)?;
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&self.to_bytes())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Splits the index file `bytes` into its metadata and the length of its nodes.
+    pub(crate) fn split(
+        bytes: &[u8],
+    ) -> std::result::Result<Option<(IndexMetadata, usize)>, Box<dyn Error>> {
+        if bytes.len() < TRAILER_SIZE {
+            return Ok(None);
+        }
+        let trailer = &bytes[bytes.len() - TRAILER_SIZE..];
+        let Some(section_size) = section_size(trailer, bytes.len())? else {
+            return Ok(None);
+        };
+        let nodes_size = bytes.len() - TRAILER_SIZE - section_size;
+        let metadata = Self::parse(&bytes[nodes_size..nodes_size + section_size], nodes_size)?;
+        Ok(Some((metadata, nodes_size)))
+    }
+
+    /// Parses the section of a file whose nodes are `nodes_size` bytes long.
+    fn parse(
+        section: &[u8],
+        nodes_size: usize,
+    ) -> std::result::Result<IndexMetadata, Box<dyn Error>> {
+        let mut reader = Reader(section);
+        let version = reader.u32()?;
+        if version != METADATA_VERSION {
+            return Err(format!("Unsupported metadata version {version}").into());
+        }
+        let [index_type, vector_encoding, flags, _] = reader.take(4)? else {
+            unreachable!()
+        };
+        let index_type = IndexType::try_from(*index_type)?;
+        let vector_encoding = VectorEncoding::try_from(*vector_encoding)?;
+        let has_build_params = flags & 1 != 0;
+        let dimension = reader.u32()? as usize;
+        let n_items = reader.u64()? as usize;
+        let n_trees = reader.u64()? as usize;
+        let seed = reader.u64()?;
+        let n_threads = reader.u32()? as usize;
+        let checksum = reader.u32()?;
+        let region_size = reader.u64()? as usize;
+        let region_checksums = (0..reader.u32()?)
+            .map(|_| reader.u32())
+            .collect::<std::result::Result<Vec<u32>, _>>()?;
+        if region_size == 0 || region_checksums.len() != nodes_size.div_ceil(region_size) {
+            return Err("Index metadata doesn't match the file length, it may be truncated".into());
+        }
+        let mut user = BTreeMap::new();
+        for _ in 0..reader.u32()? {
+            let key = reader.string()?;
+            user.insert(key, reader.string()?);
+        }
+        if !reader.0.is_empty() {
+            return Err("Trailing bytes in index metadata".into());
+        }
+        Ok(IndexMetadata {
+            version,
+            index_type,
+            vector_encoding,
+            dimension,
+            n_items,
+            n_trees,
+            build_params: has_build_params.then_some(BuildParams { seed, n_threads }),
+            checksum,
+            region_size,
+            region_checksums,
+            user,
+        })
+    }
+
+    /// Checks that the index is loaded the way it was built.
+    pub(crate) fn check(
+        &self,
+        dimension: usize,
+        index_type: &IndexType,
+        vector_encoding: VectorEncoding,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        if self.dimension != dimension {
+            return Err(format!(
+                "Index of dimension {}, loaded with dimension {dimension}",
+                self.dimension
+            )
+            .into());
+        }
+        // Custom distances may serve files of any metric whose node layout they share
+        if index_type != &IndexType::Custom && &self.index_type != index_type {
+            return Err(
+                format!("Index of type {}, loaded as {index_type}", self.index_type).into(),
+            );
+        }
+        if self.vector_encoding != vector_encoding {
+            return Err(format!(
+                "Index of {} vectors, loaded as {vector_encoding}",
+                self.vector_encoding
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Checks that the footer matches the nodes the loader found.
+    pub(crate) fn check_nodes(
+        &self,
+        nodes_size: usize,
+        node_size: usize,
+        size: usize,
+        n_trees: usize,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        if !nodes_size.is_multiple_of(node_size) || size != self.n_items || n_trees != self.n_trees
+        {
+            return Err(format!(
+                "Index metadata of {} items and {} trees doesn't match the nodes of {size} items \
+                 and {n_trees} trees, the file is corrupt, see IndexMetadata::verify_file",
+                self.n_items, self.n_trees
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Display for IndexMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "version: {}", self.version)?;
+        writeln!(f, "index_type: {}", self.index_type)?;
+        writeln!(f, "vector_encoding: {}", self.vector_encoding)?;
+        writeln!(f, "dimension: {}", self.dimension)?;
+        writeln!(f, "n_items: {}", self.n_items)?;
+        writeln!(f, "n_trees: {}", self.n_trees)?;
+        if let Some(build_params) = &self.build_params {
+            writeln!(f, "seed: {}", build_params.seed)?;
+            writeln!(f, "n_threads: {}", build_params.n_threads)?;
+        }
+        writeln!(
+            f,
+            "regions: {} of {} bytes",
+            self.region_checksums.len(),
+            self.region_size
+        )?;
+        write!(f, "checksum: {:08x}", self.checksum)?;
+        for (key, value) in self.user.iter() {
+            write!(f, "\n{key}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+const NO_CHECKSUMS: &str = "Index file without checksums, it may be truncated";
+
+/// Length of the metadata section ending with `trailer`, `None` without the magic.
+fn section_size(
+    trailer: &[u8],
+    file_size: usize,
+) -> std::result::Result<Option<usize>, Box<dyn Error>> {
+    if &trailer[8..] != METADATA_MAGIC {
+        return Ok(None);
+    }
+    let section_size = u64::from_le_bytes(trailer[..8].try_into()?) as usize;
+    if section_size > file_size - TRAILER_SIZE {
+        return Err("Truncated index metadata".into());
+    }
+    Ok(Some(section_size))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> std::result::Result<&'a [u8], Box<dyn Error>> {
+        if self.0.len() < n {
+            return Err("Truncated index metadata".into());
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> std::result::Result<u64, Box<dyn Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn string(&mut self) -> std::result::Result<String, Box<dyn Error>> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
+
+impl AnnoyIndex {
+    /// Footer of the loaded file, `None` for plain annoy files.
+    pub fn metadata(&self) -> Option<&IndexMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Footer of the loaded file, or metadata describing a plain annoy file, e.g. to annotate it
+    /// with [`IndexMetadata::write`].
+    pub fn describe(&self) -> IndexMetadata {
+        match &self.metadata {
+            Some(metadata) => metadata.clone(),
+            None => IndexMetadata::new(
+                self.index_type.clone(),
+                self.vector_encoding,
+                self.dimension,
+                self.size,
+                self.n_trees(),
+                self.node_bytes(),
+            ),
+        }
+    }
+
+    /// Loads an index whose dimension, type and encoding are read from its footer.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_described(
+        index_file_path: &str,
+    ) -> std::result::Result<AnnoyIndex, Box<dyn Error>> {
+        let metadata = IndexMetadata::read(index_file_path)?
+            .ok_or("Index file without metadata, its dimension and type are unknown")?;
+        if metadata.index_type == IndexType::Custom {
+            return Err("Indexes with a custom metric are loaded with load_with_distance".into());
+        }
+        Self::load_quantized(
+            metadata.dimension,
+            index_file_path,
+            metadata.index_type,
+            metadata.vector_encoding,
+        )
+    }
+
+    /// Same as [`AnnoyIndex::load_described`], from an in-memory buffer.
+    pub fn load_described_from_buffer(
+        buffer: Vec<u8>,
+    ) -> std::result::Result<AnnoyIndex, Box<dyn Error>> {
+        let metadata = IndexMetadata::find(&buffer)?
+            .ok_or("Index without metadata, its dimension and type are unknown")?;
+        if metadata.index_type == IndexType::Custom {
+            return Err("Indexes with a custom metric are loaded with load_with_distance".into());
+        }
+        Self::load_quantized_from_buffer(
+            buffer,
+            metadata.dimension,
+            metadata.index_type,
+            metadata.vector_encoding,
+        )
+    }
+
+    /// Node ranges whose checksums don't match, empty if the index is intact. Errors for plain
+    /// annoy files, which have no checksums. Reads every node, it can run on another thread while
+    /// the index serves searches.
+    pub fn verify(&self) -> std::result::Result<Vec<Range<usize>>, Box<dyn Error>> {
+        let metadata = self.metadata.as_ref().ok_or(NO_CHECKSUMS)?;
+        let node_size = self.node_size;
+        Ok(metadata
+            .corrupt_ranges(self.node_bytes())
+            .into_iter()
+            .map(|range| range.start / node_size..range.end.div_ceil(node_size))
+            .collect())
+    }
+
+    /// Loads an index and verifies all of its checksums, rejecting files without them, e.g.
+    /// torn downloads whose footer is missing, as well as files with corrupt nodes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_verified(
+        dimension: usize,
+        index_file_path: &str,
+        index_type: IndexType,
+    ) -> std::result::Result<AnnoyIndex, Box<dyn Error>> {
+        let index = Self::load(dimension, index_file_path, index_type)?;
+        let corrupt = index.verify()?;
+        if !corrupt.is_empty() {
+            return Err(format!("Corrupt nodes {corrupt:?} in {index_file_path}").into());
+        }
+        Ok(index)
+    }
+
+    /// The nodes of the index, without the footer.
+    pub(crate) fn node_bytes(&self) -> &[u8] {
+        self.storage.read_slice(0, self.n_nodes * self.node_size)
+    }
+}
@@ -0,0 +1,78 @@
+use std::{error::Error, sync::OnceLock, time::Duration};
+
+/// Where the bytes of a loaded index live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    /// Memory mapped file, pages are faulted in lazily while searching.
+    Mmap,
+    /// Owned in-memory buffer.
+    Buffer,
+    /// Bytes kept alive by their owner, e.g. a buffer of another language runtime.
+    External,
+}
+
+/// Reported once per successfully loaded index.
+#[derive(Debug, Clone)]
+pub struct LoadMetrics {
+    pub storage_kind: StorageKind,
+    /// Size of the index in bytes.
+    pub index_size: usize,
+    pub duration: Duration,
+    /// Major page faults of the process while loading, only available on linux.
+    pub major_page_faults: Option<u64>,
+}
+
+/// Reported once per query, whichever search method served it.
+#[derive(Debug, Clone)]
+pub struct SearchMetrics {
+    pub duration: Duration,
+    pub n_results: usize,
+    /// The effective search_k, i.e. `n_results * n_trees` when a non-positive value was passed.
+    pub search_k: usize,
+    pub nodes_visited: usize,
+    pub candidate_count: usize,
+    pub distance_computations: usize,
+    pub is_truncated: bool,
+}
+
+/// Receives metrics of every index in the process, e.g. to aggregate them into counters and
+/// histograms of a metrics exporter. Implementations must be cheap, they run on the search path.
+pub trait MetricsRecorder: Send + Sync {
+    fn record_load(&self, metrics: &LoadMetrics) {
+        let _ = metrics;
+    }
+
+    fn record_search(&self, metrics: &SearchMetrics) {
+        let _ = metrics;
+    }
+}
+
+static RECORDER: OnceLock<Box<dyn MetricsRecorder>> = OnceLock::new();
+
+/// Installs the process wide [`MetricsRecorder`], it can only be installed once.
+pub fn set_metrics_recorder(
+    recorder: impl MetricsRecorder + 'static,
+) -> Result<(), Box<dyn Error>> {
+    RECORDER
+        .set(Box::new(recorder))
+        .map_err(|_| "A metrics recorder is already installed".into())
+}
+
+pub(crate) fn metrics_recorder() -> Option<&'static dyn MetricsRecorder> {
+    RECORDER.get().map(|recorder| recorder.as_ref())
+}
+
+/// Reads `majflt` from `/proc/self/stat`.
+pub(crate) fn major_page_faults() -> Option<u64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // Fields after the parenthesized command name, which may contain spaces; majflt is field 12.
+    stat.rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .nth(9)?
+        .parse()
+        .ok()
+}
@@ -2,8 +2,50 @@ pub(crate) mod annoy_index_impl;
 pub(crate) mod node;
 pub(crate) mod utils;
 
+pub(crate) use node::NodeHeader;
+pub use node::NodeHeaderKind;
+
+pub mod distance;
+pub use distance::*;
+
+pub mod quantization;
+pub use quantization::*;
+
 pub mod serving;
 pub use serving::*;
+
+pub mod tombstones;
+pub use tombstones::*;
+
+pub mod builder;
+pub use builder::*;
+
+pub mod delta;
+pub use delta::*;
+
+pub mod compaction;
+pub use compaction::*;
+
+pub mod dataset;
+pub use dataset::*;
+
+pub mod export;
+
+pub mod metadata;
+pub use metadata::*;
+
+#[cfg(all(feature = "arrow", not(target_arch = "wasm32")))]
+pub mod arrow_interop;
+
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+pub mod metrics;
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+pub use metrics::*;
+
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub mod async_index;
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub use async_index::*;
 use std::{
     fmt::{Display, Formatter, Result},
     ops::Index,
@@ -17,6 +59,69 @@ pub struct AnnoyIndexSearchResult {
     pub is_distance_included: bool,
     pub id_list: Vec<u64>,
     pub distance_list: Vec<f32>,
+    /// Whether traversal stopped early because the [`SearchBudget`] was exhausted.
+    pub is_truncated: bool,
+}
+
+impl AnnoyIndexSearchResult {
+    /// Keeps the first `n` results whose id satisfies `f`.
+    pub(crate) fn retain_first(&mut self, n: usize, f: impl Fn(u64) -> bool) {
+        let kept: Vec<usize> = (0..self.id_list.len())
+            .filter(|&i| f(self.id_list[i]))
+            .take(n)
+            .collect();
+        self.id_list = kept.iter().map(|&i| self.id_list[i]).collect();
+        if self.is_distance_included {
+            self.distance_list = kept.iter().map(|&i| self.distance_list[i]).collect();
+        }
+        self.count = self.id_list.len();
+    }
+}
+
+/// Limits how much work a single query may spend traversing the trees.
+#[derive(Debug, Clone, Copy)]
+pub enum SearchBudget {
+    /// Stop traversal once this instant has passed, checked every
+    /// [`SearchBudget::DEADLINE_CHECK_INTERVAL`] visited nodes.
+    #[cfg(not(target_arch = "wasm32"))]
+    Deadline(std::time::Instant),
+    /// Stop traversal once this many candidates are collected. Each costs a distance evaluation,
+    /// except for duplicates found in several trees.
+    MaxCandidates(usize),
+}
+
+impl SearchBudget {
+    /// Reading the clock for every visited node would slow down traversal noticeably.
+    pub const DEADLINE_CHECK_INTERVAL: usize = 64;
+
+    pub(crate) fn is_exhausted(&self, nodes_visited: usize, n_candidates: usize) -> bool {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Deadline(deadline) => {
+                nodes_visited.is_multiple_of(Self::DEADLINE_CHECK_INTERVAL)
+                    && std::time::Instant::now() >= *deadline
+            }
+            Self::MaxCandidates(max) => n_candidates >= *max,
+        }
+    }
+}
+
+/// Statistics collected while serving a single query, useful for debugging slow queries
+/// or poor results.
+#[derive(Debug, Clone, Default)]
+pub struct SearchStats {
+    /// Number of nodes popped from the traversal queue.
+    pub nodes_visited: usize,
+    /// Number of split nodes whose margin against the query was evaluated.
+    pub margin_evaluations: usize,
+    /// Number of candidate items collected from leaves, before dedup.
+    pub candidate_count: usize,
+    /// Number of distances computed between the query and candidate items.
+    pub distance_computations: usize,
+    /// Number of candidates skipped because they are deleted, see [`AnnoyIndex::delete_item`].
+    pub deleted_skipped: usize,
+    /// Indices of the trees each result was found in, in the same order as `id_list`.
+    pub result_trees: Vec<Vec<usize>>,
 }
 
 #[repr(u8)]
@@ -29,6 +134,9 @@ pub enum IndexType {
     Manhattan = 2,
     Hamming = 3,
     Dot = 4,
+    /// Served with a user-defined [`Distance`], only loaded via [`AnnoyIndex::load_with_distance`]
+    /// and never converted from its discriminant.
+    Custom = 5,
 }
 
 impl Display for IndexType {
@@ -38,11 +146,58 @@ impl Display for IndexType {
     }
 }
 
+impl TryFrom<u8> for IndexType {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(IndexType::Angular),
+            1 => Ok(IndexType::Euclidean),
+            2 => Ok(IndexType::Manhattan),
+            3 => Ok(IndexType::Hamming),
+            4 => Ok(IndexType::Dot),
+            _ => Err(format!("Invalid index type {value}").into()),
+        }
+    }
+}
+
+impl std::str::FromStr for IndexType {
+    type Err = Box<dyn std::error::Error>;
+
+    /// Parses the lowercase names produced by [`Display`].
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "angular" => Ok(IndexType::Angular),
+            "euclidean" => Ok(IndexType::Euclidean),
+            "manhattan" => Ok(IndexType::Manhattan),
+            "hamming" => Ok(IndexType::Hamming),
+            "dot" => Ok(IndexType::Dot),
+            _ => Err(format!("Invalid index type {s}").into()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum Storage {
     #[cfg(not(target_arch = "wasm32"))]
     Mmap(Box<memmap2::Mmap>),
     Buffer(Vec<u8>),
+    /// Bytes kept alive by their owner, see [`AnnoyIndex::load_from_owner`].
+    External(ExternalBytes),
+}
+
+pub(crate) struct ExternalBytes(pub(crate) Box<dyn AsRef<[u8]> + Send + Sync>);
+
+impl std::fmt::Debug for ExternalBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "ExternalBytes({} bytes)", (*self.0).as_ref().len())
+    }
+}
+
+impl ExternalBytes {
+    pub(crate) fn bytes(&self) -> &[u8] {
+        (*self.0).as_ref()
+    }
 }
 
 impl StorageExtensions for Storage {
@@ -51,6 +206,7 @@ impl StorageExtensions for Storage {
             #[cfg(not(target_arch = "wasm32"))]
             Self::Mmap(mmap) => mmap.read_i32(idx),
             Self::Buffer(buffer) => buffer.read_i32(idx),
+            Self::External(bytes) => bytes.bytes().read_i32(idx),
         }
     }
 
@@ -59,6 +215,7 @@ impl StorageExtensions for Storage {
             #[cfg(not(target_arch = "wasm32"))]
             Self::Mmap(mmap) => mmap.read_f32(idx),
             Self::Buffer(buffer) => buffer.read_f32(idx),
+            Self::External(bytes) => bytes.bytes().read_f32(idx),
         }
     }
 
@@ -67,6 +224,28 @@ impl StorageExtensions for Storage {
             #[cfg(not(target_arch = "wasm32"))]
             Self::Mmap(mmap) => mmap.read_slice(idx, len),
             Self::Buffer(buffer) => buffer.read_slice(idx, len),
+            Self::External(bytes) => bytes.bytes().read_slice(idx, len),
+        }
+    }
+}
+
+impl Storage {
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    pub(crate) fn kind(&self) -> StorageKind {
+        match self {
+            Self::Mmap(_) => StorageKind::Mmap,
+            Self::Buffer(_) => StorageKind::Buffer,
+            Self::External(_) => StorageKind::External,
+        }
+    }
+
+    #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Mmap(mmap) => mmap.len(),
+            Self::Buffer(buffer) => buffer.len(),
+            Self::External(bytes) => bytes.bytes().len(),
         }
     }
 }
@@ -78,6 +257,7 @@ impl Index<usize> for Storage {
             #[cfg(not(target_arch = "wasm32"))]
             Self::Mmap(mmap) => &mmap[index],
             Self::Buffer(buffer) => &buffer[index],
+            Self::External(bytes) => &bytes.bytes()[index],
         }
     }
 }
@@ -87,9 +267,15 @@ pub struct AnnoyIndex {
     pub index_type: IndexType,
     pub node_size: usize,
     pub size: usize,
+    pub vector_encoding: VectorEncoding,
+    pub(crate) distance: Metric,
     pub(crate) max_descendants: i32,
     pub(crate) offset_before_children: usize,
     pub(crate) node_header_size: usize,
     pub(crate) storage: Storage,
     pub(crate) roots: Vec<usize>,
+    pub(crate) tombstones: Tombstones,
+    /// Number of nodes, the file may continue with a footer.
+    pub(crate) n_nodes: usize,
+    pub(crate) metadata: Option<IndexMetadata>,
 }
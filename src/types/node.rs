@@ -1,5 +1,5 @@
-use crate::{IndexType, Storage};
-use std::mem;
+use crate::Storage;
+use std::{mem, slice};
 
 pub(crate) struct Node {
     pub offset: usize,
@@ -10,33 +10,141 @@ impl Node {
     pub fn new_with_id(
         id: usize,
         node_size: usize,
-        index_type: &IndexType,
+        header_kind: NodeHeaderKind,
         storage: &Storage,
     ) -> Node {
         let offset = id * node_size;
-        let header = NodeHeader::new(offset, index_type, storage);
+        let header = NodeHeader::new(offset, header_kind, storage);
         Node { offset, header }
     }
 }
 
+/// Layout of the header at the beginning of each node, shared by metrics of the same family.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum NodeHeaderKind {
+    /// `n_descendants`, `children`
+    Angular,
+    /// `n_descendants`, `bias`, `children`
+    Minkowski,
+    /// `n_descendants`, `children`, `dot_factor`
+    Dot,
+}
+
+impl NodeHeaderKind {
+    pub const fn header_size(&self) -> usize {
+        match self {
+            Self::Angular => NodeHeaderAngular::header_size(),
+            Self::Minkowski => NodeHeaderMinkowski::header_size(),
+            Self::Dot => NodeHeaderDot::header_size(),
+        }
+    }
+
+    /// Offset of the children ids, leaves store their descendant ids from there.
+    pub const fn offset_before_children(&self) -> usize {
+        match self {
+            Self::Angular | Self::Dot => 4,
+            Self::Minkowski => 8,
+        }
+    }
+}
+
 #[repr(C)]
-pub(crate) enum NodeHeader {
+pub enum NodeHeader {
     Angular(NodeHeaderAngular),
     Minkowski(NodeHeaderMinkowski),
     Dot(NodeHeaderDot),
 }
 
 impl NodeHeader {
-    pub fn new(offset: usize, index_type: &IndexType, storage: &Storage) -> NodeHeader {
-        match index_type {
-            IndexType::Angular => {
+    pub(crate) fn new(offset: usize, header_kind: NodeHeaderKind, storage: &Storage) -> NodeHeader {
+        match header_kind {
+            NodeHeaderKind::Angular => {
                 NodeHeader::Angular(unsafe { *NodeHeaderAngular::read(storage, offset) })
             }
-            IndexType::Euclidean | IndexType::Manhattan => {
+            NodeHeaderKind::Minkowski => {
                 NodeHeader::Minkowski(unsafe { *NodeHeaderMinkowski::read(storage, offset) })
             }
-            IndexType::Dot => NodeHeader::Dot(unsafe { *NodeHeaderDot::read(storage, offset) }),
-            _ => unimplemented!("Index type not supported"),
+            NodeHeaderKind::Dot => {
+                NodeHeader::Dot(unsafe { *NodeHeaderDot::read(storage, offset) })
+            }
+        }
+    }
+
+    /// Header of a split node, `bias` is only stored by the minkowski layout.
+    pub fn new_split(
+        header_kind: NodeHeaderKind,
+        n_descendants: i32,
+        children: [i32; 2],
+        bias: f32,
+    ) -> NodeHeader {
+        match header_kind {
+            NodeHeaderKind::Angular => NodeHeader::Angular(NodeHeaderAngular {
+                n_descendants,
+                children,
+            }),
+            NodeHeaderKind::Minkowski => NodeHeader::Minkowski(NodeHeaderMinkowski {
+                n_descendants,
+                bias,
+                children,
+            }),
+            NodeHeaderKind::Dot => NodeHeader::Dot(NodeHeaderDot {
+                n_descendants,
+                children,
+                dot_factor: 0.0,
+            }),
+        }
+    }
+
+    /// Same split with other descendants, keeping `bias` and `dot_factor`.
+    pub(crate) fn with_descendants(&self, n_descendants: i32, children: [i32; 2]) -> NodeHeader {
+        match *self {
+            NodeHeader::Angular(_) => NodeHeader::Angular(NodeHeaderAngular {
+                n_descendants,
+                children,
+            }),
+            NodeHeader::Minkowski(h) => NodeHeader::Minkowski(NodeHeaderMinkowski {
+                n_descendants,
+                children,
+                ..h
+            }),
+            NodeHeader::Dot(h) => NodeHeader::Dot(NodeHeaderDot {
+                n_descendants,
+                children,
+                ..h
+            }),
+        }
+    }
+
+    /// Only stored by the dot layout.
+    pub(crate) fn set_dot_factor(&mut self, dot_factor: f32) {
+        if let NodeHeader::Dot(h) = self {
+            h.dot_factor = dot_factor;
+        }
+    }
+
+    pub fn get_bias(&self) -> f32 {
+        match self {
+            NodeHeader::Minkowski(h) => h.bias,
+            _ => 0.0,
+        }
+    }
+
+    pub fn get_dot_factor(&self) -> f32 {
+        match self {
+            NodeHeader::Dot(h) => h.dot_factor,
+            _ => 0.0,
+        }
+    }
+
+    /// Raw bytes of the header as laid out in the index file.
+    pub fn as_bytes(&self) -> &[u8] {
+        fn bytes_of<T>(h: &T) -> &[u8] {
+            unsafe { slice::from_raw_parts(h as *const T as *const u8, mem::size_of::<T>()) }
+        }
+        match self {
+            NodeHeader::Angular(h) => bytes_of(h),
+            NodeHeader::Minkowski(h) => bytes_of(h),
+            NodeHeader::Dot(h) => bytes_of(h),
         }
     }
 
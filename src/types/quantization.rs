@@ -0,0 +1,408 @@
+use super::node::NodeHeader;
+use super::utils::*;
+use super::*;
+use crate::internals::storage_ext::StorageExtensions;
+use half::{bf16, f16};
+use ordered_float::OrderedFloat;
+use std::{borrow::Cow, error::Error};
+
+/// Element type of the item and split vectors stored in each node.
+#[repr(u8)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen::prelude::wasm_bindgen)]
+pub enum VectorEncoding {
+    /// Plain annoy layout.
+    F32 = 0,
+    F16 = 1,
+    BF16 = 2,
+    /// Scaled int8, each vector is prefixed with its f32 scale.
+    I8 = 3,
+}
+
+impl Display for VectorEncoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let r = format!("{self:?}").to_lowercase();
+        f.write_str(&r)
+    }
+}
+
+impl TryFrom<u8> for VectorEncoding {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(VectorEncoding::F32),
+            1 => Ok(VectorEncoding::F16),
+            2 => Ok(VectorEncoding::BF16),
+            3 => Ok(VectorEncoding::I8),
+            _ => Err(format!("Invalid vector encoding {value}").into()),
+        }
+    }
+}
+
+impl VectorEncoding {
+    pub(crate) const fn prefix_size(&self) -> usize {
+        match self {
+            Self::I8 => FLOAT32_SIZE,
+            _ => 0,
+        }
+    }
+
+    pub(crate) const fn element_size(&self) -> usize {
+        match self {
+            Self::F32 => FLOAT32_SIZE,
+            Self::F16 | Self::BF16 => 2,
+            Self::I8 => 1,
+        }
+    }
+
+    /// Node size in bytes, padded so that every node stays 4-byte aligned.
+    pub(crate) const fn node_size(&self, node_header_size: usize, dimension: usize) -> usize {
+        let size = node_header_size + self.prefix_size() + self.element_size() * dimension;
+        size.div_ceil(INT32_SIZE) * INT32_SIZE
+    }
+
+    pub(crate) fn encode(&self, v: &[f32], dst: &mut Vec<u8>) {
+        match self {
+            Self::F32 => v.iter().for_each(|x| dst.extend(x.to_ne_bytes())),
+            Self::F16 => v
+                .iter()
+                .for_each(|&x| dst.extend(f16::from_f32(x).to_ne_bytes())),
+            Self::BF16 => v
+                .iter()
+                .for_each(|&x| dst.extend(bf16::from_f32(x).to_ne_bytes())),
+            Self::I8 => {
+                let max = v.iter().fold(0.0_f32, |m, x| m.max(x.abs()));
+                let scale = max / i8::MAX as f32;
+                dst.extend(scale.to_ne_bytes());
+                v.iter().for_each(|&x| {
+                    let q = if scale > 0.0 {
+                        (x / scale).round()
+                    } else {
+                        0.0
+                    };
+                    dst.push(q as i8 as u8)
+                });
+            }
+        }
+    }
+}
+
+/// A borrowed node vector in its stored encoding.
+pub(crate) enum NodeVector<'a> {
+    F32(&'a [f32]),
+    F16(&'a [f16]),
+    BF16(&'a [bf16]),
+    I8(f32, &'a [i8]),
+}
+
+impl NodeVector<'_> {
+    pub(crate) fn read(
+        storage: &Storage,
+        offset: usize,
+        dimension: usize,
+        encoding: VectorEncoding,
+    ) -> NodeVector<'_> {
+        match encoding {
+            VectorEncoding::F32 => NodeVector::F32(storage.read_slice(offset, dimension)),
+            VectorEncoding::F16 => NodeVector::F16(storage.read_slice(offset, dimension)),
+            VectorEncoding::BF16 => NodeVector::BF16(storage.read_slice(offset, dimension)),
+            VectorEncoding::I8 => NodeVector::I8(
+                storage.read_f32(offset),
+                storage.read_slice(offset + FLOAT32_SIZE, dimension),
+            ),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            NodeVector::F32(v) => v.len(),
+            NodeVector::F16(v) => v.len(),
+            NodeVector::BF16(v) => v.len(),
+            NodeVector::I8(_, v) => v.len(),
+        }
+    }
+
+    /// Decoded value of the `i`-th element.
+    pub fn get(&self, i: usize) -> f32 {
+        match self {
+            NodeVector::F32(v) => v[i],
+            NodeVector::F16(v) => v[i].to_f32(),
+            NodeVector::BF16(v) => v[i].to_f32(),
+            NodeVector::I8(scale, v) => v[i] as f32 * scale,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    pub fn to_vec(&self) -> Vec<f32> {
+        match self {
+            NodeVector::F32(v) => v.to_vec(),
+            NodeVector::F16(v) => v.iter().map(|x| x.to_f32()).collect(),
+            NodeVector::BF16(v) => v.iter().map(|x| x.to_f32()).collect(),
+            NodeVector::I8(scale, v) => v.iter().map(|&x| x as f32 * scale).collect(),
+        }
+    }
+
+    /// Borrows f32 vectors, decodes the others.
+    pub fn to_f32(&self) -> Cow<'_, [f32]> {
+        match *self {
+            NodeVector::F32(v) => Cow::Borrowed(v),
+            _ => Cow::Owned(self.to_vec()),
+        }
+    }
+
+    pub fn dot_product(&self, q: &[f32]) -> f32 {
+        match *self {
+            NodeVector::F32(v) => dot_product(v, q),
+            NodeVector::F16(v) => dot_product_encoded(q, v, f16::to_f32),
+            NodeVector::BF16(v) => dot_product_encoded(q, v, bf16::to_f32),
+            NodeVector::I8(scale, v) => dot_product_encoded(q, v, |x| x as f32 * scale),
+        }
+    }
+
+    pub fn cosine_distance(&self, q: &[f32]) -> f32 {
+        match *self {
+            NodeVector::F32(v) => cosine_distance(v, q),
+            NodeVector::F16(v) => cosine_distance_encoded(q, v, f16::to_f32),
+            NodeVector::BF16(v) => cosine_distance_encoded(q, v, bf16::to_f32),
+            NodeVector::I8(scale, v) => cosine_distance_encoded(q, v, |x| x as f32 * scale),
+        }
+    }
+
+    pub fn euclidean_distance(&self, q: &[f32]) -> f32 {
+        match *self {
+            NodeVector::F32(v) => euclidean_distance(v, q),
+            NodeVector::F16(v) => euclidean_distance_encoded(q, v, f16::to_f32),
+            NodeVector::BF16(v) => euclidean_distance_encoded(q, v, bf16::to_f32),
+            NodeVector::I8(scale, v) => euclidean_distance_encoded(q, v, |x| x as f32 * scale),
+        }
+    }
+
+    pub fn manhattan_distance(&self, q: &[f32]) -> f32 {
+        match *self {
+            NodeVector::F32(v) => manhattan_distance(v, q),
+            NodeVector::F16(v) => manhattan_distance_encoded(q, v, f16::to_f32),
+            NodeVector::BF16(v) => manhattan_distance_encoded(q, v, bf16::to_f32),
+            NodeVector::I8(scale, v) => manhattan_distance_encoded(q, v, |x| x as f32 * scale),
+        }
+    }
+}
+
+impl AnnoyIndex {
+    /// Converts an f32 index into `encoding`, returning the bytes of the new index file.
+    /// Leaves that no longer fit into the smaller nodes are split further.
+    ///
+    /// The file ends with an [`IndexMetadata`] footer recording the encoding, so that loading it
+    /// with another one fails.
+    pub fn quantize(
+        &self,
+        encoding: VectorEncoding,
+    ) -> std::result::Result<Vec<u8>, Box<dyn Error>> {
+        if self.vector_encoding != VectorEncoding::F32 {
+            return Err(format!(
+                "Only f32 indexes can be quantized, got {}",
+                self.vector_encoding
+            )
+            .into());
+        }
+        let n_nodes = self.n_nodes;
+        let n_regular_nodes = n_nodes - self.roots.len();
+        let node_size = encoding.node_size(self.node_header_size, self.dimension);
+        let mut quantizer = Quantizer {
+            index: self,
+            encoding,
+            node_size,
+            max_descendants: (node_size - self.offset_before_children) / INT32_SIZE,
+            first_extra_id: n_regular_nodes,
+            extra: Vec::new(),
+        };
+        let mut regular = Vec::with_capacity(n_regular_nodes * node_size);
+        for id in 0..n_regular_nodes {
+            quantizer.write_node(&mut regular, id);
+        }
+        // Copies of the roots must stay at the end of the file, after nodes created by splitting
+        let mut root_copies = Vec::with_capacity(self.roots.len() * node_size);
+        for id in n_regular_nodes..n_nodes {
+            quantizer.write_node(&mut root_copies, id);
+        }
+        regular.extend(quantizer.extra);
+        regular.extend(root_copies);
+        let mut metadata = IndexMetadata::new(
+            self.index_type.clone(),
+            encoding,
+            self.dimension,
+            self.size,
+            self.n_trees(),
+            &regular,
+        );
+        if let Some(source) = &self.metadata {
+            metadata.build_params = source.build_params.clone();
+            metadata.user = source.user.clone();
+        }
+        regular.extend(metadata.to_bytes());
+        Ok(regular)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_quantized(
+        &self,
+        index_file_path: &str,
+        encoding: VectorEncoding,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        let buffer = self.quantize(encoding)?;
+        std::fs::write(index_file_path, buffer)?;
+        Ok(())
+    }
+
+    /// Searches `n_candidates` items in this (typically quantized) index, then re-ranks them
+    /// with full precision vectors read from `full_precision_index`, which must hold the same
+    /// items with the same dimension and index type.
+    pub fn get_nearest_reranked(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        full_precision_index: &AnnoyIndex,
+        n_candidates: usize,
+    ) -> std::result::Result<AnnoyIndexSearchResult, Box<dyn Error>> {
+        if full_precision_index.dimension != self.dimension
+            || full_precision_index.size != self.size
+            || full_precision_index.index_type != self.index_type
+        {
+            return Err(format!(
+                "Full precision index ({}d, {} items, {}) doesn't match this one ({}d, {} items, {})",
+                full_precision_index.dimension,
+                full_precision_index.size,
+                full_precision_index.index_type,
+                self.dimension,
+                self.size,
+                self.index_type
+            )
+            .into());
+        }
+        let candidates =
+            self.get_nearest(query_vector, n_candidates.max(n_results), search_k, false);
+        let mut reranked: Vec<(u64, f32)> = candidates
+            .id_list
+            .iter()
+            .map(|&id| {
+                let v = full_precision_index
+                    .get_node_vector(id as usize * full_precision_index.node_size);
+                (
+                    id,
+                    full_precision_index.get_distance_no_norm(&v, query_vector),
+                )
+            })
+            .collect();
+        reranked.sort_by_key(|&(_, d)| OrderedFloat(d));
+        reranked.truncate(n_results);
+        Ok(AnnoyIndexSearchResult {
+            count: reranked.len(),
+            is_distance_included: should_include_distance,
+            id_list: reranked.iter().map(|&(id, _)| id).collect(),
+            distance_list: if should_include_distance {
+                reranked
+                    .iter()
+                    .map(|&(_, d)| full_precision_index.normalized_distance(d))
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            is_truncated: candidates.is_truncated,
+        })
+    }
+}
+
+struct Quantizer<'a> {
+    index: &'a AnnoyIndex,
+    encoding: VectorEncoding,
+    node_size: usize,
+    max_descendants: usize,
+    first_extra_id: usize,
+    extra: Vec<u8>,
+}
+
+impl Quantizer<'_> {
+    fn write_node(&mut self, dst: &mut Vec<u8>, id: usize) {
+        let index = self.index;
+        let node = index.get_node_from_id(id);
+        let n_descendants = node.header.get_n_descendant();
+        if id < index.size || n_descendants > index.max_descendants {
+            // Items and split nodes keep their header, only the vector is re-encoded
+            let header: &[u8] = index
+                .storage
+                .read_slice(node.offset, index.node_header_size);
+            let v = index.get_node_vector(node.offset).to_vec();
+            self.write_vector_node(dst, header, &v);
+        } else if n_descendants as usize <= self.max_descendants {
+            let ids = index.get_descendant_id_slice(node.offset, n_descendants as usize);
+            self.write_leaf_node(dst, ids);
+        } else {
+            let ids = index.get_descendant_id_slice(node.offset, n_descendants as usize);
+            self.write_split_node(dst, ids);
+        }
+    }
+
+    fn write_vector_node(&self, dst: &mut Vec<u8>, header: &[u8], v: &[f32]) {
+        let start = dst.len();
+        dst.extend_from_slice(header);
+        self.encoding.encode(v, dst);
+        dst.resize(start + self.node_size, 0);
+    }
+
+    fn write_leaf_node(&self, dst: &mut Vec<u8>, ids: &[i32]) {
+        let start = dst.len();
+        dst.extend((ids.len() as i32).to_ne_bytes());
+        dst.resize(start + self.index.offset_before_children, 0);
+        ids.iter().for_each(|id| dst.extend(id.to_ne_bytes()));
+        dst.resize(start + self.node_size, 0);
+    }
+
+    /// Splits `ids` by the hyperplane between two of its items, the same way a split node would.
+    fn write_split_node(&mut self, dst: &mut Vec<u8>, ids: &[i32]) {
+        let index = self.index;
+        let vectors: Vec<Vec<f32>> = ids
+            .iter()
+            .map(|&id| index.get_item_vector(id as u64))
+            .collect();
+        let (normal, bias) = index.distance.create_split(&vectors);
+        let header_kind = index.distance.header_kind();
+        let split_header = NodeHeader::new_split(header_kind, ids.len() as i32, [0, 0], bias);
+        let split_vector = NodeVector::F32(&normal);
+        let (mut side_0, mut side_1) = (Vec::new(), Vec::new());
+        for (&id, v) in ids.iter().zip(vectors.iter()) {
+            if index.distance.margin(&split_header, &split_vector, v) > 0.0 {
+                side_1.push(id);
+            } else {
+                side_0.push(id);
+            }
+        }
+        if side_0.is_empty() || side_1.is_empty() {
+            let (a, b) = ids.split_at(ids.len() / 2);
+            side_0 = a.to_vec();
+            side_1 = b.to_vec();
+        }
+        let children = [self.add_subtree(&side_0), self.add_subtree(&side_1)];
+        let header = NodeHeader::new_split(header_kind, ids.len() as i32, children, bias);
+        self.write_vector_node(dst, header.as_bytes(), &normal);
+    }
+
+    fn add_subtree(&mut self, ids: &[i32]) -> i32 {
+        if ids.len() == 1 {
+            return ids[0];
+        }
+        let mut node = Vec::with_capacity(self.node_size);
+        if ids.len() <= self.max_descendants {
+            self.write_leaf_node(&mut node, ids);
+        } else {
+            self.write_split_node(&mut node, ids);
+        }
+        let id = self.first_extra_id + self.extra.len() / self.node_size;
+        self.extra.extend(node);
+        id as i32
+    }
+}
@@ -1,10 +1,32 @@
 use super::*;
 use crate::internals::priority_queue::*;
 use ordered_float::OrderedFloat;
-use std::{cmp::Reverse, collections::BinaryHeap};
+use std::{
+    cell::Cell,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+};
 
 pub trait AnnoyIndexSearchApi {
     fn get_item_vector(&self, item_index: u64) -> Vec<f32>;
+    /// Metric of the index, used by the default [`AnnoyIndexSearchApi::get_distance`].
+    ///
+    /// By default, there is none.
+    fn metric(&self) -> Option<&dyn Distance> {
+        None
+    }
+    /// Distance between two items, as reported in search results.
+    ///
+    /// By default, computed from both item vectors with [`AnnoyIndexSearchApi::metric`],
+    /// implementors without a metric have to override it.
+    fn get_distance(&self, i: u64, j: u64) -> f32 {
+        let metric = self
+            .metric()
+            .expect("get_distance needs either a metric or an override");
+        metric.normalized_distance(
+            metric.distance(&self.get_item_vector(i), &self.get_item_vector(j)),
+        )
+    }
     fn get_nearest(
         &self,
         query_vector: &[f32],
@@ -19,13 +41,94 @@ pub trait AnnoyIndexSearchApi {
         search_k: i32,
         should_include_distance: bool,
     ) -> AnnoyIndexSearchResult;
+    /// Same as [`AnnoyIndexSearchApi::get_nearest`], but ids in `excluded_ids` are never returned,
+    /// other candidates are fetched in their place so that up to `n_results` items are still returned.
+    ///
+    /// By default, as many more results as there are excluded ids are searched and then filtered.
+    fn get_nearest_excluding(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        excluded_ids: &HashSet<u64>,
+    ) -> AnnoyIndexSearchResult {
+        let mut result = self.get_nearest(
+            query_vector,
+            n_results + excluded_ids.len(),
+            search_k,
+            should_include_distance,
+        );
+        result.retain_first(n_results, |id| !excluded_ids.contains(&id));
+        result
+    }
+    /// Same as [`AnnoyIndexSearchApi::get_nearest_to_item`], with `exclude_self` dropping `item_index`
+    /// itself from the results and `excluded_ids` dropping e.g. already seen items.
+    fn get_nearest_to_item_excluding(
+        &self,
+        item_index: u64,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        exclude_self: bool,
+        excluded_ids: &HashSet<u64>,
+    ) -> AnnoyIndexSearchResult {
+        let n_excluded = excluded_ids.len() + exclude_self as usize;
+        let mut result = self.get_nearest_to_item(
+            item_index,
+            n_results + n_excluded,
+            search_k,
+            should_include_distance,
+        );
+        result.retain_first(n_results, |id| {
+            !(excluded_ids.contains(&id) || exclude_self && id == item_index)
+        });
+        result
+    }
+    /// Same as [`AnnoyIndexSearchApi::get_nearest`], additionally returning [`SearchStats`]
+    /// that describe how the traversal went.
+    ///
+    /// By default, the stats are empty.
+    fn get_nearest_with_stats(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> (AnnoyIndexSearchResult, SearchStats) {
+        let result = self.get_nearest(query_vector, n_results, search_k, should_include_distance);
+        (result, SearchStats::default())
+    }
+    /// Same as [`AnnoyIndexSearchApi::get_nearest`], but traversal stops as soon as `budget` is exhausted,
+    /// the best results among candidates collected so far are returned with `is_truncated` set.
+    ///
+    /// By default, the budget is ignored and searches are complete.
+    fn get_nearest_with_budget(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        _budget: SearchBudget,
+    ) -> AnnoyIndexSearchResult {
+        self.get_nearest(query_vector, n_results, search_k, should_include_distance)
+    }
 }
 
 impl AnnoyIndexSearchApi for AnnoyIndex {
     fn get_item_vector(&self, item_index: u64) -> Vec<f32> {
         let node_offset = item_index as usize * self.node_size;
-        let slice = self.get_node_slice_with_offset(node_offset);
-        slice.to_vec()
+        self.get_node_vector(node_offset).to_vec()
+    }
+
+    fn metric(&self) -> Option<&dyn Distance> {
+        Some(self.distance.as_distance())
+    }
+
+    fn get_distance(&self, i: u64, j: u64) -> f32 {
+        let v = self.get_node_vector(i as usize * self.node_size);
+        let query_vector = self.get_item_vector(j);
+        self.normalized_distance(self.get_distance_no_norm(&v, &query_vector))
     }
 
     fn get_nearest(
@@ -35,6 +138,184 @@ impl AnnoyIndexSearchApi for AnnoyIndex {
         search_k: i32,
         should_include_distance: bool,
     ) -> AnnoyIndexSearchResult {
+        self.get_nearest_inner(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            |_| false,
+        )
+    }
+
+    fn get_nearest_to_item(
+        &self,
+        item_index: u64,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> AnnoyIndexSearchResult {
+        let item_vector = self.get_item_vector(item_index);
+        self.get_nearest(
+            item_vector.as_slice(),
+            n_results,
+            search_k,
+            should_include_distance,
+        )
+    }
+
+    fn get_nearest_excluding(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        excluded_ids: &HashSet<u64>,
+    ) -> AnnoyIndexSearchResult {
+        self.get_nearest_inner(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            |id| excluded_ids.contains(&id),
+        )
+    }
+
+    fn get_nearest_to_item_excluding(
+        &self,
+        item_index: u64,
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        exclude_self: bool,
+        excluded_ids: &HashSet<u64>,
+    ) -> AnnoyIndexSearchResult {
+        let item_vector = self.get_item_vector(item_index);
+        self.get_nearest_inner(
+            item_vector.as_slice(),
+            n_results,
+            search_k,
+            should_include_distance,
+            |id| (exclude_self && id == item_index) || excluded_ids.contains(&id),
+        )
+    }
+
+    fn get_nearest_with_stats(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> (AnnoyIndexSearchResult, SearchStats) {
+        self.search(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            |_| false,
+            None,
+        )
+    }
+
+    fn get_nearest_with_budget(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        budget: SearchBudget,
+    ) -> AnnoyIndexSearchResult {
+        let (result, _stats) = self.search(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            |_| false,
+            Some(budget),
+        );
+        result
+    }
+}
+
+impl AnnoyIndex {
+    fn get_nearest_inner(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        is_excluded: impl Fn(u64) -> bool,
+    ) -> AnnoyIndexSearchResult {
+        let (result, _stats) = self.search(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            is_excluded,
+            None,
+        );
+        result
+    }
+
+    fn search(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        is_excluded: impl Fn(u64) -> bool,
+        budget: Option<SearchBudget>,
+    ) -> (AnnoyIndexSearchResult, SearchStats) {
+        let (mut result, stats) = self.search_unnormalized(
+            query_vector,
+            n_results,
+            search_k,
+            should_include_distance,
+            is_excluded,
+            budget,
+        );
+        for d in result.distance_list.iter_mut() {
+            *d = self.normalized_distance(*d);
+        }
+        (result, stats)
+    }
+
+    /// Searches like [`AnnoyIndexSearchApi::get_nearest_with_stats`], with distances before normalization.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "trace",
+            skip_all,
+            fields(
+                n_results = n_results,
+                search_k = search_k,
+                candidate_count = tracing::field::Empty,
+                is_truncated = tracing::field::Empty,
+            )
+        )
+    )]
+    pub(crate) fn search_unnormalized(
+        &self,
+        query_vector: &[f32],
+        n_results: usize,
+        search_k: i32,
+        should_include_distance: bool,
+        is_excluded: impl Fn(u64) -> bool,
+        budget: Option<SearchBudget>,
+    ) -> (AnnoyIndexSearchResult, SearchStats) {
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        let started = metrics_recorder().map(|_| std::time::Instant::now());
+        let mut stats = SearchStats::default();
+        let mut is_truncated = false;
+        // Deleted items are skipped like excluded ones, so traversal goes on until enough live
+        // candidates are collected.
+        let deleted_skipped = Cell::new(0);
+        let is_excluded = |id: u64| {
+            if self.tombstones.contains(id) {
+                deleted_skipped.set(deleted_skipped.get() + 1);
+                return true;
+            }
+            is_excluded(id)
+        };
         let result_capacity = n_results.min(self.size).max(1);
         let search_k_fixed = if search_k > 0 {
             search_k as usize
@@ -43,51 +324,78 @@ impl AnnoyIndexSearchApi for AnnoyIndex {
         };
 
         let mut pq = BinaryHeap::with_capacity(result_capacity);
-        for &id in self.roots.iter() {
+        for (tree, &id) in self.roots.iter().enumerate() {
             pq.push(BinaryHeapItem {
-                item: id as i32,
+                item: (id as i32, tree as u32),
                 ord: OrderedFloat(f32::MAX),
             });
         }
 
-        let mut nearest_neighbors = Vec::with_capacity(search_k_fixed);
+        // (item id, index of the tree it was found in)
+        let mut nearest_neighbors: Vec<(i32, u32)> = Vec::with_capacity(search_k_fixed);
         while !pq.is_empty() && nearest_neighbors.len() < search_k_fixed {
+            if let Some(budget) = &budget {
+                if budget.is_exhausted(stats.nodes_visited, nearest_neighbors.len()) {
+                    is_truncated = true;
+                    break;
+                }
+            }
             if let Some(BinaryHeapItem {
-                item: top_node_id_i32,
+                item: (top_node_id_i32, tree),
                 ord: top_node_margin,
             }) = pq.pop()
             {
+                stats.nodes_visited += 1;
                 let top_node_id = top_node_id_i32 as usize;
                 let top_node = self.get_node_from_id(top_node_id);
                 let top_node_header = top_node.header;
                 let top_node_offset = top_node.offset;
                 let n_descendants = top_node_header.get_n_descendant();
                 if n_descendants == 1 && top_node_id < self.size {
-                    nearest_neighbors.push(top_node_id_i32);
+                    if !is_excluded(top_node_id as u64) {
+                        nearest_neighbors.push((top_node_id_i32, tree));
+                    }
                 } else if n_descendants <= self.max_descendants {
                     let children_id_slice =
                         self.get_descendant_id_slice(top_node_offset, n_descendants as usize);
-                    nearest_neighbors.extend_from_slice(children_id_slice);
+                    nearest_neighbors.extend(
+                        children_id_slice
+                            .iter()
+                            .filter(|&&id| !is_excluded(id as u64))
+                            .map(|&id| (id, tree)),
+                    );
                 } else {
-                    let v = self.get_node_slice_with_offset(top_node_offset);
-                    let margin = self.get_margin(v, query_vector, top_node_offset);
+                    let v = self.get_node_vector(top_node_offset);
+                    let margin = self.get_margin(&top_node_header, &v, query_vector);
+                    stats.margin_evaluations += 1;
                     let [child_0, child_1] = top_node_header.get_children_id_slice();
                     // NOTE: Hamming has different logic to calculate margin
                     pq.push(BinaryHeapItem {
-                        item: child_1,
+                        item: (child_1, tree),
                         ord: OrderedFloat(top_node_margin.0.min(margin)),
                     });
                     pq.push(BinaryHeapItem {
-                        item: child_0,
+                        item: (child_0, tree),
                         ord: OrderedFloat(top_node_margin.0.min(-margin)),
                     });
                 }
             }
         }
+        // A leaf adds all its descendants at once, which may overshoot the allowed candidates
+        if let Some(SearchBudget::MaxCandidates(max)) = budget {
+            if nearest_neighbors.len() > max {
+                nearest_neighbors.truncate(max);
+                is_truncated = true;
+            }
+        }
+        stats.candidate_count = nearest_neighbors.len();
+        stats.deleted_skipped = deleted_skipped.get();
         nearest_neighbors.sort_unstable();
+        // Items of the heap are positions of the first occurrence of each id in nearest_neighbors,
+        // so that trees an id was found in can be looked up afterwards without extra allocations.
         let mut sorted_nns = BinaryHeap::with_capacity(nearest_neighbors.len());
         let mut nn_id_last = -1;
-        for nn_id in nearest_neighbors {
+        for (pos, &(nn_id, _)) in nearest_neighbors.iter().enumerate() {
             if nn_id == nn_id_last {
                 continue;
             }
@@ -98,10 +406,11 @@ impl AnnoyIndexSearchApi for AnnoyIndex {
                 continue;
             }
 
-            let s = self.get_node_slice_with_offset(nn_id as usize * self.node_size);
+            let s = self.get_node_vector(nn_id as usize * self.node_size);
+            stats.distance_computations += 1;
             sorted_nns.push(Reverse(BinaryHeapItem {
-                item: nn_id,
-                ord: OrderedFloat(self.get_distance_no_norm(s, query_vector)),
+                item: pos,
+                ord: OrderedFloat(self.get_distance_no_norm(&s, query_vector)),
             }));
         }
 
@@ -112,39 +421,53 @@ impl AnnoyIndexSearchApi for AnnoyIndex {
         } else {
             0
         });
+        stats.result_trees = Vec::with_capacity(final_result_capcity);
         for _i in 0..final_result_capcity {
             if let Some(Reverse(BinaryHeapItem {
-                item,
+                item: pos,
                 ord: OrderedFloat(ord),
-            })) = &sorted_nns.pop()
+            })) = sorted_nns.pop()
             {
-                id_list.push(*item as u64);
+                let id = nearest_neighbors[pos].0;
+                id_list.push(id as u64);
                 if should_include_distance {
-                    distance_list.push(self.normalized_distance(*ord));
+                    distance_list.push(ord);
                 }
+                let mut trees: Vec<usize> = nearest_neighbors[pos..]
+                    .iter()
+                    .take_while(|(nn_id, _)| *nn_id == id)
+                    .map(|&(_, tree)| tree as usize)
+                    .collect();
+                trees.dedup();
+                stats.result_trees.push(trees);
             }
         }
-        AnnoyIndexSearchResult {
-            count: final_result_capcity,
-            is_distance_included: should_include_distance,
-            id_list,
-            distance_list,
-        }
-    }
 
-    fn get_nearest_to_item(
-        &self,
-        item_index: u64,
-        n_results: usize,
-        search_k: i32,
-        should_include_distance: bool,
-    ) -> AnnoyIndexSearchResult {
-        let item_vector = self.get_item_vector(item_index);
-        self.get_nearest(
-            item_vector.as_slice(),
-            n_results,
-            search_k,
-            should_include_distance,
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("candidate_count", stats.candidate_count)
+            .record("is_truncated", is_truncated);
+        #[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+        if let (Some(recorder), Some(started)) = (metrics_recorder(), started) {
+            recorder.record_search(&SearchMetrics {
+                duration: started.elapsed(),
+                n_results,
+                search_k: search_k_fixed,
+                nodes_visited: stats.nodes_visited,
+                candidate_count: stats.candidate_count,
+                distance_computations: stats.distance_computations,
+                is_truncated,
+            });
+        }
+        (
+            AnnoyIndexSearchResult {
+                count: final_result_capcity,
+                is_distance_included: should_include_distance,
+                id_list,
+                distance_list,
+                is_truncated,
+            },
+            stats,
         )
     }
 }
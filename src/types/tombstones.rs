@@ -0,0 +1,174 @@
+use super::*;
+use std::error::Error;
+
+const TOMBSTONES_MAGIC: &[u8; 8] = b"ANNTOMB1";
+
+/// Bitmap of deleted item ids, see [`AnnoyIndex::delete_item`].
+///
+/// Serialized as a sidecar file next to the index: a magic, the index size and the bitmap words,
+/// all little endian.
+#[derive(Debug, Clone, Default)]
+pub struct Tombstones {
+    words: Vec<u64>,
+    count: usize,
+}
+
+impl Tombstones {
+    pub fn contains(&self, id: u64) -> bool {
+        let (word, bit) = Self::position(id);
+        self.words.get(word).is_some_and(|w| w & bit != 0)
+    }
+
+    /// Returns whether `id` was newly inserted.
+    pub fn insert(&mut self, id: u64) -> bool {
+        let (word, bit) = Self::position(id);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let is_new = self.words[word] & bit == 0;
+        if is_new {
+            self.words[word] |= bit;
+            self.count += 1;
+        }
+        is_new
+    }
+
+    /// Returns whether `id` was present.
+    pub fn remove(&mut self, id: u64) -> bool {
+        let (word, bit) = Self::position(id);
+        let was_present = self.contains(id);
+        if was_present {
+            self.words[word] &= !bit;
+            self.count -= 1;
+        }
+        was_present
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.count = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &w)| {
+            (0..64)
+                .filter(move |bit| w & (1 << bit) != 0)
+                .map(move |bit| (i * 64 + bit) as u64)
+        })
+    }
+
+    pub fn to_bytes(&self, index_size: usize) -> Vec<u8> {
+        let n_words = index_size.div_ceil(64);
+        let mut bytes = Vec::with_capacity(16 + n_words * 8);
+        bytes.extend_from_slice(TOMBSTONES_MAGIC);
+        bytes.extend_from_slice(&(index_size as u64).to_le_bytes());
+        for i in 0..n_words {
+            let w = self.words.get(i).copied().unwrap_or_default();
+            bytes.extend_from_slice(&w.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Parses bytes written by [`Tombstones::to_bytes`] for an index of `index_size` items.
+    pub fn from_bytes(
+        bytes: &[u8],
+        index_size: usize,
+    ) -> std::result::Result<Tombstones, Box<dyn Error>> {
+        if bytes.len() < 16 || &bytes[..8] != TOMBSTONES_MAGIC {
+            return Err("Not a tombstones file".into());
+        }
+        let size = u64::from_le_bytes(bytes[8..16].try_into()?) as usize;
+        if size != index_size {
+            return Err(
+                format!("Tombstones of an index of {size} items, {index_size} expected").into(),
+            );
+        }
+        let words = &bytes[16..];
+        if words.len() != index_size.div_ceil(64) * 8 {
+            return Err("Truncated tombstones file".into());
+        }
+        let words: Vec<u64> = words
+            .chunks_exact(8)
+            .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        let count = words.iter().map(|w| w.count_ones() as usize).sum();
+        Ok(Tombstones { words, count })
+    }
+
+    fn position(id: u64) -> (usize, u64) {
+        ((id / 64) as usize, 1 << (id % 64))
+    }
+}
+
+impl AnnoyIndex {
+    /// Marks an item as deleted, it's never returned by searches from now on. Searches keep
+    /// traversing until enough live candidates are collected, so a growing deleted fraction
+    /// makes them slower, see [`AnnoyIndex::deleted_fraction`].
+    ///
+    /// Returns whether the item was live.
+    pub fn delete_item(&mut self, item_index: u64) -> std::result::Result<bool, Box<dyn Error>> {
+        if item_index as usize >= self.size {
+            return Err(format!("Item {item_index} out of range").into());
+        }
+        Ok(self.tombstones.insert(item_index))
+    }
+
+    /// Reverts [`AnnoyIndex::delete_item`], returns whether the item was deleted.
+    pub fn undelete_item(&mut self, item_index: u64) -> bool {
+        self.tombstones.remove(item_index)
+    }
+
+    pub fn is_deleted(&self, item_index: u64) -> bool {
+        self.tombstones.contains(item_index)
+    }
+
+    pub fn tombstones(&self) -> &Tombstones {
+        &self.tombstones
+    }
+
+    /// Replaces the deleted items, e.g. with tombstones persisted by another process.
+    pub fn set_tombstones(
+        &mut self,
+        tombstones: Tombstones,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        if let Some(id) = tombstones.iter().find(|&id| id as usize >= self.size) {
+            return Err(format!("Item {id} out of range").into());
+        }
+        self.tombstones = tombstones;
+        Ok(())
+    }
+
+    pub fn deleted_count(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    /// Fraction of items that are deleted, a rebuild restores search speed once it grows large.
+    pub fn deleted_fraction(&self) -> f32 {
+        if self.size == 0 {
+            0.0
+        } else {
+            self.tombstones.len() as f32 / self.size as f32
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_tombstones(&self, path: &str) -> std::result::Result<(), Box<dyn Error>> {
+        std::fs::write(path, self.tombstones.to_bytes(self.size))?;
+        Ok(())
+    }
+
+    /// Replaces the deleted items with those saved by [`AnnoyIndex::save_tombstones`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_tombstones(&mut self, path: &str) -> std::result::Result<(), Box<dyn Error>> {
+        let bytes = std::fs::read(path)?;
+        self.set_tombstones(Tombstones::from_bytes(&bytes, self.size)?)
+    }
+}
@@ -15,10 +15,6 @@ type SimdType = Simd<f32, SIMD_LANES>;
 pub const INT32_SIZE: usize = mem::size_of::<i32>();
 pub const FLOAT32_SIZE: usize = mem::size_of::<f32>();
 
-pub fn minkowski_margin(u: &[f32], v: &[f32], bias: f32) -> f32 {
-    bias + dot_product(u, v)
-}
-
 /*
 template<typename S, typename T, typename Distance, typename Random, class ThreadedBuildPolicy>
   class AnnoyIndex : public AnnoyIndexInterface<S, T,
@@ -177,6 +173,45 @@ pub fn manhattan_distance_simd(u: &[f32], v: &[f32]) -> f32 {
     sum.reduce_sum()
 }
 
+// Kernels below take the node vector in its stored (possibly quantized) element type,
+// `decode` converts each element back to f32 so that no intermediate buffer is needed.
+
+pub fn dot_product_encoded<T: Copy>(u: &[f32], v: &[T], decode: impl Fn(T) -> f32) -> f32 {
+    u.iter().zip(v.iter()).map(|(&x, &y)| x * decode(y)).sum()
+}
+
+pub fn cosine_distance_encoded<T: Copy>(u: &[f32], v: &[T], decode: impl Fn(T) -> f32) -> f32 {
+    let mut pp: f32 = 0.0;
+    let mut qq: f32 = 0.0;
+    let mut pq: f32 = 0.0;
+    for (&_u, &_v) in u.iter().zip(v.iter()) {
+        let _v = decode(_v);
+        pp += _u * _u;
+        qq += _v * _v;
+        pq += _u * _v;
+    }
+    let ppqq = pp * qq;
+    if ppqq.is_sign_positive() {
+        2.0 - 2.0 * pq / ppqq.sqrt()
+    } else {
+        2.0
+    }
+}
+
+pub fn euclidean_distance_encoded<T: Copy>(u: &[f32], v: &[T], decode: impl Fn(T) -> f32) -> f32 {
+    u.iter()
+        .zip(v.iter())
+        .map(|(&x, &y)| (x - decode(y)).powi(2))
+        .sum()
+}
+
+pub fn manhattan_distance_encoded<T: Copy>(u: &[f32], v: &[T], decode: impl Fn(T) -> f32) -> f32 {
+    u.iter()
+        .zip(v.iter())
+        .map(|(&x, &y)| (x - decode(y)).abs())
+        .sum()
+}
+
 pub(crate) fn get_nth_descendant_id(
     storage: &impl StorageExtensions,
     node_offset: usize,
@@ -391,6 +426,37 @@ mod tests {
         assert_eq!(r, 3.5382423400878906);
     }
 
+    #[test]
+    fn test_encoded_distance_parity() {
+        let u = [0.171_244_26, -0.205_300_45, -0.053_370_67, 0.450_461_36];
+        let v = [-0.171_119_3, -0.056_770_597, -0.645_999_2, 0.793_953_7];
+        let identity = |x: f32| x;
+        assert_eq!(
+            dot_product_encoded(&u, &v, identity),
+            dot_product_no_simd(&u, &v)
+        );
+        assert_eq!(
+            cosine_distance_encoded(&u, &v, identity),
+            cosine_distance_no_simd(&u, &v)
+        );
+        assert_eq!(
+            euclidean_distance_encoded(&u, &v, identity),
+            euclidean_distance_no_simd(&u, &v)
+        );
+        assert_eq!(
+            manhattan_distance_encoded(&u, &v, identity),
+            manhattan_distance_no_simd(&u, &v)
+        );
+
+        let scale = 0.5;
+        let v_i8: [i8; 4] = [1, -2, 3, -4];
+        let v_f32: Vec<f32> = v_i8.iter().map(|&x| x as f32 * scale).collect();
+        assert_eq!(
+            euclidean_distance_encoded(&u, &v_i8, |x| x as f32 * scale),
+            euclidean_distance_no_simd(&u, &v_f32)
+        );
+    }
+
     #[test]
     #[cfg(nightly)]
     fn test_cosine_distance_simd_parity() {
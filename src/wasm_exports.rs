@@ -1,17 +1,79 @@
 use crate::*;
-use js_sys::{Array, Error, Uint8Array};
-use serde::*;
+use js_sys::{Error, Function, Promise, Reflect, Uint8Array};
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
 
 #[wasm_bindgen]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct SearchResultJs {
-    pub id: u64,
-    pub distance: Option<f32>,
+    ids: Vec<u32>,
+    distances: Option<Vec<f32>>,
 }
 
-impl Drop for SearchResultJs {
-    fn drop(&mut self) {}
+#[wasm_bindgen]
+impl SearchResultJs {
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// `Uint32Array` of item ids, nearest first.
+    #[wasm_bindgen(getter)]
+    pub fn ids(&self) -> Vec<u32> {
+        self.ids.clone()
+    }
+
+    /// `Float32Array` of distances, `undefined` unless requested.
+    #[wasm_bindgen(getter)]
+    pub fn distances(&self) -> Option<Vec<f32>> {
+        self.distances.clone()
+    }
+}
+
+impl From<AnnoyIndexSearchResult> for SearchResultJs {
+    fn from(result: AnnoyIndexSearchResult) -> Self {
+        SearchResultJs {
+            ids: result.id_list.iter().map(|&id| id as u32).collect(),
+            distances: if result.is_distance_included {
+                Some(result.distance_list)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Results of [`AnnoyIndexJs::get_nearest_batch`], query `i` owns
+/// `ids[i * n_results..i * n_results + counts[i]]`.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct BatchSearchResultJs {
+    pub n_results: usize,
+    counts: Vec<u32>,
+    ids: Vec<u32>,
+    distances: Option<Vec<f32>>,
+}
+
+#[wasm_bindgen]
+impl BatchSearchResultJs {
+    /// `Uint32Array` of the number of results of each query.
+    #[wasm_bindgen(getter)]
+    pub fn counts(&self) -> Vec<u32> {
+        self.counts.clone()
+    }
+
+    /// `Uint32Array` of `counts.length * n_results` ids, unused slots are `0xffffffff`.
+    #[wasm_bindgen(getter)]
+    pub fn ids(&self) -> Vec<u32> {
+        self.ids.clone()
+    }
+
+    /// `Float32Array` laid out as `ids`, unused slots are `NaN`.
+    #[wasm_bindgen(getter)]
+    pub fn distances(&self) -> Option<Vec<f32>> {
+        self.distances.clone()
+    }
 }
 
 #[wasm_bindgen]
@@ -37,50 +99,32 @@ impl AnnoyIndexJs {
         }
     }
 
-    pub fn get_item_vector(&self, item_index: u32) -> Result<Array, Error> {
+    /// Returns a `Float32Array`.
+    pub fn get_item_vector(&self, item_index: u32) -> Result<Vec<f32>, Error> {
         let index = unsafe { &*self.index_ptr };
         if (item_index as usize) >= index.size {
             return Err(Error::new("item_index out of range"));
         }
-        let item_vec = index.get_item_vector(item_index as u64);
-        let array = Array::new();
-        for v in item_vec {
-            array.push(&JsValue::from_f64(v as f64));
-        }
-        Ok(array)
+        Ok(index.get_item_vector(item_index as u64))
     }
 
+    /// `query_vector` is a `Float32Array`, or a plain array of numbers.
     pub fn get_nearest(
         &self,
-        query_vector: Array,
+        query_vector: &[f32],
         n_results: u32,
         search_k: i32,
         should_include_distance: bool,
-    ) -> Result<Array, Error> {
+    ) -> Result<SearchResultJs, Error> {
         let index = unsafe { &*self.index_ptr };
-        if query_vector.length() as usize != index.dimension {
-            return Err(Error::new(&format!(
-                "Wrong input dimension, {} expected, {} provided.",
-                index.dimension,
-                query_vector.length()
-            )));
-        }
-        let mut vec = Vec::with_capacity(index.dimension);
-        for i in 0..(index.dimension as i32) {
-            let v = query_vector.at(i);
-            if let Some(v) = v.as_f64() {
-                vec.push(v as f32);
-            } else {
-                return Err(Error::new("Input array should be of number type."));
-            }
-        }
+        check_dimension(index, query_vector.len())?;
         let result = index.get_nearest(
-            vec.as_slice(),
+            query_vector,
             n_results as usize,
             search_k,
             should_include_distance,
         );
-        convert_result(result)
+        Ok(result.into())
     }
 
     pub fn get_nearest_to_item(
@@ -89,7 +133,7 @@ impl AnnoyIndexJs {
         n_results: u32,
         search_k: i32,
         should_include_distance: bool,
-    ) -> Result<Array, Error> {
+    ) -> Result<SearchResultJs, Error> {
         let index = unsafe { &*self.index_ptr };
         if (item_index as usize) >= index.size {
             return Err(Error::new("item_index out of range"));
@@ -100,41 +144,168 @@ impl AnnoyIndexJs {
             search_k,
             should_include_distance,
         );
-        convert_result(result)
+        Ok(result.into())
+    }
+
+    /// Searches every `dimension` long query in `query_vectors`, a `Float32Array` holding queries back to back.
+    pub fn get_nearest_batch(
+        &self,
+        query_vectors: &[f32],
+        n_results: u32,
+        search_k: i32,
+        should_include_distance: bool,
+    ) -> Result<BatchSearchResultJs, Error> {
+        let index = unsafe { &*self.index_ptr };
+        if query_vectors.len() % index.dimension != 0 {
+            return Err(Error::new(&format!(
+                "Length of query_vectors should be a multiple of {}, {} provided.",
+                index.dimension,
+                query_vectors.len()
+            )));
+        }
+        let n_queries = query_vectors.len() / index.dimension;
+        let n_results = n_results as usize;
+        let mut counts = Vec::with_capacity(n_queries);
+        let mut ids = vec![u32::MAX; n_queries * n_results];
+        let mut distances = if should_include_distance {
+            Some(vec![f32::NAN; n_queries * n_results])
+        } else {
+            None
+        };
+        for (i, query_vector) in query_vectors.chunks_exact(index.dimension).enumerate() {
+            let result =
+                index.get_nearest(query_vector, n_results, search_k, should_include_distance);
+            let offset = i * n_results;
+            for (j, &id) in result.id_list.iter().enumerate() {
+                ids[offset + j] = id as u32;
+            }
+            if let Some(distances) = distances.as_mut() {
+                distances[offset..offset + result.count].copy_from_slice(&result.distance_list);
+            }
+            counts.push(result.count as u32);
+        }
+        Ok(BatchSearchResultJs {
+            n_results,
+            counts,
+            ids,
+            distances,
+        })
     }
 }
 
+fn check_dimension(index: &AnnoyIndex, dimension: usize) -> Result<(), Error> {
+    if dimension != index.dimension {
+        return Err(Error::new(&format!(
+            "Wrong input dimension, {} expected, {} provided.",
+            index.dimension, dimension
+        )));
+    }
+    Ok(())
+}
+
 #[wasm_bindgen]
 pub fn load_index(
     u8a: &Uint8Array,
     dimension: usize,
     index_type: IndexType,
 ) -> Result<AnnoyIndexJs, Error> {
-    let mut buffer = vec![0_u8; u8a.length() as usize];
-    u8a.copy_to(&mut buffer);
-    let index = AnnoyIndex::load_from_buffer(buffer, dimension, index_type)
-        .map_err(|err| Error::new(&format!("{err}")))?;
-    Ok(AnnoyIndexJs {
-        dimension: index.dimension,
-        size: index.size,
-
-        index_ptr: Box::into_raw(Box::new(index)),
-    })
-}
-
-fn convert_result(result: AnnoyIndexSearchResult) -> Result<Array, Error> {
-    let array = Array::new();
-    for i in 0..result.count {
-        let id = result.id_list[i];
-        let distance = if result.is_distance_included {
-            Some(result.distance_list[i])
-        } else {
-            None
-        };
-        array.push(
-            &serde_wasm_bindgen::to_value(&SearchResultJs { id, distance })
-                .map_err(|err| Error::new(&format!("{err}")))?,
-        );
+    let mut loader = IndexLoaderJs::new(Some(u8a.length()));
+    loader.push(u8a);
+    loader.finish(dimension, index_type)
+}
+
+/// Builds an index from chunks copied straight into wasm memory, so that the
+/// index file never has to be held by js and wasm at the same time.
+#[wasm_bindgen]
+#[derive(Debug, Default)]
+pub struct IndexLoaderJs {
+    buffer: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl IndexLoaderJs {
+    /// `expected_length`, e.g. from the `Content-Length` header, avoids reallocations.
+    #[wasm_bindgen(constructor)]
+    pub fn new(expected_length: Option<u32>) -> IndexLoaderJs {
+        IndexLoaderJs {
+            buffer: Vec::with_capacity(expected_length.unwrap_or_default() as usize),
+        }
+    }
+
+    pub fn push(&mut self, chunk: &Uint8Array) {
+        let start = self.buffer.len();
+        self.buffer.resize(start + chunk.length() as usize, 0);
+        chunk.copy_to(&mut self.buffer[start..]);
     }
-    Ok(array)
+
+    pub fn finish(self, dimension: usize, index_type: IndexType) -> Result<AnnoyIndexJs, Error> {
+        let index = AnnoyIndex::load_from_buffer(self.buffer, dimension, index_type)
+            .map_err(|err| Error::new(&format!("{err}")))?;
+        Ok(AnnoyIndexJs {
+            dimension: index.dimension,
+            size: index.size,
+
+            index_ptr: Box::into_raw(Box::new(index)),
+        })
+    }
+}
+
+/// Loads an index from a fetch `Response` or a `ReadableStream` of `Uint8Array` chunks.
+#[wasm_bindgen]
+pub async fn load_index_from_stream(
+    source: JsValue,
+    dimension: usize,
+    index_type: IndexType,
+) -> Result<AnnoyIndexJs, Error> {
+    let body = get(&source, "body")?;
+    let (stream, expected_length) = if body.is_undefined() {
+        (source, None)
+    } else {
+        (body, content_length(&source))
+    };
+    if stream.is_null() {
+        return Err(Error::new("Response has no body"));
+    }
+
+    let reader = call(&stream, "getReader")?;
+    let mut loader = IndexLoaderJs::new(expected_length);
+    loop {
+        let chunk = JsFuture::from(call(&reader, "read")?.unchecked_into::<Promise>())
+            .await
+            .map_err(to_error)?;
+        if get(&chunk, "done")?.as_bool().unwrap_or(true) {
+            break;
+        }
+        loader.push(get(&chunk, "value")?.unchecked_ref::<Uint8Array>());
+    }
+    loader.finish(dimension, index_type)
+}
+
+fn content_length(response: &JsValue) -> Option<u32> {
+    let headers = get(response, "headers").ok()?;
+    let get_header = get(&headers, "get").ok()?.dyn_into::<Function>().ok()?;
+    get_header
+        .call1(&headers, &JsValue::from_str("content-length"))
+        .ok()?
+        .as_string()?
+        .parse()
+        .ok()
+}
+
+fn get(target: &JsValue, key: &str) -> Result<JsValue, Error> {
+    Reflect::get(target, &JsValue::from_str(key)).map_err(to_error)
+}
+
+fn call(target: &JsValue, method: &str) -> Result<JsValue, Error> {
+    get(target, method)?
+        .dyn_into::<Function>()
+        .map_err(|_| Error::new(&format!("{method} is not a function")))?
+        .call0(target)
+        .map_err(to_error)
+}
+
+fn to_error(value: JsValue) -> Error {
+    value
+        .dyn_into::<Error>()
+        .unwrap_or_else(|value| Error::new(&format!("{value:?}")))
 }
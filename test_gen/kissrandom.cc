@@ -0,0 +1,52 @@
+// Prints the first outputs of annoy's Kiss64Random for its default seed, the known answers of
+// the Kiss64Random tests. The struct is copied from annoy's src/kissrandom.h.
+//
+//   g++ -o kissrandom kissrandom.cc && ./kissrandom
+#include <cstdint>
+#include <cstdio>
+
+struct Kiss64Random {
+  uint64_t x;
+  uint64_t y;
+  uint64_t z;
+  uint64_t c;
+
+  static const uint64_t default_seed = 1234567890987654321ULL;
+
+  Kiss64Random(uint64_t seed = default_seed) {
+    x = seed;
+    y = 362436362436362436ULL;
+    z = 1066149217761810ULL;
+    c = 123456123456123456ULL;
+  }
+
+  uint64_t kiss() {
+    // Linear congruence generator
+    z = 6906969069LL*z+1234567;
+
+    // Xor shift
+    y ^= (y<<13);
+    y ^= (y>>17);
+    y ^= (y<<43);
+
+    // Multiply-with-carry (uint128_t t = (2^58 + 1) * x + c; c = t >> 64; x = (uint64_t) t)
+    uint64_t t = (x<<58)+c;
+    c = (x>>6);
+    x += t;
+    c += (x<t);
+
+    return x + y + z;
+  }
+};
+
+int main() {
+  Kiss64Random rng;
+  for (int i = 0; i < 5; i++) {
+    printf("%llu\n", (unsigned long long)rng.kiss());
+  }
+  rng = Kiss64Random(42);
+  for (int i = 0; i < 5; i++) {
+    printf("%llu\n", (unsigned long long)rng.kiss());
+  }
+  return 0;
+}
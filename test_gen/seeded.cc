@@ -0,0 +1,447 @@
+// Builds the seeded fixtures tests/seeded.{metric}.5d.ann without python, from the items of the
+// tests/index.{metric}.5d.ann fixtures, with seed 42, 10 trees and one thread.
+//
+// The build is a transcription of annoy's (annoylib.h, kissrandom.h) for float vectors and int32
+// ids, with the metric-specific parts of Angular, Euclidean, Manhattan and DotProduct, so that
+// the fixtures can be generated where annoy isn't installed. test_gen/seeded.py builds them with
+// annoy itself, which gives the same files where annoy is compiled without fused multiply-adds,
+// e.g. on x86_64.
+//
+//   g++ -O2 -o seeded seeded.cc && ./seeded
+#include <cmath>
+#include <cstdint>
+#include <cstdio>
+#include <cstring>
+#include <string>
+#include <vector>
+
+using std::vector;
+typedef int32_t S;
+typedef float T;
+
+struct Kiss64Random {
+  uint64_t x;
+  uint64_t y;
+  uint64_t z;
+  uint64_t c;
+
+  static const uint64_t default_seed = 1234567890987654321ULL;
+
+  Kiss64Random(uint64_t seed = default_seed) {
+    x = seed;
+    y = 362436362436362436ULL;
+    z = 1066149217761810ULL;
+    c = 123456123456123456ULL;
+  }
+
+  uint64_t kiss() {
+    z = 6906969069LL*z+1234567;
+    y ^= (y<<13);
+    y ^= (y>>17);
+    y ^= (y<<43);
+    uint64_t t = (x<<58)+c;
+    c = (x>>6);
+    x += t;
+    c += (x<t);
+    return x + y + z;
+  }
+  inline int flip() {
+    return kiss() & 1;
+  }
+  inline size_t index(size_t n) {
+    return kiss() % n;
+  }
+};
+
+inline T dot(const T* x, const T* y, int f) {
+  T s = 0;
+  for (int z = 0; z < f; z++) {
+    s += (*x) * (*y);
+    x++;
+    y++;
+  }
+  return s;
+}
+
+inline T get_norm(const T* v, int f) {
+  return sqrt(dot(v, v, f));
+}
+
+enum Metric { ANGULAR, EUCLIDEAN, MANHATTAN, DOT };
+
+// A node of size s: n_descendants, then the metric-specific fields, children and vector.
+//   Angular:   n_descendants, children[2] (norm in children[0] for items), v
+//   Minkowski: n_descendants, a, children[2], v
+//   Dot:       n_descendants, children[2], dot_factor, v
+struct Layout {
+  Metric metric;
+  int f;
+  size_t s;
+
+  size_t children_offset() const { return metric == EUCLIDEAN || metric == MANHATTAN ? 8 : 4; }
+  size_t v_offset() const { return metric == ANGULAR ? 12 : 16; }
+
+  S* n_descendants(char* n) const { return (S*)n; }
+  S* children(char* n) const { return (S*)(n + children_offset()); }
+  T* norm(char* n) const { return (T*)(n + 4); }
+  T* a(char* n) const { return (T*)(n + 4); }
+  T* dot_factor(char* n) const { return (T*)(n + 12); }
+  T* v(char* n) const { return (T*)(n + v_offset()); }
+};
+
+struct Builder {
+  Layout l;
+  int _f;
+  size_t _s;
+  S _K;
+  S _n_items;
+  S _n_nodes;
+  vector<char> _nodes;
+  S _nodes_size;
+  vector<S> _roots;
+  uint64_t _seed;
+
+  Builder(Metric metric, int f) {
+    _f = f;
+    l.metric = metric;
+    l.f = f;
+    l.s = (metric == ANGULAR ? 12 : 16) + f * sizeof(T);
+    _s = l.s;
+    _K = (S)((_s - l.children_offset()) / sizeof(S));
+    _n_items = 0;
+    _n_nodes = 0;
+    _nodes_size = 0;
+    _seed = Kiss64Random::default_seed;
+  }
+
+  char* _get(S i) { return &_nodes[(size_t)i * _s]; }
+
+  void _allocate_size(S n) {
+    if (n > _nodes_size) {
+      const double reallocation_factor = 1.3;
+      S new_nodes_size = std::max(n, (S)((_nodes_size + 1) * reallocation_factor));
+      _nodes.resize((size_t)new_nodes_size * _s, 0);
+      _nodes_size = new_nodes_size;
+    }
+  }
+
+  // Distance-specific parts, the node arguments are scratch nodes or nodes of the index
+  void zero_value(char* n) {
+    if (l.metric == DOT) *l.dot_factor(n) = 0;
+  }
+
+  void init_node(char* n) {
+    if (l.metric == ANGULAR) *l.norm(n) = dot(l.v(n), l.v(n), _f);
+  }
+
+  void copy_node(char* dest, char* source) {
+    memcpy(l.v(dest), l.v(source), _f * sizeof(T));
+    if (l.metric == DOT) *l.dot_factor(dest) = *l.dot_factor(source);
+  }
+
+  void normalize(char* n) {
+    if (l.metric == DOT) {
+      T norm = sqrt(dot(l.v(n), l.v(n), _f) + pow(*l.dot_factor(n), 2));
+      if (norm > 0) {
+        for (int z = 0; z < _f; z++)
+          l.v(n)[z] /= norm;
+        *l.dot_factor(n) /= norm;
+      }
+      return;
+    }
+    T norm = get_norm(l.v(n), _f);
+    if (norm > 0) {
+      for (int z = 0; z < _f; z++)
+        l.v(n)[z] /= norm;
+    }
+  }
+
+  void update_mean(char* mean, char* new_node, T norm, int c) {
+    for (int z = 0; z < _f; z++)
+      l.v(mean)[z] = (l.v(mean)[z] * c + l.v(new_node)[z] / norm) / (c + 1);
+    if (l.metric == DOT)
+      *l.dot_factor(mean) = (*l.dot_factor(mean) * c + *l.dot_factor(new_node) / norm) / (c + 1);
+  }
+
+  T distance(char* x, char* y) {
+    const T* xv = l.v(x);
+    const T* yv = l.v(y);
+    switch (l.metric) {
+      case ANGULAR: {
+        T pp = *l.norm(x) ? *l.norm(x) : dot(xv, xv, _f);
+        T qq = *l.norm(y) ? *l.norm(y) : dot(yv, yv, _f);
+        T pq = dot(xv, yv, _f);
+        T ppqq = pp * qq;
+        if (ppqq > 0) return 2.0 - 2.0 * pq / sqrt(ppqq);
+        else return 2.0;
+      }
+      case EUCLIDEAN: {
+        T d = 0.0;
+        for (int i = 0; i < _f; i++) {
+          const T tmp = xv[i] - yv[i];
+          d += tmp * tmp;
+        }
+        return d;
+      }
+      case MANHATTAN: {
+        T d = 0.0;
+        for (int i = 0; i < _f; i++)
+          d += fabs(xv[i] - yv[i]);
+        return d;
+      }
+      case DOT:
+        return -dot(xv, yv, _f);
+    }
+    return 0;
+  }
+
+  T margin(char* n, char* y) {
+    switch (l.metric) {
+      case ANGULAR:
+        return dot(l.v(n), l.v(y), _f);
+      case EUCLIDEAN:
+      case MANHATTAN:
+        return *l.a(n) + dot(l.v(n), l.v(y), _f);
+      case DOT:
+        return dot(l.v(n), l.v(y), _f) + (*l.dot_factor(n) * *l.dot_factor(n));
+    }
+    return 0;
+  }
+
+  bool side(char* n, char* y, Kiss64Random& random) {
+    T dot = margin(n, y);
+    if (dot != 0)
+      return (dot > 0);
+    else
+      return (bool)random.flip();
+  }
+
+  void two_means(const vector<char*>& nodes, Kiss64Random& random, bool cosine, char* p, char* q) {
+    static int iteration_steps = 200;
+    size_t count = nodes.size();
+
+    size_t i = random.index(count);
+    size_t j = random.index(count-1);
+    j += (j >= i);
+
+    copy_node(p, nodes[i]);
+    copy_node(q, nodes[j]);
+
+    if (cosine) { normalize(p); normalize(q); }
+    init_node(p);
+    init_node(q);
+
+    int ic = 1, jc = 1;
+    for (int l = 0; l < iteration_steps; l++) {
+      size_t k = random.index(count);
+      T di = ic * distance(p, nodes[k]),
+        dj = jc * distance(q, nodes[k]);
+      T norm = cosine ? get_norm(this->l.v(nodes[k]), _f) : 1;
+      if (!(norm > T(0))) {
+        continue;
+      }
+      if (di < dj) {
+        update_mean(p, nodes[k], norm, ic);
+        init_node(p);
+        ic++;
+      } else if (dj < di) {
+        update_mean(q, nodes[k], norm, jc);
+        init_node(q);
+        jc++;
+      }
+    }
+  }
+
+  void create_split(const vector<char*>& nodes, Kiss64Random& random, char* n) {
+    vector<char> p_buf(_s, 0), q_buf(_s, 0);
+    char* p = p_buf.data();
+    char* q = q_buf.data();
+    zero_value(p);
+    zero_value(q);
+    two_means(nodes, random, l.metric == ANGULAR || l.metric == DOT, p, q);
+    for (int z = 0; z < _f; z++)
+      l.v(n)[z] = l.v(p)[z] - l.v(q)[z];
+    if (l.metric == DOT)
+      *l.dot_factor(n) = *l.dot_factor(p) - *l.dot_factor(q);
+    normalize(n);
+    if (l.metric == EUCLIDEAN || l.metric == MANHATTAN) {
+      *l.a(n) = 0.0;
+      for (int z = 0; z < _f; z++)
+        *l.a(n) += -l.v(n)[z] * (l.v(p)[z] + l.v(q)[z]) / 2;
+    }
+  }
+
+  void add_item(S item, const T* w) {
+    _allocate_size(item + 1);
+    char* n = _get(item);
+
+    zero_value(n);
+
+    l.children(n)[0] = 0;
+    l.children(n)[1] = 0;
+    *l.n_descendants(n) = 1;
+
+    for (int z = 0; z < _f; z++)
+      l.v(n)[z] = w[z];
+
+    init_node(n);
+
+    if (item >= _n_items)
+      _n_items = item + 1;
+  }
+
+  void preprocess() {
+    if (l.metric != DOT) return;
+    for (S i = 0; i < _n_items; i++) {
+      char* node = _get(i);
+      T d = dot(l.v(node), l.v(node), _f);
+      T norm = d < 0 ? 0 : sqrt(d);
+      *l.dot_factor(node) = norm;
+    }
+    T max_norm = 0;
+    for (S i = 0; i < _n_items; i++) {
+      char* node = _get(i);
+      if (*l.dot_factor(node) > max_norm) {
+        max_norm = *l.dot_factor(node);
+      }
+    }
+    for (S i = 0; i < _n_items; i++) {
+      char* node = _get(i);
+      T node_norm = *l.dot_factor(node);
+      // In double precision, which reproduces more of the dot factors of tests/index.dot.5d.ann
+      // than float does
+      double squared_norm_diff = pow(max_norm, 2) - pow(node_norm, 2);
+      T dot_factor = squared_norm_diff < 0 ? 0 : sqrt(squared_norm_diff);
+      *l.dot_factor(node) = dot_factor;
+    }
+  }
+
+  void build(int q) {
+    preprocess();
+    _n_nodes = _n_items;
+    thread_build(q, 0);
+    _allocate_size(_n_nodes + (S)_roots.size());
+    for (size_t i = 0; i < _roots.size(); i++)
+      memcpy(_get(_n_nodes + (S)i), _get(_roots[i]), _s);
+    _n_nodes += _roots.size();
+  }
+
+  void thread_build(int q, int thread_idx) {
+    Kiss64Random _random(_seed + thread_idx);
+    vector<S> thread_roots;
+    while (thread_roots.size() < (size_t)q) {
+      vector<S> indices;
+      for (S i = 0; i < _n_items; i++) {
+        if (*l.n_descendants(_get(i)) >= 1) {
+          indices.push_back(i);
+        }
+      }
+      thread_roots.push_back(_make_tree(indices, true, _random));
+    }
+    _roots.insert(_roots.end(), thread_roots.begin(), thread_roots.end());
+  }
+
+  double _split_imbalance(const vector<S>& left_indices, const vector<S>& right_indices) {
+    double ls = (float)left_indices.size();
+    double rs = (float)right_indices.size();
+    float f = ls / (ls + rs + 1e-9);
+    return std::max(f, 1-f);
+  }
+
+  S _make_tree(const vector<S>& indices, bool is_root, Kiss64Random& _random) {
+    if (indices.size() == 1 && !is_root)
+      return indices[0];
+
+    if (indices.size() <= (size_t)_K &&
+        (!is_root || (size_t)_n_items <= (size_t)_K || indices.size() == 1)) {
+      _allocate_size(_n_nodes + 1);
+      S item = _n_nodes++;
+      char* m = _get(item);
+      *l.n_descendants(m) = is_root ? _n_items : (S)indices.size();
+      if (!indices.empty())
+        memcpy(l.children(m), &indices[0], indices.size() * sizeof(S));
+      return item;
+    }
+
+    vector<char*> children;
+    for (size_t i = 0; i < indices.size(); i++) {
+      children.push_back(_get(indices[i]));
+    }
+
+    vector<S> children_indices[2];
+    vector<char> m_buf(_s, 0);
+    char* m = m_buf.data();
+
+    for (int attempt = 0; attempt < 3; attempt++) {
+      children_indices[0].clear();
+      children_indices[1].clear();
+      create_split(children, _random, m);
+
+      for (size_t i = 0; i < indices.size(); i++) {
+        S j = indices[i];
+        bool side_ = side(m, _get(j), _random);
+        children_indices[side_].push_back(j);
+      }
+
+      if (_split_imbalance(children_indices[0], children_indices[1]) < 0.95)
+        break;
+    }
+
+    while (_split_imbalance(children_indices[0], children_indices[1]) > 0.99) {
+      children_indices[0].clear();
+      children_indices[1].clear();
+      for (int z = 0; z < _f; z++)
+        l.v(m)[z] = 0;
+      for (size_t i = 0; i < indices.size(); i++) {
+        S j = indices[i];
+        children_indices[_random.flip()].push_back(j);
+      }
+    }
+
+    int flip = (children_indices[0].size() > children_indices[1].size());
+
+    *l.n_descendants(m) = is_root ? _n_items : (S)indices.size();
+    for (int side_ = 0; side_ < 2; side_++) {
+      l.children(m)[side_^flip] = _make_tree(children_indices[side_^flip], false, _random);
+    }
+
+    _allocate_size(_n_nodes + 1);
+    S item = _n_nodes++;
+    memcpy(_get(item), m, _s);
+    return item;
+  }
+};
+
+int main() {
+  const char* names[] = {"angular", "euclidean", "manhattan", "dot"};
+  const Metric metrics[] = {ANGULAR, EUCLIDEAN, MANHATTAN, DOT};
+  const int f = 5;
+  const S n_items = 100;
+  for (int i = 0; i < 4; i++) {
+    Builder builder(metrics[i], f);
+    builder._seed = 42;
+    std::string in = std::string("../tests/index.") + names[i] + ".5d.ann";
+    FILE* file = fopen(in.c_str(), "rb");
+    if (!file) {
+      perror(in.c_str());
+      return 1;
+    }
+    vector<char> node(builder._s);
+    for (S item = 0; item < n_items; item++) {
+      if (fread(node.data(), builder._s, 1, file) != 1) {
+        perror(in.c_str());
+        return 1;
+      }
+      builder.add_item(item, builder.l.v(node.data()));
+    }
+    fclose(file);
+
+    builder.build(10);
+    std::string out = std::string("../tests/seeded.") + names[i] + ".5d.ann";
+    file = fopen(out.c_str(), "wb");
+    fwrite(builder._nodes.data(), builder._s, builder._n_nodes, file);
+    fclose(file);
+    printf("%s: %d nodes\n", out.c_str(), builder._n_nodes);
+  }
+  return 0;
+}
@@ -0,0 +1,20 @@
+from annoy import AnnoyIndex
+
+# Builds the items of the test indexes again with a fixed seed on one thread, the fixtures that
+# AnnoyIndexBuilder must reproduce byte for byte. seeded.cc builds the same files without annoy.
+metrics = ["angular", "euclidean", "manhattan", "dot"]
+dim = 5
+seed = 42
+n_trees = 10
+
+for metric in metrics:
+    print(f'Generating seeded index for {metric}')
+    u = AnnoyIndex(dim, metric)
+    u.load(f'./../tests/index.{metric}.{dim}d.ann')
+
+    t = AnnoyIndex(dim, metric)
+    t.set_seed(seed)
+    for i in range(u.get_n_items()):
+        t.add_item(i, u.get_item_vector(i))
+    t.build(n_trees, n_jobs=1)
+    t.save(f'./../tests/seeded.{metric}.{dim}d.ann')
@@ -0,0 +1,104 @@
+//! Its own test binary, so that the global allocator only counts the disk build.
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+    use annoy_rs::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const DIMENSION: usize = 64;
+    const N_ITEMS: usize = 10_000;
+
+    struct CountingAllocator;
+
+    static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::SeqCst);
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    /// Deterministic pseudo random vectors, so that items needn't be kept around.
+    fn item_vector(item: u64) -> Vec<f32> {
+        let mut state = item.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        (0..DIMENSION)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % 2000) as f32 / 1000.0 - 1.0
+            })
+            .collect()
+    }
+
+    fn is_hole(item: u64) -> bool {
+        item % 97 == 13
+    }
+
+    #[test]
+    fn disk_build_tests() {
+        let dir = std::env::temp_dir().join(format!("annoy-disk-build-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for index_type in [IndexType::Angular, IndexType::Euclidean, IndexType::Dot] {
+            let path = dir.join(format!("{index_type}.ann"));
+            let path = path.to_str().unwrap();
+
+            let baseline = ALLOCATED.load(Ordering::SeqCst);
+            PEAK.store(baseline, Ordering::SeqCst);
+            let mut builder = DiskIndexBuilder::new(DIMENSION, index_type.clone(), path).unwrap();
+            builder.set_seed(7);
+            builder.set_n_threads(2);
+            builder.insert_metadata("source", "disk_build_tests");
+            for item in (0..N_ITEMS as u64).filter(|&item| !is_hole(item)) {
+                builder.add_item(item, &item_vector(item)).unwrap();
+            }
+            assert_eq!(builder.n_items(), N_ITEMS);
+            builder.build(4).unwrap();
+            let peak = PEAK.load(Ordering::SeqCst) - baseline;
+            let vectors_size = N_ITEMS * DIMENSION * 4;
+            assert!(
+                peak < vectors_size / 4,
+                "{index_type} build peaked at {peak} bytes of heap"
+            );
+            let tree_files = std::fs::read_dir(&dir).unwrap().count();
+            assert_eq!(tree_files, 1, "temporary tree files are removed");
+
+            // Same trees as an in-memory build
+            let mut memory_builder = AnnoyIndexBuilder::new(DIMENSION, index_type.clone()).unwrap();
+            memory_builder.set_seed(7);
+            memory_builder.set_n_threads(2);
+            memory_builder.insert_metadata("source", "disk_build_tests");
+            for item in (0..N_ITEMS as u64).filter(|&item| !is_hole(item)) {
+                memory_builder.add_item(item, &item_vector(item)).unwrap();
+            }
+            assert!(std::fs::read(path).unwrap() == memory_builder.build(4).unwrap());
+
+            let index = AnnoyIndex::load(DIMENSION, path, index_type.clone()).unwrap();
+            assert_eq!(index.size, N_ITEMS);
+            assert_eq!(index.n_trees(), 4);
+            assert_eq!(index.metadata().unwrap().n_items, N_ITEMS);
+            for item in [0, 1, 999, N_ITEMS as u64 - 1] {
+                assert_eq!(index.get_item_vector(item), item_vector(item));
+                if index_type != IndexType::Dot {
+                    let nearest = index.get_nearest_to_item(item, 1, -1, false);
+                    assert_eq!(nearest.id_list, [item]);
+                }
+            }
+            std::fs::remove_file(path).unwrap();
+        }
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -2,6 +2,8 @@
 #[cfg(not(target_arch = "wasm32"))]
 mod tests {
     use annoy_rs::*;
+    use std::collections::HashSet;
+    use std::time::{Duration, Instant};
 
     const F32_PRECISION: usize = 2;
     const TEST_INDEX_DIM: usize = 5;
@@ -78,9 +80,11 @@ mod tests {
         expected_distance_list: &[f32],
     ) {
         let filepath = format!("tests/index.{index_type}.{TEST_INDEX_DIM}d.ann");
+        let buffer = std::fs::read(&filepath).unwrap();
         for index in [
             AnnoyIndex::load(TEST_INDEX_DIM, &filepath, index_type.clone()).unwrap(),
-            AnnoyIndex::load_into_mem(TEST_INDEX_DIM, &filepath, index_type).unwrap(),
+            AnnoyIndex::load_into_mem(TEST_INDEX_DIM, &filepath, index_type.clone()).unwrap(),
+            AnnoyIndex::load_from_owner(buffer, TEST_INDEX_DIM, index_type).unwrap(),
         ] {
             assert_eq!(index.get_item_vector(3), expected_item3_vec);
 
@@ -102,9 +106,441 @@ mod tests {
                 let b = expected_distance_list[i];
                 assert!((a - b).abs() < 1e-5);
             }
+            for (&id, &distance) in id_list.iter().zip(distance_list.iter()) {
+                assert!((index.get_distance(0, id) - distance).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn exclusion_tests() {
+        let filepath = format!("tests/index.angular.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Angular).unwrap();
+        let nearest = index.get_nearest_to_item(0, 6, -1, false);
+        assert_eq!(nearest.id_list[0], 0);
+
+        let without_self =
+            index.get_nearest_to_item_excluding(0, 5, -1, true, true, &HashSet::new());
+        assert_eq!(without_self.count, 5);
+        assert_eq!(without_self.id_list, &nearest.id_list[1..]);
+        assert_eq!(without_self.distance_list.len(), 5);
+
+        let excluded: HashSet<u64> = [4, 37].into_iter().collect();
+        let without_seen = index.get_nearest_to_item_excluding(0, 4, -1, false, true, &excluded);
+        assert_eq!(without_seen.count, 4);
+        assert!(without_seen
+            .id_list
+            .iter()
+            .all(|id| *id != 0 && !excluded.contains(id)));
+        assert_eq!(without_seen.id_list[..2], nearest.id_list[3..5]);
+
+        let v0 = index.get_item_vector(0);
+        let excluded_by_vector = index.get_nearest_excluding(&v0, 4, -1, false, &excluded);
+        assert_eq!(excluded_by_vector.id_list[0], 0);
+        assert!(excluded_by_vector
+            .id_list
+            .iter()
+            .all(|id| !excluded.contains(id)));
+
+        // Default implementations over-fetch and filter
+        struct Wrapper(AnnoyIndex);
+        impl AnnoyIndexSearchApi for Wrapper {
+            fn get_item_vector(&self, item_index: u64) -> Vec<f32> {
+                self.0.get_item_vector(item_index)
+            }
+            fn metric(&self) -> Option<&dyn Distance> {
+                self.0.metric()
+            }
+            fn get_nearest(
+                &self,
+                query_vector: &[f32],
+                n_results: usize,
+                search_k: i32,
+                should_include_distance: bool,
+            ) -> AnnoyIndexSearchResult {
+                self.0
+                    .get_nearest(query_vector, n_results, search_k, should_include_distance)
+            }
+            fn get_nearest_to_item(
+                &self,
+                item_index: u64,
+                n_results: usize,
+                search_k: i32,
+                should_include_distance: bool,
+            ) -> AnnoyIndexSearchResult {
+                self.0
+                    .get_nearest_to_item(item_index, n_results, search_k, should_include_distance)
+            }
+        }
+        let wrapper = Wrapper(index);
+        let wrapped = wrapper.get_nearest_to_item_excluding(0, 4, -1, true, true, &excluded);
+        assert_eq!(wrapped.id_list, without_seen.id_list);
+        assert_eq!(wrapped.distance_list.len(), 4);
+        let wrapped = wrapper.get_nearest_excluding(&v0, 4, -1, false, &excluded);
+        assert_eq!(wrapped.id_list, excluded_by_vector.id_list);
+        let (wrapped, stats) = wrapper.get_nearest_with_stats(&v0, 4, -1, false);
+        assert_eq!(
+            wrapped.id_list,
+            wrapper.0.get_nearest(&v0, 4, -1, false).id_list
+        );
+        assert_eq!(stats.nodes_visited, 0);
+        let wrapped =
+            wrapper.get_nearest_with_budget(&v0, 4, -1, false, SearchBudget::MaxCandidates(1));
+        assert!(!wrapped.is_truncated);
+        assert_eq!(wrapped.count, 4);
+        for &id in &wrapper.0.get_nearest(&v0, 4, -1, false).id_list {
+            assert!((wrapper.get_distance(0, id) - wrapper.0.get_distance(0, id)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn search_stats_tests() {
+        let filepath = format!("tests/index.euclidean.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Euclidean).unwrap();
+        let v0 = index.get_item_vector(0);
+        let nearest = index.get_nearest(&v0, 5, -1, true);
+        let (nearest_with_stats, stats) = index.get_nearest_with_stats(&v0, 5, -1, true);
+        assert_eq!(format!("{nearest:?}"), format!("{nearest_with_stats:?}"));
+
+        assert!(stats.nodes_visited > 0);
+        assert!(stats.margin_evaluations < stats.nodes_visited);
+        assert!(stats.candidate_count >= stats.distance_computations);
+        assert!(stats.distance_computations >= nearest.count);
+        assert_eq!(stats.result_trees.len(), nearest.count);
+        for trees in stats.result_trees.iter() {
+            assert!(!trees.is_empty());
+            assert!(trees.windows(2).all(|w| w[0] < w[1]));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn async_tests() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let filepath = format!("tests/index.angular.{TEST_INDEX_DIM}d.ann");
+            assert!(
+                AsyncAnnoyIndex::load(TEST_INDEX_DIM, "missing.ann", IndexType::Angular, 1)
+                    .await
+                    .is_err()
+            );
+            let index = AsyncAnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Angular, 1)
+                .await
+                .unwrap();
+            let v0 = index.index().get_item_vector(0);
+            let expected = index.index().get_nearest(&v0, 5, -1, true);
+
+            let nearest = index.get_nearest(v0.clone(), 5, -1, true).await.unwrap();
+            assert_eq!(format!("{nearest:?}"), format!("{expected:?}"));
+            let nearest = index.get_nearest_to_item(0, 5, -1, true).await.unwrap();
+            assert_eq!(format!("{nearest:?}"), format!("{expected:?}"));
+            assert!(index.get_nearest_to_item(100, 5, -1, true).await.is_err());
+            assert!(index.get_nearest(vec![0.0], 5, -1, true).await.is_err());
+
+            // Only one search runs at a time, the others wait for it
+            let (a, b, batch) = tokio::join!(
+                index.get_nearest(v0.clone(), 5, -1, true),
+                index.get_nearest_to_item(0, 5, -1, true),
+                index.get_nearest_batch(vec![v0.clone(); 3], 5, -1, true),
+            );
+            assert_eq!(format!("{:?}", a.unwrap()), format!("{expected:?}"));
+            assert_eq!(format!("{:?}", b.unwrap()), format!("{expected:?}"));
+            let batch = batch.unwrap();
+            assert_eq!(batch.len(), 3);
+            assert!(batch
+                .iter()
+                .all(|r| format!("{r:?}") == format!("{expected:?}")));
+
+            // A dropped search gives its slot back
+            let pending = index.get_nearest(v0.clone(), 5, -1, true);
+            drop(pending);
+            let mut started = Box::pin(index.get_nearest_batch(vec![v0.clone(); 100], 5, -1, true));
+            assert!(futures_poll_once(started.as_mut()).is_none());
+            drop(started);
+            let nearest = index.get_nearest(v0, 5, -1, true).await.unwrap();
+            assert_eq!(format!("{nearest:?}"), format!("{expected:?}"));
+        });
+    }
+
+    #[cfg(feature = "async")]
+    fn futures_poll_once<F: std::future::Future>(
+        future: std::pin::Pin<&mut F>,
+    ) -> Option<F::Output> {
+        let waker = std::task::Waker::noop();
+        match future.poll(&mut std::task::Context::from_waker(waker)) {
+            std::task::Poll::Ready(output) => Some(output),
+            std::task::Poll::Pending => None,
+        }
+    }
+
+    #[test]
+    fn search_budget_tests() {
+        let filepath = format!("tests/index.manhattan.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Manhattan).unwrap();
+        let v0 = index.get_item_vector(0);
+        let nearest = index.get_nearest(&v0, 5, -1, true);
+        assert!(!nearest.is_truncated);
+
+        let generous = index.get_nearest_with_budget(
+            &v0,
+            5,
+            -1,
+            true,
+            SearchBudget::Deadline(Instant::now() + Duration::from_secs(60)),
+        );
+        assert_eq!(format!("{nearest:?}"), format!("{generous:?}"));
+
+        let expired =
+            index.get_nearest_with_budget(&v0, 5, -1, true, SearchBudget::Deadline(Instant::now()));
+        assert!(expired.is_truncated);
+        assert_eq!(expired.count, 0);
+
+        let limited =
+            index.get_nearest_with_budget(&v0, 5, -1, true, SearchBudget::MaxCandidates(3));
+        assert!(limited.is_truncated);
+        assert!(limited.count > 0 && limited.count <= 3);
+        assert_eq!(limited.id_list[0], 0);
+    }
+
+    #[test]
+    fn quantization_tests() {
+        for index_type in [
+            IndexType::Angular,
+            IndexType::Euclidean,
+            IndexType::Manhattan,
+            IndexType::Dot,
+        ] {
+            let filepath = format!("tests/index.{index_type}.{TEST_INDEX_DIM}d.ann");
+            let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, index_type.clone()).unwrap();
+            for encoding in [
+                VectorEncoding::F32,
+                VectorEncoding::F16,
+                VectorEncoding::BF16,
+                VectorEncoding::I8,
+            ] {
+                let buffer = index.quantize(encoding).unwrap();
+                for other in [VectorEncoding::F32, VectorEncoding::F16, VectorEncoding::I8] {
+                    if other != encoding {
+                        assert!(AnnoyIndex::load_quantized_from_buffer(
+                            buffer.clone(),
+                            TEST_INDEX_DIM,
+                            index_type.clone(),
+                            other,
+                        )
+                        .is_err());
+                    }
+                }
+                let quantized = AnnoyIndex::load_quantized_from_buffer(
+                    buffer,
+                    TEST_INDEX_DIM,
+                    index_type.clone(),
+                    encoding,
+                )
+                .unwrap();
+                assert_eq!(quantized.size, index.size);
+                assert_eq!(quantized.vector_encoding, encoding);
+                assert!(quantized.node_size <= index.node_size);
+
+                for item in 0..index.size as u64 {
+                    let v = index.get_item_vector(item);
+                    let vq = quantized.get_item_vector(item);
+                    for (a, b) in v.iter().zip(vq.iter()) {
+                        assert!((a - b).abs() < 0.02, "{index_type} {encoding}: {a} vs {b}");
+                    }
+                }
+
+                let v0 = index.get_item_vector(0);
+                let all = index.get_nearest(&v0, index.size, index.size as i32 * 10, true);
+                let all_quantized =
+                    quantized.get_nearest(&v0, index.size, index.size as i32 * 10, true);
+                assert_eq!(all_quantized.count, index.size, "{index_type} {encoding}");
+
+                let reranked = quantized
+                    .get_nearest_reranked(&v0, 5, -1, true, &index, 20)
+                    .unwrap();
+                assert_eq!(
+                    reranked.id_list,
+                    all.id_list[..5],
+                    "{index_type} {encoding}"
+                );
+                assert_eq!(reranked.distance_list, all.distance_list[..5]);
+                // Ids of the quantized index would be read out of bounds of another index
+                let other = AnnoyIndex::load_from_buffer(
+                    include_bytes!("hole.10d.ann").to_vec(),
+                    10,
+                    IndexType::Angular,
+                )
+                .unwrap();
+                assert!(quantized
+                    .get_nearest_reranked(&v0, 5, -1, true, &other, 20)
+                    .is_err());
+            }
+
+            // Plain files record no encoding, but their roots only make sense with the right one
+            let buffer = std::fs::read(&filepath).unwrap();
+            for (dimension, encoding) in [
+                (TEST_INDEX_DIM, VectorEncoding::F16),
+                (TEST_INDEX_DIM, VectorEncoding::I8),
+                (TEST_INDEX_DIM - 1, VectorEncoding::F32),
+                (TEST_INDEX_DIM + 1, VectorEncoding::F32),
+            ] {
+                assert!(
+                    AnnoyIndex::load_quantized_from_buffer(
+                        buffer.clone(),
+                        dimension,
+                        index_type.clone(),
+                        encoding
+                    )
+                    .is_err(),
+                    "{index_type} {dimension} {encoding}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quantization_leaf_split_tests() {
+        static HOLE_INDEX_BYTES: &[u8] = include_bytes!("hole.10d.ann");
+        let index =
+            AnnoyIndex::load_from_buffer(HOLE_INDEX_BYTES.into(), 10, IndexType::Angular).unwrap();
+        let buffer = index.quantize(VectorEncoding::I8).unwrap();
+        let quantized = AnnoyIndex::load_quantized_from_buffer(
+            buffer,
+            10,
+            IndexType::Angular,
+            VectorEncoding::I8,
+        )
+        .unwrap();
+        assert_eq!(quantized.size, 1001);
+        let v = index.get_item_vector(1000);
+        let nearest = quantized.get_nearest(&v, 100, -1, true);
+        assert_eq!(nearest.count, 1);
+        assert_eq!(nearest.id_list[0], 1000);
+
+        assert!(quantized.quantize(VectorEncoding::F16).is_err());
+    }
+
+    #[test]
+    fn load_from_owner_tests() {
+        struct Offset(Vec<u8>);
+
+        impl AsRef<[u8]> for Offset {
+            fn as_ref(&self) -> &[u8] {
+                &self.0[1..]
+            }
+        }
+
+        let filepath = format!("tests/index.angular.{TEST_INDEX_DIM}d.ann");
+        let buffer = std::fs::read(&filepath).unwrap();
+        let mut shifted = vec![0];
+        shifted.extend_from_slice(&buffer);
+        let err = AnnoyIndex::load_from_owner(Offset(shifted), TEST_INDEX_DIM, IndexType::Angular)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("aligned"));
+
+        let index = AnnoyIndex::load_from_owner(
+            std::sync::Arc::<[u8]>::from(buffer),
+            TEST_INDEX_DIM,
+            IndexType::Angular,
+        )
+        .unwrap();
+        assert_eq!(
+            index.get_nearest_to_item(0, 5, -1, false).id_list,
+            [0, 4, 37, 61, 29]
+        );
+
+        // Owners holding their bytes inline are served from where the box put them
+        #[repr(align(4))]
+        struct Inline([u8; 15872]);
+
+        impl AsRef<[u8]> for Inline {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        let owner = Inline(std::fs::read(&filepath).unwrap().try_into().unwrap());
+        let index = AnnoyIndex::load_from_owner(owner, TEST_INDEX_DIM, IndexType::Angular).unwrap();
+        assert_eq!(
+            index.get_nearest_to_item(0, 5, -1, false).id_list,
+            [0, 4, 37, 61, 29]
+        );
+    }
+
+    struct WeightedEuclideanDistance {
+        weights: Vec<f32>,
+    }
+
+    impl Distance for WeightedEuclideanDistance {
+        fn header_kind(&self) -> NodeHeaderKind {
+            NodeHeaderKind::Minkowski
+        }
+
+        fn margin(&self, normal: &[f32], bias: f32, query: &[f32]) -> f32 {
+            EuclideanDistance.margin(normal, bias, query)
+        }
+
+        fn distance(&self, v: &[f32], query: &[f32]) -> f32 {
+            v.iter()
+                .zip(query.iter())
+                .zip(self.weights.iter())
+                .map(|((x, y), w)| w * (x - y).powi(2))
+                .sum()
+        }
+
+        fn normalized_distance(&self, d: f32) -> f32 {
+            d.sqrt()
+        }
+
+        fn create_split(&self, vectors: &[Vec<f32>]) -> (Vec<f32>, f32) {
+            EuclideanDistance.create_split(vectors)
         }
     }
 
+    #[test]
+    fn custom_distance_tests() {
+        let filepath = format!("tests/index.euclidean.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Euclidean).unwrap();
+        let custom = AnnoyIndex::load_with_distance(
+            TEST_INDEX_DIM,
+            &filepath,
+            WeightedEuclideanDistance {
+                weights: vec![4.0; TEST_INDEX_DIM],
+            },
+            VectorEncoding::F32,
+        )
+        .unwrap();
+        assert_eq!(custom.index_type, IndexType::Custom);
+        assert_eq!(custom.size, index.size);
+
+        let v0 = index.get_item_vector(0);
+        let nearest = index.get_nearest(&v0, 5, -1, true);
+        let nearest_custom = custom.get_nearest(&v0, 5, -1, true);
+        assert_eq!(nearest_custom.id_list, nearest.id_list);
+        for (a, b) in nearest
+            .distance_list
+            .iter()
+            .zip(nearest_custom.distance_list.iter())
+        {
+            assert!((a * 2.0 - b).abs() < 1e-5);
+        }
+
+        let quantized = AnnoyIndex::load_from_buffer_with_distance(
+            index.quantize(VectorEncoding::F16).unwrap(),
+            TEST_INDEX_DIM,
+            EuclideanDistance,
+            VectorEncoding::F16,
+        )
+        .unwrap();
+        assert_eq!(quantized.get_nearest(&v0, 1, -1, false).id_list, [0]);
+
+        assert!(AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Custom).is_err());
+        assert!(IndexType::try_from(5).is_err());
+    }
+
     #[test]
     fn hole_tests() {
         static HOLE_INDEX_BYTES: &[u8] = include_bytes!("hole.10d.ann");
@@ -130,6 +566,898 @@ mod tests {
         assert_eq!(nearest.distance_list[0], 1.212572);
     }
 
+    #[test]
+    fn tombstone_tests() {
+        let filepath = format!("tests/index.angular.{TEST_INDEX_DIM}d.ann");
+        let mut index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Angular).unwrap();
+        let v0 = index.get_item_vector(0);
+        let nearest = index.get_nearest(&v0, 10, -1, true);
+
+        assert!(index.delete_item(0).unwrap());
+        assert!(!index.delete_item(0).unwrap());
+        assert!(index.delete_item(TEST_NODE_COUNT as u64).is_err());
+        index.delete_item(nearest.id_list[2]).unwrap();
+        assert!(index.is_deleted(0));
+        assert_eq!(index.deleted_count(), 2);
+        assert_eq!(index.deleted_fraction(), 0.02);
+
+        let (live, stats) = index.get_nearest_with_stats(&v0, 5, -1, true);
+        assert_eq!(live.count, 5);
+        assert!(live.id_list.iter().all(|&id| !index.is_deleted(id)));
+        assert_eq!(live.id_list[..2], [nearest.id_list[1], nearest.id_list[3]]);
+        assert!(stats.deleted_skipped >= 2);
+        let all = index.get_nearest(&v0, TEST_NODE_COUNT, 10000, false);
+        assert_eq!(all.count, TEST_NODE_COUNT - 2);
+
+        let sidecar = std::env::temp_dir().join("tombstone_tests.ann.deleted");
+        let sidecar = sidecar.to_str().unwrap();
+        index.save_tombstones(sidecar).unwrap();
+        let mut reloaded = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Angular).unwrap();
+        reloaded.load_tombstones(sidecar).unwrap();
+        assert_eq!(reloaded.deleted_count(), 2);
+        assert_eq!(
+            reloaded.tombstones().iter().collect::<Vec<_>>(),
+            [0, nearest.id_list[2]]
+        );
+        std::fs::remove_file(sidecar).unwrap();
+
+        assert!(index.undelete_item(0));
+        assert!(!index.undelete_item(0));
+        assert_eq!(index.get_nearest(&v0, 1, -1, false).id_list, [0]);
+        assert!(Tombstones::from_bytes(&[0; 16], TEST_NODE_COUNT).is_err());
+    }
+
+    #[test]
+    fn compaction_tests() {
+        for index_type in [
+            IndexType::Angular,
+            IndexType::Euclidean,
+            IndexType::Manhattan,
+            IndexType::Dot,
+        ] {
+            let filepath = format!("tests/index.{index_type}.{TEST_INDEX_DIM}d.ann");
+            let mut index =
+                AnnoyIndex::load(TEST_INDEX_DIM, &filepath, index_type.clone()).unwrap();
+            for id in (0..TEST_NODE_COUNT as u64).step_by(3) {
+                index.delete_item(id).unwrap();
+            }
+            let n_live = TEST_NODE_COUNT - index.deleted_count();
+            let v1 = index.get_item_vector(1);
+            let expected = index.get_nearest(&v1, 10, -1, true);
+            let expected_all = index.get_nearest(&v1, 10, 10000, true);
+
+            for preserve_splits in [true, false] {
+                let compaction = index.compact(preserve_splits).unwrap();
+                assert_eq!(compaction.size(), n_live);
+                assert_eq!(compaction.id_map[0], None);
+                assert_eq!(compaction.id_map[1], Some(0));
+                assert_eq!(compaction.id_map[2], Some(1));
+                assert_eq!(compaction.id_map[4], Some(2));
+                let old_ids = compaction.old_ids();
+                assert_eq!(old_ids[..3], [1, 2, 4]);
+
+                let compacted = AnnoyIndex::load_from_buffer(
+                    compaction.buffer,
+                    TEST_INDEX_DIM,
+                    index_type.clone(),
+                )
+                .unwrap();
+                assert_eq!(compacted.size, n_live);
+                assert_eq!(compacted.n_trees(), index.n_trees());
+                assert_eq!(compacted.get_item_vector(2), index.get_item_vector(4));
+
+                let to_old = |ids: Vec<u64>| -> Vec<u64> {
+                    ids.iter().map(|&id| old_ids[id as usize]).collect()
+                };
+                let nearest_all = compacted.get_nearest(&v1, 10, 10000, true);
+                assert_eq!(to_old(nearest_all.id_list), expected_all.id_list);
+                assert_eq!(nearest_all.distance_list, expected_all.distance_list);
+                if preserve_splits {
+                    let nearest = compacted.get_nearest(&v1, 10, -1, true);
+                    assert_eq!(to_old(nearest.id_list), expected.id_list, "{index_type}");
+                }
+            }
+        }
+
+        let filepath = format!("tests/index.euclidean.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Euclidean).unwrap();
+        let compaction = index.compact(true).unwrap();
+        assert_eq!(
+            compaction.old_ids(),
+            (0..TEST_NODE_COUNT as u64).collect::<Vec<_>>()
+        );
+        let dir = std::env::temp_dir();
+        let index_path = dir.join("compaction_tests.ann");
+        let id_map_path = dir.join("compaction_tests.ids");
+        compaction
+            .save(index_path.to_str().unwrap(), id_map_path.to_str().unwrap())
+            .unwrap();
+        let id_map = std::fs::read_to_string(&id_map_path).unwrap();
+        assert_eq!(id_map.lines().count(), TEST_NODE_COUNT);
+        assert_eq!(id_map.lines().nth(7), Some("7\t7"));
+        let compacted = AnnoyIndex::load(
+            TEST_INDEX_DIM,
+            index_path.to_str().unwrap(),
+            IndexType::Euclidean,
+        )
+        .unwrap();
+        let v0 = index.get_item_vector(0);
+        assert_eq!(
+            format!("{:?}", compacted.get_nearest(&v0, 5, -1, true)),
+            format!("{:?}", index.get_nearest(&v0, 5, -1, true))
+        );
+        std::fs::remove_file(index_path).unwrap();
+        std::fs::remove_file(id_map_path).unwrap();
+
+        static HOLE_INDEX_BYTES: &[u8] = include_bytes!("hole.10d.ann");
+        let index =
+            AnnoyIndex::load_from_buffer(HOLE_INDEX_BYTES.into(), 10, IndexType::Angular).unwrap();
+        // Only item 1000 is live
+        assert!(index.compact(true).is_err());
+
+        // Two far apart clusters end up on both sides of the root split, deleting one of them
+        // leaves a split node of the other as the root
+        let mut builder = AnnoyIndexBuilder::new(TEST_INDEX_DIM, IndexType::Euclidean).unwrap();
+        for item in 0..100 {
+            let offset = if item < 50 { 0.0 } else { 1000.0 };
+            let v: Vec<f32> = (0..TEST_INDEX_DIM)
+                .map(|i| offset + ((item * 7 + i * 13) % 17) as f32)
+                .collect();
+            builder.add_item(item as u64, &v).unwrap();
+        }
+        let mut index = AnnoyIndex::load_from_buffer(
+            builder.build(1).unwrap(),
+            TEST_INDEX_DIM,
+            IndexType::Euclidean,
+        )
+        .unwrap();
+        for id in 0..50 {
+            index.delete_item(id).unwrap();
+        }
+        let compaction = index.compact(true).unwrap();
+        let compacted =
+            AnnoyIndex::load_from_buffer(compaction.buffer, TEST_INDEX_DIM, IndexType::Euclidean)
+                .unwrap();
+        assert_eq!(compacted.size, 50);
+        assert_eq!(compacted.n_trees(), 1);
+        let v = compacted.get_item_vector(7);
+        assert_eq!(compacted.get_nearest(&v, 1, -1, false).id_list, [7]);
+        assert_eq!(compacted.get_nearest(&v, 100, 1000, false).count, 50);
+
+        let custom = AnnoyIndex::load_with_distance(
+            TEST_INDEX_DIM,
+            &filepath,
+            WeightedEuclideanDistance {
+                weights: vec![4.0; TEST_INDEX_DIM],
+            },
+            VectorEncoding::F32,
+        )
+        .unwrap();
+        for preserve_splits in [true, false] {
+            let compacted = AnnoyIndex::load_from_buffer_with_distance(
+                custom.compact(preserve_splits).unwrap().buffer,
+                TEST_INDEX_DIM,
+                WeightedEuclideanDistance {
+                    weights: vec![4.0; TEST_INDEX_DIM],
+                },
+                VectorEncoding::F32,
+            )
+            .unwrap();
+            assert_eq!(compacted.size, TEST_NODE_COUNT);
+            assert_eq!(compacted.get_nearest(&v0, 1, -1, false).id_list, [0]);
+        }
+    }
+
+    #[test]
+    fn builder_tests() {
+        for index_type in [
+            IndexType::Angular,
+            IndexType::Euclidean,
+            IndexType::Manhattan,
+            IndexType::Dot,
+        ] {
+            let filepath = format!("tests/index.{index_type}.{TEST_INDEX_DIM}d.ann");
+            let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, index_type.clone()).unwrap();
+            let mut builder = AnnoyIndexBuilder::new(TEST_INDEX_DIM, index_type.clone()).unwrap();
+            for item in 0..index.size as u64 {
+                builder
+                    .add_item(item, &index.get_item_vector(item))
+                    .unwrap();
+            }
+            let buffer = builder.build(10).unwrap();
+            assert_eq!(buffer, builder.build(10).unwrap());
+            if index_type != IndexType::Dot {
+                // Item nodes are laid out as annoy does. The fixtures were built by an annoy whose
+                // compiler fused the multiply-adds of the norms cached by angular, the builder
+                // doesn't, so those may differ in the last bit.
+                let annoy_buffer = std::fs::read(&filepath).unwrap();
+                let norm = |node: &[u8]| f32::from_ne_bytes(node[4..8].try_into().unwrap());
+                for (node, annoy_node) in buffer
+                    .chunks_exact(index.node_size)
+                    .zip(annoy_buffer.chunks_exact(index.node_size))
+                    .take(index.size)
+                {
+                    if index_type == IndexType::Angular {
+                        assert_eq!(node[..4], annoy_node[..4]);
+                        assert_eq!(node[12..], annoy_node[12..]);
+                        let diff = norm(node).to_bits().abs_diff(norm(annoy_node).to_bits());
+                        assert!(diff <= 1);
+                    } else {
+                        assert_eq!(node, annoy_node);
+                    }
+                }
+            }
+
+            let built =
+                AnnoyIndex::load_from_buffer(buffer, TEST_INDEX_DIM, index_type.clone()).unwrap();
+            assert_eq!(built.size, index.size);
+            assert_eq!(built.get_item_vector(3), index.get_item_vector(3));
+            let v0 = index.get_item_vector(0);
+            let all = index.get_nearest(&v0, index.size, index.size as i32 * 10, true);
+            let all_built = built.get_nearest(&v0, index.size, index.size as i32 * 10, true);
+            assert_eq!(all_built.count, index.size, "{index_type}");
+            if index_type != IndexType::Dot {
+                assert_eq!(built.get_nearest(&v0, 1, -1, false).id_list, [0]);
+            }
+            assert_eq!(
+                all_built.distance_list[..5].round_to(F32_PRECISION),
+                all.distance_list[..5].round_to(F32_PRECISION),
+                "{index_type}"
+            );
+        }
+
+        let mut builder = AnnoyIndexBuilder::new(TEST_INDEX_DIM, IndexType::Euclidean).unwrap();
+        builder.add_item(0, &[1.0; TEST_INDEX_DIM]).unwrap();
+        assert!(builder.build(1).is_err());
+        assert!(builder.build(0).is_err());
+        assert!(builder.add_item(1, &[1.0; 3]).is_err());
+        builder.add_item(2, &[2.0; TEST_INDEX_DIM]).unwrap();
+        let built = AnnoyIndex::load_from_buffer(
+            builder.build(2).unwrap(),
+            TEST_INDEX_DIM,
+            IndexType::Euclidean,
+        )
+        .unwrap();
+        assert_eq!(built.size, 3);
+        assert_eq!(
+            built
+                .get_nearest(&[0.0; TEST_INDEX_DIM], 5, -1, false)
+                .id_list,
+            [0, 2]
+        );
+    }
+
+    // Single-threaded seeded builds are laid out like annoy's, see test_gen/seeded.py.
+    #[test]
+    fn seeded_builder_tests() {
+        for index_type in [
+            IndexType::Angular,
+            IndexType::Euclidean,
+            IndexType::Manhattan,
+            IndexType::Dot,
+        ] {
+            let filepath = format!("tests/index.{index_type}.{TEST_INDEX_DIM}d.ann");
+            let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, index_type.clone()).unwrap();
+            let mut builder = AnnoyIndexBuilder::new(TEST_INDEX_DIM, index_type.clone()).unwrap();
+            builder.set_seed(42);
+            builder.set_n_threads(1);
+            for item in 0..index.size as u64 {
+                builder
+                    .add_item(item, &index.get_item_vector(item))
+                    .unwrap();
+            }
+            let buffer = builder.build(10).unwrap();
+
+            let annoy_buffer =
+                std::fs::read(format!("tests/seeded.{index_type}.{TEST_INDEX_DIM}d.ann")).unwrap();
+            assert_eq!(buffer.len(), annoy_buffer.len(), "{index_type}");
+            let built = AnnoyIndex::load_from_buffer(
+                annoy_buffer.clone(),
+                TEST_INDEX_DIM,
+                index_type.clone(),
+            )
+            .unwrap();
+            let items_len = index.size * index.node_size;
+            let roots_start = buffer.len() - built.n_trees() * index.node_size;
+            assert_eq!(
+                buffer[..items_len],
+                annoy_buffer[..items_len],
+                "{index_type}"
+            );
+            // Split and leaf nodes
+            assert_eq!(
+                buffer[items_len..roots_start],
+                annoy_buffer[items_len..roots_start],
+                "{index_type}"
+            );
+            assert_eq!(
+                buffer[roots_start..],
+                annoy_buffer[roots_start..],
+                "{index_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_builder_tests() {
+        let filepath = format!("tests/index.angular.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Angular).unwrap();
+        let build = |seed: u64, n_threads: usize| {
+            let mut builder = AnnoyIndexBuilder::new(TEST_INDEX_DIM, IndexType::Angular).unwrap();
+            builder.set_seed(seed);
+            builder.set_n_threads(n_threads);
+            for item in 0..index.size as u64 {
+                builder
+                    .add_item(item, &index.get_item_vector(item))
+                    .unwrap();
+            }
+            builder.build(10).unwrap()
+        };
+
+        let single = build(42, 1);
+        let parallel = build(42, 4);
+        assert_eq!(parallel, build(42, 4));
+        assert_ne!(parallel, single);
+        assert_ne!(build(43, 4), parallel);
+        let items_len = index.size * index.node_size;
+        assert_eq!(parallel[..items_len], single[..items_len]);
+
+        let built =
+            AnnoyIndex::load_from_buffer(parallel, TEST_INDEX_DIM, IndexType::Angular).unwrap();
+        assert_eq!(built.size, index.size);
+        assert_eq!(built.n_trees(), 10);
+        for item in 0..index.size as u64 {
+            let nearest = built.get_nearest_to_item(item, 1, -1, false);
+            assert_eq!(nearest.id_list, [item]);
+        }
+    }
+
+    #[test]
+    fn delta_index_tests() {
+        let filepath = format!("tests/index.euclidean.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Euclidean).unwrap();
+        let v0 = index.get_item_vector(0);
+        let expected = index.get_nearest(&v0, 5, -1, true);
+
+        let mut delta = DeltaIndex::new(index);
+        let near_v0: Vec<f32> = v0.iter().map(|x| x + 0.001).collect();
+        let id = delta.add_item(&near_v0).unwrap();
+        assert_eq!(id, TEST_NODE_COUNT as u64);
+        assert_eq!(delta.size(), TEST_NODE_COUNT + 1);
+        assert_eq!(delta.delta_len(), 1);
+        assert!(delta.add_item(&[0.0; 3]).is_err());
+        assert_eq!(delta.get_item_vector(id), near_v0);
+
+        let nearest = delta.get_nearest(&v0, 5, -1, true);
+        assert_eq!(nearest.id_list[..2], [0, id]);
+        assert_eq!(nearest.id_list[2..], expected.id_list[1..4]);
+        assert_eq!(nearest.distance_list[2..], expected.distance_list[1..4]);
+        assert_eq!(delta.get_nearest_to_item(id, 1, -1, false).id_list, [id]);
+        assert_eq!(delta.try_get_item_vector(id), Some(near_v0.clone()));
+        assert_eq!(delta.try_get_item_vector(id + 1), None);
+        assert_eq!(delta.get_nearest_to_item(id + 1, 5, -1, true).count, 0);
+
+        let excluded = HashSet::from([0]);
+        let nearest = delta.get_nearest_excluding(&v0, 2, 1000, false, &excluded);
+        assert_eq!(nearest.id_list, [id, expected.id_list[1]]);
+        let nearest = delta.get_nearest_to_item_excluding(id, 1, -1, false, true, &excluded);
+        assert_eq!(nearest.count, 1);
+        assert_ne!(nearest.id_list[0], id);
+
+        let (with_stats, stats) = delta.get_nearest_with_stats(&v0, 5, -1, true);
+        assert_eq!(
+            with_stats.id_list,
+            delta.get_nearest(&v0, 5, -1, true).id_list
+        );
+        assert_eq!(stats.result_trees.len(), 5);
+        assert!(stats.result_trees[1].is_empty());
+
+        let flushed = AnnoyIndex::load_from_buffer(
+            delta.flush(10).unwrap(),
+            TEST_INDEX_DIM,
+            IndexType::Euclidean,
+        )
+        .unwrap();
+        assert_eq!(flushed.size, TEST_NODE_COUNT + 1);
+        assert_eq!(flushed.get_item_vector(id), near_v0);
+        let nearest_flushed = flushed.get_nearest(&v0, 5, TEST_NODE_COUNT as i32 * 10, false);
+        assert_eq!(nearest_flushed.id_list[..2], [0, id]);
+
+        let weighted = || WeightedEuclideanDistance {
+            weights: vec![4.0; TEST_INDEX_DIM],
+        };
+        let custom = AnnoyIndex::load_with_distance(
+            TEST_INDEX_DIM,
+            &filepath,
+            weighted(),
+            VectorEncoding::F32,
+        )
+        .unwrap();
+        let mut delta = DeltaIndex::new(custom);
+        let id = delta.add_item(&near_v0).unwrap();
+        let nearest = delta.get_nearest(&v0, 2, -1, true);
+        let flushed = AnnoyIndex::load_from_buffer_with_distance(
+            delta.flush(10).unwrap(),
+            TEST_INDEX_DIM,
+            weighted(),
+            VectorEncoding::F32,
+        )
+        .unwrap();
+        let nearest_flushed = flushed.get_nearest(&v0, 2, TEST_NODE_COUNT as i32 * 10, true);
+        assert_eq!(nearest_flushed.id_list, [0, id]);
+        assert_eq!(nearest_flushed.distance_list, nearest.distance_list);
+    }
+
+    #[test]
+    fn dataset_tests() {
+        let filepath = format!("tests/index.euclidean.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Euclidean).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..index.size as u64)
+            .map(|item| index.get_item_vector(item))
+            .collect();
+        let options = ImportOptions::default();
+
+        let mut fvecs = Vec::new();
+        let mut csv = String::from("id,a,b,c,d,e\n");
+        for (item, v) in vectors.iter().enumerate() {
+            fvecs.extend((TEST_INDEX_DIM as i32).to_le_bytes());
+            v.iter().for_each(|x| fvecs.extend(x.to_le_bytes()));
+            let values: Vec<String> = v.iter().map(f32::to_string).collect();
+            csv.push_str(&format!("{},{}\n", item * 2, values.join(",")));
+        }
+        let imported = Vectors::from_bytes(&fvecs, VectorFormat::Fvecs, &options).unwrap();
+        assert_eq!(imported.dimension, TEST_INDEX_DIM);
+        assert_eq!(imported.len(), TEST_NODE_COUNT);
+        assert_eq!(imported.vector(42), vectors[42]);
+        assert!(
+            Vectors::from_bytes(&fvecs[..fvecs.len() - 1], VectorFormat::Fvecs, &options).is_err()
+        );
+
+        let csv_options = ImportOptions {
+            id_column: Some(0),
+            has_header: true,
+        };
+        let from_csv =
+            Vectors::from_bytes(csv.as_bytes(), VectorFormat::Csv, &csv_options).unwrap();
+        assert_eq!(from_csv.ids[..3], [0, 2, 4]);
+        assert_eq!(from_csv.vector(42), vectors[42]);
+        assert!(Vectors::from_bytes(b"1,2\n3\n", VectorFormat::Csv, &options).is_err());
+
+        let mut npy = b"\x93NUMPY\x01\x00".to_vec();
+        let header = format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': ({TEST_NODE_COUNT}, {TEST_INDEX_DIM}), }}"
+        );
+        let header = format!("{header:<118}\n");
+        npy.extend((header.len() as u16).to_le_bytes());
+        npy.extend(header.as_bytes());
+        for v in vectors.iter() {
+            v.iter().for_each(|&x| npy.extend((x as f64).to_le_bytes()));
+        }
+        let imported = Vectors::from_bytes(&npy, VectorFormat::Npy, &options).unwrap();
+        assert_eq!(
+            imported.ids,
+            (0..TEST_NODE_COUNT as u64).collect::<Vec<_>>()
+        );
+        assert_eq!(imported.vector(99), vectors[99]);
+        for shape in [")", "5, 5)", "(", "(18446744073709551615, 2)"] {
+            let mut npy = b"\x93NUMPY\x01\x00".to_vec();
+            let header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {shape}, }}");
+            npy.extend((header.len() as u16).to_le_bytes());
+            npy.extend(header.as_bytes());
+            npy.extend([0; 16]);
+            assert!(Vectors::from_bytes(&npy, VectorFormat::Npy, &options).is_err());
+        }
+
+        let bvecs = [2, 0, 0, 0, 7, 255, 2, 0, 0, 0, 0, 1];
+        let imported = Vectors::from_bytes(&bvecs, VectorFormat::Bvecs, &options).unwrap();
+        assert_eq!(imported.data, [7.0, 255.0, 0.0, 1.0]);
+        for dimension in [-1, i32::MAX] {
+            let mut fvecs = dimension.to_le_bytes().to_vec();
+            fvecs.extend([0; 8]);
+            assert!(Vectors::from_bytes(&fvecs, VectorFormat::Fvecs, &options).is_err());
+        }
+        assert_eq!(
+            VectorFormat::from_path("base.FVECS"),
+            Some(VectorFormat::Fvecs)
+        );
+        assert_eq!(VectorFormat::from_path("base.txt"), None);
+
+        let mut builder = AnnoyIndexBuilder::new(TEST_INDEX_DIM, IndexType::Euclidean).unwrap();
+        from_csv.add_to(&mut builder).unwrap();
+        assert_eq!(builder.n_items(), TEST_NODE_COUNT * 2 - 1);
+
+        // Ground truth of the first 10 items as queries, by brute force
+        let k = 10;
+        let mut ivecs = Vec::new();
+        for v in vectors.iter().take(10) {
+            let nearest = index.get_nearest(v, TEST_NODE_COUNT, -1, true);
+            ivecs.extend((k as i32).to_le_bytes());
+            nearest.id_list[..k]
+                .iter()
+                .for_each(|&id| ivecs.extend((id as i32).to_le_bytes()));
+        }
+        let ground_truth = GroundTruth::from_ivecs_bytes(&ivecs).unwrap();
+        assert_eq!(ground_truth.neighbors.len(), 10);
+        assert_eq!(ground_truth.neighbors[3][0], 3);
+        let queries = Vectors::from_bytes(
+            &fvecs[..10 * (TEST_INDEX_DIM + 1) * 4],
+            VectorFormat::Fvecs,
+            &options,
+        )
+        .unwrap();
+        let search_k = TEST_NODE_COUNT as i32 * 10;
+        let recall = ground_truth
+            .evaluate(&index, &queries, k, search_k)
+            .unwrap();
+        assert_eq!(recall, 1.0);
+        assert_eq!(ground_truth.recall(3, &[3, 1000, 1001], 3), 1.0 / 3.0);
+        assert!(ground_truth
+            .evaluate(&index, &from_csv, k, search_k)
+            .is_err());
+    }
+
+    #[test]
+    fn export_tests() {
+        static HOLE_INDEX_BYTES: &[u8] = include_bytes!("hole.10d.ann");
+        let index =
+            AnnoyIndex::load_from_buffer(HOLE_INDEX_BYTES.into(), 10, IndexType::Angular).unwrap();
+        let vectors = index.item_vectors();
+        assert_eq!(vectors.ids, [1000]);
+        assert_eq!(vectors.vector(0), index.get_item_vector(1000));
+
+        let filepath = format!("tests/index.euclidean.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Euclidean).unwrap();
+        let vectors = index.item_vectors();
+        assert_eq!(vectors.len(), TEST_NODE_COUNT);
+        for format in [VectorFormat::Fvecs, VectorFormat::Npy, VectorFormat::Csv] {
+            for options in [
+                ImportOptions::default(),
+                ImportOptions {
+                    id_column: Some(2),
+                    has_header: true,
+                },
+            ] {
+                let bytes = vectors.to_bytes(format, &options).unwrap();
+                let imported = Vectors::from_bytes(&bytes, format, &options).unwrap();
+                assert_eq!(imported.ids, vectors.ids);
+                assert_eq!(imported.data, vectors.data, "{format} round trip");
+            }
+        }
+        assert!(vectors
+            .to_bytes(VectorFormat::Bvecs, &ImportOptions::default())
+            .is_err());
+        let npy = vectors
+            .to_bytes(VectorFormat::Npy, &ImportOptions::default())
+            .unwrap();
+        assert_eq!((npy.len() - TEST_NODE_COUNT * TEST_INDEX_DIM * 4) % 64, 0);
+
+        let mut json = Vec::new();
+        index.write_tree_json(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["index_type"], "euclidean");
+        let trees = json["trees"].as_array().unwrap();
+        assert_eq!(trees.len(), index.n_trees());
+        fn collect_items(node: &serde_json::Value, items: &mut Vec<u64>) {
+            if let Some(item) = node.get("item") {
+                items.push(item.as_u64().unwrap());
+            } else if let Some(leaf) = node.get("items") {
+                items.extend(
+                    leaf.as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|id| id.as_u64().unwrap()),
+                );
+            } else {
+                assert_eq!(node["normal"].as_array().unwrap().len(), TEST_INDEX_DIM);
+                assert!(node["bias"].is_number());
+                node["children"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .for_each(|child| collect_items(child, items));
+            }
+        }
+        for tree in trees {
+            let mut items = Vec::new();
+            collect_items(tree, &mut items);
+            items.sort_unstable();
+            assert_eq!(items, (0..TEST_NODE_COUNT as u64).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn metadata_tests() {
+        let filepath = format!("tests/index.euclidean.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Euclidean).unwrap();
+        assert!(index.metadata().is_none());
+        let mut builder = AnnoyIndexBuilder::new(TEST_INDEX_DIM, IndexType::Euclidean).unwrap();
+        builder.set_seed(42);
+        builder.set_n_threads(2);
+        for item in 0..index.size as u64 {
+            builder
+                .add_item(item, &index.get_item_vector(item))
+                .unwrap();
+        }
+        let plain = builder.build(10).unwrap();
+        builder.insert_metadata("model", "test-embeddings");
+        let buffer = builder.build(10).unwrap();
+        assert_eq!(buffer[..plain.len()], plain[..]);
+
+        let metadata = IndexMetadata::find(&buffer).unwrap().unwrap();
+        assert_eq!(metadata.index_type, IndexType::Euclidean);
+        assert_eq!(metadata.vector_encoding, VectorEncoding::F32);
+        assert_eq!(metadata.dimension, TEST_INDEX_DIM);
+        assert_eq!(metadata.n_items, index.size);
+        assert_eq!(metadata.n_trees, 10);
+        assert_eq!(
+            metadata.build_params,
+            Some(BuildParams {
+                seed: 42,
+                n_threads: 2
+            })
+        );
+        assert_eq!(metadata.user["model"], "test-embeddings");
+        assert!(IndexMetadata::find(&plain).unwrap().is_none());
+
+        let built =
+            AnnoyIndex::load_from_buffer(buffer.clone(), TEST_INDEX_DIM, IndexType::Euclidean)
+                .unwrap();
+        assert_eq!(built.metadata(), Some(&metadata));
+        assert_eq!(built.size, index.size);
+        assert_eq!(built.n_trees(), 10);
+        let plain_built =
+            AnnoyIndex::load_from_buffer(plain, TEST_INDEX_DIM, IndexType::Euclidean).unwrap();
+        let v0 = index.get_item_vector(0);
+        assert_eq!(
+            built.get_nearest(&v0, 10, -1, true).id_list,
+            plain_built.get_nearest(&v0, 10, -1, true).id_list
+        );
+        // Described from the plain file, without build parameters
+        let described = plain_built.describe();
+        assert_eq!(described.checksum, metadata.checksum);
+        assert_eq!(described.n_items, index.size);
+        assert_eq!(described.n_trees, 10);
+        assert!(described.build_params.is_none());
+
+        assert!(AnnoyIndex::load_from_buffer(
+            buffer.clone(),
+            TEST_INDEX_DIM + 1,
+            IndexType::Euclidean
+        )
+        .is_err());
+        assert!(
+            AnnoyIndex::load_from_buffer(buffer.clone(), TEST_INDEX_DIM, IndexType::Manhattan)
+                .is_err()
+        );
+        let described = AnnoyIndex::load_described_from_buffer(buffer.clone()).unwrap();
+        assert_eq!(described.dimension, TEST_INDEX_DIM);
+        assert_eq!(described.index_type, IndexType::Euclidean);
+        let mut newer = buffer.clone();
+        // Footers of unknown versions are rejected
+        let footer_start = newer.len() - metadata.to_bytes().len();
+        newer[footer_start] = 9;
+        assert!(AnnoyIndex::load_from_buffer(newer, TEST_INDEX_DIM, IndexType::Euclidean).is_err());
+
+        // Annotating a plain annoy file, then replacing its footer
+        let path = std::env::temp_dir().join(format!("annoy-metadata-{}.ann", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::copy(&filepath, path).unwrap();
+        let mut annotated = index.describe();
+        annotated.user.insert("source".into(), "annoy".into());
+        annotated.write(path).unwrap();
+        assert_eq!(IndexMetadata::read(path).unwrap(), Some(annotated.clone()));
+        annotated.user.clear();
+        annotated.write(path).unwrap();
+        assert_eq!(IndexMetadata::read(path).unwrap(), Some(annotated));
+        let loaded = AnnoyIndex::load_described(path).unwrap();
+        assert_eq!(loaded.size, index.size);
+        assert_eq!(loaded.node_size, index.node_size);
+        assert_eq!(
+            loaded.get_nearest(&v0, 10, -1, true).id_list,
+            index.get_nearest(&v0, 10, -1, true).id_list
+        );
+        let file_len = std::fs::metadata(path).unwrap().len() as usize;
+        let original_len = std::fs::metadata(&filepath).unwrap().len() as usize;
+        assert_eq!(file_len, original_len + index.describe().to_bytes().len());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn integrity_tests() {
+        const DIMENSION: usize = 64;
+        let mut builder = AnnoyIndexBuilder::new(DIMENSION, IndexType::Euclidean).unwrap();
+        builder.set_write_metadata(true);
+        for item in 0..2000_u64 {
+            let v: Vec<f32> = (0..DIMENSION)
+                .map(|j| ((item * 31 + j as u64 * 17) % 101) as f32)
+                .collect();
+            builder.add_item(item, &v).unwrap();
+        }
+        let buffer = builder.build(10).unwrap();
+        let metadata = IndexMetadata::find(&buffer).unwrap().unwrap();
+        assert!(metadata.region_checksums.len() > 4);
+        assert!(IndexMetadata::verify_file(&buffer).unwrap().is_empty());
+        let node_size = metadata.node_size().unwrap();
+
+        // A flipped bit in an item vector is only found by verification
+        let mut corrupt = buffer.clone();
+        let item = 900;
+        corrupt[item * node_size + 20] ^= 1;
+        let corrupt_ranges = IndexMetadata::verify_file(&corrupt).unwrap();
+        assert_eq!(corrupt_ranges.len(), 1);
+        assert!(corrupt_ranges[0].contains(&(item * node_size + 20)));
+        let index = AnnoyIndex::load_from_buffer(corrupt, DIMENSION, IndexType::Euclidean).unwrap();
+        let corrupt_nodes = index.verify().unwrap();
+        assert_eq!(corrupt_nodes.len(), 1);
+        assert!(corrupt_nodes[0].contains(&item));
+        assert!(corrupt_nodes[0].len() < index.size / 4);
+
+        // Corrupt roots are rejected by loaders but still pinpointed
+        let mut corrupt = buffer.clone();
+        let nodes_size = buffer.len() - metadata.to_bytes().len();
+        corrupt[nodes_size - node_size] ^= 1;
+        assert!(
+            AnnoyIndex::load_from_buffer(corrupt.clone(), DIMENSION, IndexType::Euclidean).is_err()
+        );
+        let corrupt_ranges = IndexMetadata::verify_file(&corrupt).unwrap();
+        assert_eq!(corrupt_ranges.len(), 1);
+        assert_eq!(corrupt_ranges[0].end, nodes_size);
+
+        let path = std::env::temp_dir().join(format!("annoy-integrity-{}.ann", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, &buffer).unwrap();
+        let index = AnnoyIndex::load_verified(DIMENSION, path, IndexType::Euclidean).unwrap();
+        assert!(index.verify().unwrap().is_empty());
+        drop(index);
+        // Torn downloads lose the footer
+        std::fs::write(path, &buffer[..buffer.len() / 2]).unwrap();
+        assert!(AnnoyIndex::load_verified(DIMENSION, path, IndexType::Euclidean).is_err());
+        assert!(IndexMetadata::verify_file(&buffer[..buffer.len() / 2]).is_err());
+        // Without its footer, a file is served like a plain annoy file unless verified
+        std::fs::write(path, &buffer[..nodes_size]).unwrap();
+        let index = AnnoyIndex::load(DIMENSION, path, IndexType::Euclidean).unwrap();
+        assert!(index.metadata().is_none());
+        drop(index);
+        assert!(AnnoyIndex::load_verified(DIMENSION, path, IndexType::Euclidean).is_err());
+        let mut corrupt = buffer.clone();
+        corrupt[item * node_size + 20] ^= 1;
+        std::fs::write(path, &corrupt).unwrap();
+        assert!(AnnoyIndex::load_verified(DIMENSION, path, IndexType::Euclidean).is_err());
+        std::fs::remove_file(path).unwrap();
+
+        let filepath = format!("tests/index.euclidean.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Euclidean).unwrap();
+        assert!(index.verify().is_err());
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn arrow_export_tests() {
+        use arrow::array::{AsArray, UInt64Array};
+        use arrow::datatypes::{Float32Type, UInt64Type};
+
+        let filepath = format!("tests/index.dot.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Dot).unwrap();
+        let vectors = index.item_vectors();
+        let ipc = vectors.to_arrow_ipc().unwrap();
+        let mut reader =
+            arrow::ipc::reader::FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = reader.next().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), TEST_NODE_COUNT);
+        let ids = batch.column(0).as_primitive::<UInt64Type>();
+        assert_eq!(ids, &UInt64Array::from(vectors.ids.clone()));
+        let vector = batch.column(1).as_fixed_size_list().value(7);
+        assert_eq!(
+            vector.as_primitive::<Float32Type>().values().to_vec(),
+            index.get_item_vector(7)
+        );
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn arrow_tests() {
+        use arrow::array::{
+            Array, AsArray, FixedSizeListArray, Float32Array, Float64Array, ListArray, RecordBatch,
+            UInt64Array,
+        };
+        use arrow::datatypes::{DataType, Field, Float32Type, Float64Type, UInt32Type, UInt64Type};
+        use std::sync::Arc;
+
+        let filepath = format!("tests/index.angular.{TEST_INDEX_DIM}d.ann");
+        let index = AnnoyIndex::load(TEST_INDEX_DIM, &filepath, IndexType::Angular).unwrap();
+        let vectors = index.item_vectors();
+        let queries = FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+            (0..3).map(|item| Some(index.get_item_vector(item).into_iter().map(Some))),
+            TEST_INDEX_DIM as i32,
+        );
+        let batch = index.get_nearest_arrow(&queries, 5, -1, true).unwrap();
+        assert_eq!(batch.num_rows(), 15);
+        let query_idx = batch.column(0).as_primitive::<UInt32Type>();
+        let rank = batch.column(1).as_primitive::<UInt32Type>();
+        let ids = batch.column(2).as_primitive::<UInt64Type>();
+        let distances = batch.column(3).as_primitive::<Float32Type>();
+        for query in 0..3 {
+            let expected = index.get_nearest_to_item(query, 5, -1, true);
+            for r in 0..5 {
+                let row = query as usize * 5 + r;
+                assert_eq!(query_idx.value(row), query as u32);
+                assert_eq!(rank.value(row), r as u32);
+                assert_eq!(ids.value(row), expected.id_list[r]);
+                assert_eq!(distances.value(row), expected.distance_list[r]);
+            }
+        }
+        let batch = index.get_nearest_arrow(&queries, 5, -1, false).unwrap();
+        assert_eq!(batch.column(3).null_count(), 15);
+        let wrong_dimension =
+            FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>([Some([Some(0.0); 3])], 3);
+        assert!(index
+            .get_nearest_arrow(&wrong_dimension, 5, -1, false)
+            .is_err());
+
+        let lists = ListArray::from_iter_primitive::<Float64Type, _, _>([
+            Some(vec![Some(1.0), Some(2.0)]),
+            Some(vec![Some(3.0), Some(4.0)]),
+        ]);
+        let from_lists = Vectors::from_arrow(&lists).unwrap();
+        assert_eq!(
+            (from_lists.dimension, from_lists.data),
+            (2, vec![1.0, 2.0, 3.0, 4.0])
+        );
+        let ragged = ListArray::from_iter_primitive::<Float64Type, _, _>([
+            Some(vec![Some(1.0), Some(2.0)]),
+            Some(vec![Some(3.0)]),
+        ]);
+        assert!(Vectors::from_arrow(&ragged).is_err());
+        assert!(Vectors::from_arrow(&Float64Array::from(vec![1.0])).is_err());
+
+        // Build from a Parquet embedding column, with ids shifted by 10
+        let embedding = vectors.to_record_batch().unwrap().column(1).clone();
+        let ids = UInt64Array::from_iter_values(vectors.ids.iter().map(|id| id + 10));
+        let batch = RecordBatch::try_from_iter([
+            ("item_id", Arc::new(ids) as Arc<dyn Array>),
+            ("embedding", embedding),
+            (
+                "other",
+                Arc::new(Float32Array::from(vec![0.0; vectors.len()])),
+            ),
+        ])
+        .unwrap();
+        assert_eq!(
+            batch.schema().field(1).data_type(),
+            &DataType::FixedSizeList(
+                Arc::new(Field::new_list_field(DataType::Float32, false)),
+                TEST_INDEX_DIM as i32
+            )
+        );
+        let path = std::env::temp_dir().join(format!("annoy-arrow-{}.parquet", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut writer = parquet::arrow::ArrowWriter::try_new(
+            std::fs::File::create(path).unwrap(),
+            batch.schema(),
+            None,
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let imported = Vectors::read_parquet(path, "embedding", Some("item_id")).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(imported.ids[..2], [10, 11]);
+        assert_eq!(imported.data, vectors.data);
+        assert!(Vectors::from_record_batch(&batch, "missing", None).is_err());
+        let mut builder = AnnoyIndexBuilder::new(TEST_INDEX_DIM, IndexType::Angular).unwrap();
+        imported.add_to(&mut builder).unwrap();
+        let built = AnnoyIndex::load_from_buffer(
+            builder.build(10).unwrap(),
+            TEST_INDEX_DIM,
+            IndexType::Angular,
+        )
+        .unwrap();
+        assert_eq!(built.size, TEST_NODE_COUNT + 10);
+        assert_eq!(built.get_nearest_to_item(15, 1, -1, false).id_list, [15]);
+        assert_eq!(built.get_item_vector(15), index.get_item_vector(5));
+    }
+
     pub trait RoundToVec<T> {
         fn round_to(&self, n: usize) -> Vec<T>;
     }
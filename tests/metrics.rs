@@ -0,0 +1,53 @@
+//! Its own test binary, so that the process wide recorder doesn't see other tests.
+
+#[cfg(test)]
+#[cfg(all(feature = "metrics", not(target_arch = "wasm32")))]
+mod tests {
+    use annoy_rs::*;
+
+    const DIMENSION: usize = 5;
+
+    #[test]
+    fn metrics_recorder_tests() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct TestRecorder {
+            loads: Mutex<Vec<LoadMetrics>>,
+            searches: Mutex<Vec<SearchMetrics>>,
+        }
+
+        impl MetricsRecorder for &'static TestRecorder {
+            fn record_load(&self, metrics: &LoadMetrics) {
+                self.loads.lock().unwrap().push(metrics.clone());
+            }
+
+            fn record_search(&self, metrics: &SearchMetrics) {
+                self.searches.lock().unwrap().push(metrics.clone());
+            }
+        }
+
+        let recorder: &'static TestRecorder = Box::leak(Box::default());
+        set_metrics_recorder(recorder).unwrap();
+        assert!(set_metrics_recorder(recorder).is_err());
+
+        let filepath = format!("tests/index.dot.{DIMENSION}d.ann");
+        let index = AnnoyIndex::load_into_mem(DIMENSION, &filepath, IndexType::Dot).unwrap();
+        let (nearest, stats) =
+            index.get_nearest_with_stats(&index.get_item_vector(0), 7, 4321, false);
+
+        let file_size = std::fs::metadata(&filepath).unwrap().len() as usize;
+        let loads = recorder.loads.lock().unwrap();
+        assert_eq!(loads.len(), 1);
+        assert_eq!(loads[0].storage_kind, StorageKind::Buffer);
+        assert_eq!(loads[0].index_size, file_size);
+        let searches = recorder.searches.lock().unwrap();
+        assert_eq!(searches.len(), 1);
+        let search = &searches[0];
+        assert_eq!(search.search_k, 4321);
+        assert_eq!(search.n_results, 7);
+        assert_eq!(search.candidate_count, stats.candidate_count);
+        assert_eq!(search.distance_computations, stats.distance_computations);
+        assert_eq!(search.is_truncated, nearest.is_truncated);
+    }
+}
@@ -0,0 +1,266 @@
+#[cfg(test)]
+#[cfg(feature = "server")]
+mod tests {
+    use annoy_rs::server::{grpc::*, *};
+    use std::path::PathBuf;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    fn write_config(name: &str, indexes: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("annoy-server-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = String::new();
+        for (name, index_type) in indexes {
+            let path = std::fs::canonicalize(format!("tests/index.{index_type}.5d.ann")).unwrap();
+            config.push_str(&format!(
+                "[[indexes]]\nname = \"{name}\"\npath = {:?}\ndimension = 5\nindex_type = \"{index_type}\"\n",
+                path.to_str().unwrap()
+            ));
+        }
+        let config_path = dir.join("server.toml");
+        std::fs::write(&config_path, config).unwrap();
+        config_path
+    }
+
+    async fn http_request(addr: &str, method: &str, path: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split("\r\n\r\n").nth(1).unwrap().to_owned();
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn http_tests() {
+        metrics::install().unwrap();
+        let config = ServerConfig::from_file(write_config("http", &[("a", "angular")])).unwrap();
+        let registry = IndexRegistry::load(&config).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { axum::serve(listener, http::router(registry)).await });
+
+        let (status, body) = http_request(&addr, "GET", "/indexes", "").await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            r#"[{"name":"a","dimension":5,"size":100,"index_type":"angular"}]"#
+        );
+
+        let (status, body) = http_request(&addr, "GET", "/indexes/a/items/3", "").await;
+        assert_eq!(status, 200);
+        assert!(body.starts_with(r#"{"vector":[-0.38"#), "{body}");
+
+        let (status, body) = http_request(
+            &addr,
+            "POST",
+            "/indexes/a/nearest_to_item",
+            r#"{"item":0,"n_results":5,"include_distance":true}"#,
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert!(
+            body.starts_with(r#"{"ids":[0,4,37,61,29],"distances":[0.0,"#),
+            "{body}"
+        );
+
+        let query = r#"[-0.38,-0.14,0.8,-0.7,0.5]"#;
+        let (status, body) = http_request(
+            &addr,
+            "POST",
+            "/indexes/a/nearest_batch",
+            &format!(r#"{{"vectors":[{query},{query}],"n_results":3}}"#),
+        )
+        .await;
+        assert_eq!(status, 200);
+        let (_, single) = http_request(
+            &addr,
+            "POST",
+            "/indexes/a/nearest",
+            &format!(r#"{{"vector":{query},"n_results":3}}"#),
+        )
+        .await;
+        assert_eq!(body, format!(r#"{{"results":[{single},{single}]}}"#));
+
+        let (status, _) = http_request(&addr, "GET", "/indexes/b/items/0", "").await;
+        assert_eq!(status, 404);
+        let (status, _) = http_request(&addr, "GET", "/indexes/a/items/100", "").await;
+        assert_eq!(status, 400);
+        let (status, _) = http_request(
+            &addr,
+            "POST",
+            "/indexes/a/nearest",
+            r#"{"vector":[1.0],"n_results":3}"#,
+        )
+        .await;
+        assert_eq!(status, 400);
+        let (status, body) = http_request(
+            &addr,
+            "POST",
+            "/indexes/a/nearest_to_item",
+            &format!(r#"{{"item":0,"n_results":3,"search_k":{}}}"#, i32::MAX),
+        )
+        .await;
+        assert_eq!(status, 400);
+        assert!(body.contains("search_k"), "{body}");
+        let (status, _) = http_request(
+            &addr,
+            "POST",
+            "/indexes/a/nearest_batch",
+            &format!(r#"{{"vectors":[{query}],"n_results":1001}}"#),
+        )
+        .await;
+        assert_eq!(status, 400);
+
+        let (status, body) = http_request(&addr, "GET", "/metrics", "").await;
+        assert_eq!(status, 200);
+        assert!(
+            body.contains("# TYPE annoy_searches_total counter\n"),
+            "{body}"
+        );
+        assert!(!body.contains("\nannoy_searches_total 0\n"), "{body}");
+        assert!(
+            body.contains("annoy_search_k_bucket{le=\"+Inf\"}"),
+            "{body}"
+        );
+    }
+
+    #[tokio::test]
+    async fn grpc_tests() {
+        let config = ServerConfig::from_file(write_config("grpc", &[("e", "euclidean")])).unwrap();
+        let registry = IndexRegistry::load(&config).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service(registry))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+        let mut client = AnnoyClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+
+        let item = client
+            .get_item_vector(ItemVectorRequest {
+                index: "e".into(),
+                item: 0,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(item.vector.len(), 5);
+
+        let nearest = client
+            .get_nearest(NearestRequest {
+                index: "e".into(),
+                vector: item.vector.clone(),
+                n_results: 5,
+                search_k: None,
+                include_distance: true,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(nearest.ids[0], 0);
+        assert_eq!(nearest.distances[0], 0.0);
+
+        let to_item = client
+            .get_nearest_to_item(NearestToItemRequest {
+                index: "e".into(),
+                item: 0,
+                n_results: 5,
+                search_k: None,
+                include_distance: false,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(to_item.ids, nearest.ids);
+        assert!(to_item.distances.is_empty());
+
+        let batch = client
+            .get_nearest_batch(NearestBatchRequest {
+                index: "e".into(),
+                vectors: vec![item.clone(), item],
+                n_results: 5,
+                search_k: None,
+                include_distance: true,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(batch.results, vec![nearest.clone(), nearest]);
+
+        let status = client
+            .get_item_vector(ItemVectorRequest {
+                index: "x".into(),
+                item: 0,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        let status = client
+            .get_item_vector(ItemVectorRequest {
+                index: "e".into(),
+                item: 100,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = client
+            .get_nearest_to_item(NearestToItemRequest {
+                index: "e".into(),
+                item: 0,
+                n_results: 5,
+                search_k: Some(i32::MAX),
+                include_distance: false,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = client
+            .get_nearest_to_item(NearestToItemRequest {
+                index: "e".into(),
+                item: 0,
+                n_results: u32::MAX,
+                search_k: None,
+                include_distance: false,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn reload_tests() {
+        let config_path = write_config("reload", &[("a", "angular")]);
+        let registry =
+            IndexRegistry::load(&ServerConfig::from_file(&config_path).unwrap()).unwrap();
+        assert_eq!(registry.names(), vec!["a"]);
+
+        write_config("reload", &[("a", "angular"), ("d", "dot")]);
+        registry
+            .reload(&ServerConfig::from_file(&config_path).unwrap())
+            .unwrap();
+        assert_eq!(registry.names(), vec!["a", "d"]);
+
+        // A broken config keeps the previous indexes.
+        std::fs::write(
+            &config_path,
+            "[[indexes]]\nname = \"m\"\npath = \"missing.ann\"\ndimension = 5\nindex_type = \"manhattan\"\n",
+        )
+        .unwrap();
+        assert!(registry
+            .reload(&ServerConfig::from_file(&config_path).unwrap())
+            .is_err());
+        assert_eq!(registry.names(), vec!["a", "d"]);
+        assert!(registry.get("d").is_some());
+    }
+}
@@ -1,12 +1,8 @@
 #[cfg(test)]
 #[cfg(target_arch = "wasm32")]
 mod tests {
-    use annoy_rs::{
-        wasm_exports::{SearchResultJs, *},
-        IndexType,
-    };
-    use js_sys::{Array, Uint8Array};
-    use wasm_bindgen::prelude::*;
+    use annoy_rs::{wasm_exports::*, IndexType};
+    use js_sys::Uint8Array;
     use wasm_bindgen_test::*;
 
     static HOLE_INDEX_BYTES: &[u8] = include_bytes!("hole.10d.ann");
@@ -18,7 +14,7 @@ mod tests {
         let index = load_index(&u8a, 10, IndexType::Angular).unwrap();
         assert_eq!(index.dimension, 10, "Wrong dimension {}", index.dimension);
         assert_eq!(index.size, 1001, "Wrong size {}", index.size);
-        let v1 = vec![
+        let v1: Vec<f32> = vec![
             0.10471842,
             0.55223828,
             0.44094049,
@@ -30,14 +26,31 @@ mod tests {
             0.05733591,
             1.35356555,
         ];
-        let v1_array = Array::new();
-        for v in v1 {
-            v1_array.push(&JsValue::from_f64(v as f64));
+        let nearest = index.get_nearest(&v1, 100, -1, true).unwrap();
+        assert_eq!(nearest.count(), 1);
+        assert_eq!(nearest.ids(), vec![1000]);
+        assert_eq!(nearest.distances().unwrap(), vec![1.212572]);
+
+        let queries = [v1.clone(), v1].concat();
+        let batch = index.get_nearest_batch(&queries, 2, -1, false).unwrap();
+        assert_eq!(batch.counts(), vec![1, 1]);
+        assert_eq!(batch.ids(), vec![1000, u32::MAX, 1000, u32::MAX]);
+        assert!(batch.distances().is_none());
+        assert!(index
+            .get_nearest_batch(&queries[1..], 2, -1, false)
+            .is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn progressive_load_tests() {
+        let mut loader = IndexLoaderJs::new(Some(HOLE_INDEX_BYTES.len() as u32));
+        for chunk in HOLE_INDEX_BYTES.chunks(1000) {
+            let u8a = Uint8Array::new_with_length(chunk.len() as u32);
+            u8a.copy_from(chunk);
+            loader.push(&u8a);
         }
-        let nearest = index.get_nearest(v1_array, 100, -1, true).unwrap();
-        assert_eq!(nearest.length(), 1);
-        let r0: SearchResultJs = serde_wasm_bindgen::from_value(nearest.at(0)).unwrap();
-        assert_eq!(r0.id, 1000);
-        assert_eq!(r0.distance.unwrap_or_default(), 1.212572);
+        let index = loader.finish(10, IndexType::Angular).unwrap();
+        assert_eq!(index.size, 1001, "Wrong size {}", index.size);
+        assert_eq!(index.get_item_vector(1000).unwrap().len(), 10);
     }
 }